@@ -0,0 +1,506 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use crate::paywall_config::{
+    ConfigFormat, ConfigLoader, ConfigTestSuite, DocumentAndPath, InMemoryPaywallEventSink,
+    LATEST_CONFIG_VERSION, PaywallConfigV1, config_json_schema, migrate_yaml, yaml_custom_tags,
+};
+
+const USAGE: &str = "Usage:
+  rustwall check <paywall.yml>...
+  rustwall test <paywall.yml> <cases.yml> [--junit <report.xml>]
+  rustwall explain <paywall.yml> --path <url path> (--html | --markdown | --json) <file>
+  rustwall migrate <paywall.yml> [--write]
+  rustwall schema [--custom-tags]";
+
+/// Run the command line interface with the arguments after the program name
+pub fn run<W: Write, E: Write>(args: &[String], out: &mut W, err: &mut E) -> ExitCode {
+    match args.split_first() {
+        Some((command, files)) if command == "check" && !files.is_empty() => check(files, out, err),
+        Some((command, rest)) if command == "test" => match rest {
+            [config, cases] => test(config, cases, None, out, err),
+            [config, cases, flag, junit] if flag == "--junit" => {
+                test(config, cases, Some(junit), out, err)
+            }
+            _ => usage(err),
+        },
+        Some((command, [config, options @ ..])) if command == "explain" => {
+            explain(config, options, out, err)
+        }
+        Some((command, rest)) if command == "migrate" => match rest {
+            [config] => migrate(config, false, out, err),
+            [config, flag] if flag == "--write" => migrate(config, true, out, err),
+            _ => usage(err),
+        },
+        Some((command, rest)) if command == "schema" => match rest {
+            [] => schema(false, out),
+            [flag] if flag == "--custom-tags" => schema(true, out),
+            _ => usage(err),
+        },
+        _ => usage(err),
+    }
+}
+
+/// Loader of a config with its includes, and the overlay of the environment in `RUSTWALL_ENV`
+fn config_loader(config_file: &str) -> ConfigLoader {
+    let loader = ConfigLoader::new(Path::new(config_file));
+    match std::env::var("RUSTWALL_ENV") {
+        Ok(environment) => loader.with_environment(&environment),
+        Err(_) => loader,
+    }
+}
+
+fn load_config<E: Write>(config_file: &str, err: &mut E) -> Option<PaywallConfigV1> {
+    match config_loader(config_file).load() {
+        Ok(mut config) => {
+            // error events are part of the output, not printed separately
+            config.set_event_sink(Arc::new(InMemoryPaywallEventSink::default()));
+            Some(config)
+        }
+        Err(e) => {
+            let _ = writeln!(err, "error: {}", e);
+            None
+        }
+    }
+}
+
+fn usage<E: Write>(err: &mut E) -> ExitCode {
+    let _ = writeln!(err, "{}", USAGE);
+    ExitCode::from(2)
+}
+
+/// Validate every config with its includes and environment overlay, print each problem as
+/// `file:line:column: error: message` with the file it is in and fail if any config has a
+/// problem
+fn check<W: Write, E: Write>(files: &[String], out: &mut W, err: &mut E) -> ExitCode {
+    let mut has_errors = false;
+
+    for file in files {
+        let issues = config_loader(file).validate();
+        if issues.is_empty() {
+            let _ = writeln!(out, "{}: OK", file);
+        }
+        for issue in &issues {
+            let source = issue
+                .file
+                .as_ref()
+                .map_or(file.clone(), |f| f.display().to_string());
+            let _ = writeln!(
+                err,
+                "{}:{}:{}: error: {}",
+                source, issue.line, issue.column, issue.message
+            );
+        }
+        has_errors |= !issues.is_empty();
+    }
+
+    if has_errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Evaluate the test cases against the config, print the report and optionally write
+/// it as JUnit XML; fails if any case fails
+fn test<W: Write, E: Write>(
+    config_file: &str,
+    cases_file: &str,
+    junit_file: Option<&String>,
+    out: &mut W,
+    err: &mut E,
+) -> ExitCode {
+    let Some(config) = load_config(config_file, err) else {
+        return ExitCode::FAILURE;
+    };
+
+    let suite = match ConfigTestSuite::from_file(Path::new(cases_file)) {
+        Ok(suite) => suite,
+        Err(e) => {
+            let _ = writeln!(err, "{}: error: {}", cases_file, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let report = suite.run(&config);
+    let _ = writeln!(out, "{}", report);
+
+    if let Some(junit_file) = junit_file
+        && let Err(e) = fs::write(junit_file, report.to_junit_xml(config_file))
+    {
+        let _ = writeln!(err, "{}: error: {}", junit_file, e);
+        return ExitCode::FAILURE;
+    }
+
+    if report.is_success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Print the [decision trace](crate::paywall_config::DecisionTrace) of one document
+fn explain<W: Write, E: Write>(
+    config_file: &str,
+    options: &[String],
+    out: &mut W,
+    err: &mut E,
+) -> ExitCode {
+    let mut path = None;
+    let mut document = None;
+    for option in options.chunks(2) {
+        match option {
+            [flag, value] if flag == "--path" => path = Some(value),
+            [flag, value] if ["--html", "--markdown", "--json"].contains(&flag.as_str()) => {
+                document = Some((flag.as_str(), value))
+            }
+            _ => return usage(err),
+        }
+    }
+    let (Some(path), Some((doc_type, doc_file))) = (path, document) else {
+        return usage(err);
+    };
+
+    let Some(config) = load_config(config_file, err) else {
+        return ExitCode::FAILURE;
+    };
+    let content = match fs::read_to_string(doc_file) {
+        Ok(content) => content,
+        Err(e) => {
+            let _ = writeln!(err, "{}: error: {}", doc_file, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let doc_and_path = match doc_type {
+        "--html" => DocumentAndPath::new_from_html_and_path_str(&content, path),
+        "--markdown" => DocumentAndPath::new_from_markdown_and_path_str(&content, path),
+        _ => DocumentAndPath::new_from_json_and_path_str(&content, path),
+    };
+
+    match doc_and_path {
+        Ok(doc_and_path) => {
+            let doc_and_path = config.with_currency_selection(&doc_and_path);
+            let _ = writeln!(out, "{}", config.explain(&doc_and_path));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            let _ = writeln!(err, "{}: error: {}", doc_file, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Rewrite a config to the latest schema version, printing it or with `--write` replacing
+/// the file
+fn migrate<W: Write, E: Write>(
+    config_file: &str,
+    write: bool,
+    out: &mut W,
+    err: &mut E,
+) -> ExitCode {
+    if ConfigFormat::from_path(Path::new(config_file)) != ConfigFormat::Yaml {
+        let _ = writeln!(
+            err,
+            "{}: error: only YAML configs can be migrated",
+            config_file
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let migration = match fs::read_to_string(config_file)
+        .map_err(|e| e.to_string())
+        .and_then(|config_yml| migrate_yaml(&config_yml).map_err(|e| e.to_string()))
+    {
+        Ok(migration) => migration,
+        Err(e) => {
+            let _ = writeln!(err, "{}: error: {}", config_file, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if !migration.comments_preserved {
+        let _ = writeln!(
+            err,
+            "{}: warning: config was re-serialized, comments and formatting are lost",
+            config_file
+        );
+    }
+
+    if !write {
+        let _ = write!(out, "{}", migration.config_yml);
+        return ExitCode::SUCCESS;
+    }
+    if migration.from_version == LATEST_CONFIG_VERSION {
+        let _ = writeln!(
+            out,
+            "{}: already version {}",
+            config_file, LATEST_CONFIG_VERSION
+        );
+        return ExitCode::SUCCESS;
+    }
+
+    match fs::write(config_file, &migration.config_yml) {
+        Ok(()) => {
+            let _ = writeln!(
+                out,
+                "{}: migrated from version {} to {}",
+                config_file, migration.from_version, LATEST_CONFIG_VERSION
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            let _ = writeln!(err, "{}: error: {}", config_file, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Print the JSON Schema of configs, or with `--custom-tags` the tags to declare in the
+/// `yaml.customTags` setting of VS Code
+fn schema<W: Write>(custom_tags: bool, out: &mut W) -> ExitCode {
+    let schema = if custom_tags {
+        serde_json::to_string_pretty(&yaml_custom_tags())
+    } else {
+        serde_json::to_string_pretty(&config_json_schema())
+    };
+    let _ = writeln!(out, "{}", schema.unwrap_or_default());
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(args: &[&str]) -> (ExitCode, String, String) {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run(&args, &mut out, &mut err);
+
+        (
+            code,
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    fn write_file(name: &str, content: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("rustwall-cli-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_check_valid_config() {
+        let file = write_file(
+            "valid.yml",
+            "paths:\n  - paywall_conditions: []\n    price_source: !Hard $1.00\n",
+        );
+
+        let (code, out, err) = run_with(&["check", &file]);
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(out, format!("{}: OK\n", file));
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_located_errors() {
+        let file = write_file(
+            "invalid.yml",
+            "paths:\n  - id: a\n    paywall_conditions: []\n    price_source: !Hard $1.00\n  - id: a\n    paywall_conditions: []\n    price_source: !Hard $1.00\n",
+        );
+
+        let (code, out, err) = run_with(&["check", &file]);
+
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(out.is_empty());
+        assert!(
+            err.starts_with(&format!("{}:5:9: error: Duplicate", file)),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_check_resolves_includes_and_variables() {
+        let dir = std::env::temp_dir().join(format!("rustwall-cli-{}-check", std::process::id()));
+        fs::create_dir_all(dir.join("sections")).unwrap();
+        let config = dir.join("paywall.yml");
+        let section = dir.join("sections").join("research.yml");
+        fs::write(
+            &config,
+            "include: [sections/*.yml]\npaths:\n  - id: premium\n    paywall_conditions: []\n    price_source: !Hard \"${RUSTWALL_TEST_UNSET_PRICE:-$1.25}\"\n",
+        )
+        .unwrap();
+        fs::write(
+            &section,
+            "- id: research\n  paywall_conditions: []\n  price_source: !Hard $5.00\n",
+        )
+        .unwrap();
+        let config = config.to_string_lossy().into_owned();
+
+        let (code, out, err) = run_with(&["check", &config]);
+        assert_eq!(code, ExitCode::SUCCESS, "{}", err);
+        assert_eq!(out, format!("{}: OK\n", config));
+
+        fs::write(
+            &section,
+            "- id: research\n  paywall_conditions: []\n  price_source: !Hard 5.00\n",
+        )
+        .unwrap();
+
+        let (code, _, err) = run_with(&["check", &config]);
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(
+            err.starts_with(&format!("{}:3:", section.display())),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_test_writes_report_and_junit() {
+        let config = write_file(
+            "test-config.yml",
+            "paths:\n  - id: premium\n    paywall_conditions:\n      - !HasRegexPath \"^/premium/.*$\"\n    price_source: !Hard $1.00\n",
+        );
+        let fixture = write_file("test-page.html", "<html><body></body></html>");
+        let fixture_name = Path::new(&fixture).file_name().unwrap().to_str().unwrap();
+        let cases = write_file(
+            "test-cases.yml",
+            &format!(
+                "- path: /premium/a\n  html_file: {}\n  expect: {{ price: \"$1.00\" }}\n- path: /premium/b\n  html_file: {}\n  expect: {{ paywalled: false }}\n",
+                fixture_name, fixture_name
+            ),
+        );
+        let junit =
+            std::env::temp_dir().join(format!("rustwall-cli-{}-junit.xml", std::process::id()));
+
+        let (code, out, _) =
+            run_with(&["test", &config, &cases, "--junit", junit.to_str().unwrap()]);
+
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(
+            out.contains("PASS /premium/a\nFAIL /premium/b\n"),
+            "{}",
+            out
+        );
+        assert!(
+            fs::read_to_string(junit)
+                .unwrap()
+                .contains("failures=\"1\"")
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let config = write_file(
+            "explain-config.yml",
+            "paths:\n  - id: premium\n    paywall_conditions:\n      - !HasRegexPath \"^/premium/.*$\"\n      - !MatchesCssSelector div.paywall\n    price_source: !Hard $1.00\n",
+        );
+        let page = write_file(
+            "explain-page.html",
+            "<html><body><div class=\"paywall\"></div></body></html>",
+        );
+
+        let (code, out, _) =
+            run_with(&["explain", &config, "--path", "/premium/a", "--html", &page]);
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(
+            out.contains("  [x] MatchesCssSelector \"div.paywall\" (<div class=\"paywall\">)\n"),
+            "{}",
+            out
+        );
+        assert!(out.contains("  price: Hard $1.00 -> $1.00\n"));
+    }
+
+    #[test]
+    fn test_explain_with_currency_selection() {
+        let config = write_file(
+            "explain-currency-config.yml",
+            "currency_selection:\n  fallback: EUR\n  document_currency: USD\npaths:\n  - id: premium\n    paywall_conditions:\n      - !HasRegexPath \"^/premium/.*$\"\n    price_source: !FromHtmlAttribute div#price:::data-price\n  - id: table\n    paywall_conditions: []\n    price_source: !Hard { USD: $1.00, EUR: €0.95 }\n",
+        );
+        let page = write_file(
+            "explain-currency-page.html",
+            "<html><body><div id=\"price\" data-price=\"2.00\"></div></body></html>",
+        );
+
+        let (code, out, _) =
+            run_with(&["explain", &config, "--path", "/premium/a", "--html", &page]);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(out.contains("-> $2.00\n"), "{}", out);
+
+        let (_, out, _) = run_with(&["explain", &config, "--path", "/table/a", "--html", &page]);
+        assert!(out.contains("element 'table': price €0.95"), "{}", out);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let config = write_file(
+            "migrate-config.yml",
+            "# prices\npaths:\n  - paywall_conditions: []\n    price_source: !Hard $1.00\n",
+        );
+
+        let (code, out, _) = run_with(&["migrate", &config]);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(out.starts_with("# prices\nversion: 2\npaths:\n"), "{}", out);
+        assert!(fs::read_to_string(&config).unwrap().contains("paths:"));
+
+        let (code, out, _) = run_with(&["migrate", &config, "--write"]);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(out, format!("{}: migrated from version 1 to 2\n", config));
+
+        let (_, out, _) = run_with(&["check", &config]);
+        assert_eq!(out, format!("{}: OK\n", config));
+        let (_, out, _) = run_with(&["migrate", &config, "--write"]);
+        assert_eq!(out, format!("{}: already version 2\n", config));
+    }
+
+    #[test]
+    fn test_check_json_and_toml() {
+        let json = write_file(
+            "check.json",
+            r#"{ "paths": [ { "paywall_conditions": [], "price_source": { "!Hard": "$1.00" } } ] }"#,
+        );
+        let toml = write_file(
+            "check.toml",
+            "[[paths]]\npaywall_conditions = []\nprice_source = { \"!Hard\" = 1.00 }\n",
+        );
+
+        let (code, out, _) = run_with(&["check", &json]);
+        assert_eq!(code, ExitCode::SUCCESS, "{}", out);
+
+        let (code, _, err) = run_with(&["check", &toml]);
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(err.starts_with(&format!("{}:1:1: error:", toml)), "{}", err);
+    }
+
+    #[test]
+    fn test_schema() {
+        let (code, out, _) = run_with(&["schema"]);
+        assert_eq!(code, ExitCode::SUCCESS);
+        let schema: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert!(schema["$defs"]["PriceSource"].is_object());
+
+        let (_, out, _) = run_with(&["schema", "--custom-tags"]);
+        assert!(out.contains("\"!Hard mapping\""));
+    }
+
+    #[test]
+    fn test_usage_error() {
+        let (code, _, err) = run_with(&["check"]);
+        assert_eq!(code, ExitCode::from(2));
+        assert!(err.starts_with("Usage"));
+
+        let (code, _, _) = run_with(&["test", "paywall.yml"]);
+        assert_eq!(code, ExitCode::from(2));
+
+        let (code, _, _) = run_with(&["explain", "paywall.yml", "--path", "/a"]);
+        assert_eq!(code, ExitCode::from(2));
+    }
+}
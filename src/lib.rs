@@ -0,0 +1,5 @@
+pub mod cli;
+pub mod money;
+pub mod paywall_config;
+pub mod pricing;
+pub mod utils;
@@ -1,6 +1,7 @@
-pub mod paywall_config;
-pub mod utils;
+use rustwall::cli;
+use std::process::ExitCode;
 
-fn main() {
-    println!("Hello, world!");
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    cli::run(&args, &mut std::io::stdout(), &mut std::io::stderr())
 }
@@ -0,0 +1,164 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// ISO-4217 currency code, e.g. `USD` or `EUR`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CurrencyCode {
+    code: String,
+}
+
+#[derive(Debug, Error)]
+pub enum CurrencyCodeError {
+    #[error("Invalid currency code '{0}': expected three ASCII letters like 'USD'")]
+    InvalidFormat(String),
+}
+
+impl CurrencyCode {
+    pub fn new(code: &str) -> Result<Self, CurrencyCodeError> {
+        if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+            Ok(CurrencyCode {
+                code: code.to_ascii_uppercase(),
+            })
+        } else {
+            Err(CurrencyCodeError::InvalidFormat(code.to_string()))
+        }
+    }
+
+    /// Map an unambiguous currency symbol to its code; `$` is read as `USD`
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        let code = match symbol {
+            '$' => "USD",
+            '€' => "EUR",
+            '£' => "GBP",
+            '¥' => "JPY",
+            '₹' => "INR",
+            '₩' => "KRW",
+            '₽' => "RUB",
+            '₺' => "TRY",
+            '₪' => "ILS",
+            _ => return None,
+        };
+
+        Some(CurrencyCode {
+            code: code.to_string(),
+        })
+    }
+
+    /// Inverse of [from_symbol](Self::from_symbol)
+    pub fn symbol(&self) -> Option<char> {
+        let symbol = match self.code.as_str() {
+            "USD" => '$',
+            "EUR" => '€',
+            "GBP" => '£',
+            "JPY" => '¥',
+            "INR" => '₹',
+            "KRW" => '₩',
+            "RUB" => '₽',
+            "TRY" => '₺',
+            "ILS" => '₪',
+            _ => return None,
+        };
+
+        Some(symbol)
+    }
+
+    /// Number of digits after the decimal separator, e.g. 2 for `EUR` and 0 for `JPY`
+    pub fn minor_unit_digits(&self) -> u32 {
+        match self.code.as_str() {
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF"
+            | "UGX" | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.code
+    }
+}
+
+impl fmt::Display for CurrencyCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+impl FromStr for CurrencyCode {
+    type Err = CurrencyCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CurrencyCode::new(s)
+    }
+}
+
+impl Serialize for CurrencyCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CurrencyCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CurrencyCodeVisitor;
+
+        impl Visitor<'_> for CurrencyCodeVisitor {
+            type Value = CurrencyCode;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an ISO-4217 currency code like 'USD'")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                CurrencyCode::new(value).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_string(CurrencyCodeVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_code_valid() {
+        let code = CurrencyCode::new("eur").unwrap();
+        assert_eq!(code.as_str(), "EUR");
+    }
+
+    #[test]
+    fn test_currency_code_invalid() {
+        assert!(CurrencyCode::new("EURO").is_err());
+        assert!(CurrencyCode::new("E1R").is_err());
+    }
+
+    #[test]
+    fn test_currency_code_from_symbol() {
+        assert_eq!(
+            CurrencyCode::from_symbol('£').unwrap(),
+            CurrencyCode::new("GBP").unwrap()
+        );
+        assert!(CurrencyCode::from_symbol('x').is_none());
+        assert_eq!(CurrencyCode::new("EUR").unwrap().symbol(), Some('€'));
+    }
+
+    #[test]
+    fn test_currency_code_minor_unit_digits() {
+        assert_eq!(CurrencyCode::new("EUR").unwrap().minor_unit_digits(), 2);
+        assert_eq!(CurrencyCode::new("JPY").unwrap().minor_unit_digits(), 0);
+        assert_eq!(CurrencyCode::new("KWD").unwrap().minor_unit_digits(), 3);
+    }
+}
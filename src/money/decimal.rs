@@ -0,0 +1,465 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Precision kept by divisions and long multiplications
+const MAX_SCALE: u32 = 18;
+
+/// Exact decimal number used for rates, percentages and amounts in major units
+///
+/// Stored as `mantissa * 10^-scale` without trailing zeros, so equal values compare equal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DecimalError {
+    #[error("Invalid decimal '{0}'")]
+    InvalidFormat(String),
+    #[error("Decimal overflow")]
+    Overflow,
+    #[error("Division by zero")]
+    DivisionByZero,
+}
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal {
+        mantissa: 0,
+        scale: 0,
+    };
+    pub const ONE: Decimal = Decimal {
+        mantissa: 1,
+        scale: 0,
+    };
+
+    pub fn new(mantissa: i128, scale: u32) -> Decimal {
+        let mut decimal = Decimal { mantissa, scale };
+
+        while decimal.scale > 0 && decimal.mantissa % 10 == 0 {
+            decimal.mantissa /= 10;
+            decimal.scale -= 1;
+        }
+        if decimal.mantissa == 0 {
+            decimal.scale = 0;
+        }
+
+        decimal
+    }
+
+    pub fn from_integer(value: i64) -> Decimal {
+        Decimal::new(i128::from(value), 0)
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    pub fn abs(&self) -> Decimal {
+        Decimal::new(self.mantissa.abs(), self.scale)
+    }
+
+    /// Multiply by `10^exponent`
+    pub fn shift(&self, exponent: i32) -> Result<Decimal, DecimalError> {
+        if exponent >= 0 {
+            let exponent = exponent.unsigned_abs();
+            if exponent <= self.scale {
+                return Ok(Decimal::new(self.mantissa, self.scale - exponent));
+            }
+            let factor = pow10(exponent - self.scale)?;
+            let mantissa = self
+                .mantissa
+                .checked_mul(factor)
+                .ok_or(DecimalError::Overflow)?;
+            Ok(Decimal::new(mantissa, 0))
+        } else {
+            let scale = self.scale + exponent.unsigned_abs();
+            if scale > MAX_SCALE {
+                return Ok(Decimal {
+                    mantissa: self.mantissa,
+                    scale,
+                }
+                .round_to_scale(MAX_SCALE));
+            }
+            Ok(Decimal::new(self.mantissa, scale))
+        }
+    }
+
+    pub fn checked_add(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let (a, b, scale) = align(self, other)?;
+        a.checked_add(b)
+            .map(|m| Decimal::new(m, scale))
+            .ok_or(DecimalError::Overflow)
+    }
+
+    pub fn checked_sub(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let (a, b, scale) = align(self, other)?;
+        a.checked_sub(b)
+            .map(|m| Decimal::new(m, scale))
+            .ok_or(DecimalError::Overflow)
+    }
+
+    pub fn checked_mul(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        let mantissa = self
+            .mantissa
+            .checked_mul(other.mantissa)
+            .ok_or(DecimalError::Overflow)?;
+        let scale = self.scale + other.scale;
+
+        if scale > MAX_SCALE {
+            return Ok(Decimal { mantissa, scale }.round_to_scale(MAX_SCALE));
+        }
+
+        Ok(Decimal::new(mantissa, scale))
+    }
+
+    /// Division rounded half away from zero to 18 fractional digits
+    pub fn checked_div(&self, other: &Decimal) -> Result<Decimal, DecimalError> {
+        if other.is_zero() {
+            return Err(DecimalError::DivisionByZero);
+        }
+
+        let dividend = self.mantissa.unsigned_abs();
+        let mut divisor = other.mantissa.unsigned_abs();
+        let mut divisor_scale = i64::from(other.scale);
+        // keep `remainder * 10` within u128; the dropped digits are far below the precision kept
+        while divisor > u128::MAX / 10 {
+            divisor = divisor.div_ceil(10);
+            divisor_scale -= 1;
+        }
+
+        // self / other = dividend / divisor * 10^(divisor_scale - self.scale), computed by long
+        // division to MAX_SCALE + 1 fractional digits
+        let digits = i64::from(MAX_SCALE + 1) + divisor_scale - i64::from(self.scale);
+        let mut quotient = dividend / divisor;
+        if digits >= 0 {
+            let mut remainder = dividend % divisor;
+            for _ in 0..digits {
+                remainder *= 10;
+                quotient = quotient
+                    .checked_mul(10)
+                    .and_then(|q| q.checked_add(remainder / divisor))
+                    .ok_or(DecimalError::Overflow)?;
+                remainder %= divisor;
+            }
+        } else {
+            quotient = u32::try_from(-digits)
+                .ok()
+                .and_then(|d| 10u128.checked_pow(d))
+                .map_or(0, |factor| quotient / factor);
+        }
+
+        let magnitude = i128::try_from(quotient).map_err(|_| DecimalError::Overflow)?;
+        let negative = (self.mantissa < 0) != (other.mantissa < 0);
+        let rounded = Decimal {
+            mantissa: if negative { -magnitude } else { magnitude },
+            scale: MAX_SCALE + 1,
+        }
+        .round_to_scale(MAX_SCALE);
+
+        Ok(rounded)
+    }
+
+    /// Round half away from zero to an integer
+    pub fn round(&self) -> i128 {
+        self.round_to_scale(0).mantissa
+    }
+
+    pub fn floor(&self) -> i128 {
+        match 10i128.checked_pow(self.scale) {
+            Some(divisor) => self.mantissa.div_euclid(divisor),
+            // any mantissa is smaller than 10^scale
+            None if self.mantissa < 0 => -1,
+            None => 0,
+        }
+    }
+
+    /// Round half away from zero to at most `scale` fractional digits
+    pub fn round_to_scale(&self, scale: u32) -> Decimal {
+        if self.scale <= scale {
+            return *self;
+        }
+
+        let Some(divisor) = 10i128.checked_pow(self.scale - scale) else {
+            return Decimal::ZERO;
+        };
+        let quotient = self.mantissa / divisor;
+        let remainder = (self.mantissa % divisor).abs();
+        let rounded = if remainder >= divisor - remainder {
+            quotient + self.mantissa.signum()
+        } else {
+            quotient
+        };
+
+        Decimal::new(rounded, scale)
+    }
+}
+
+fn pow10(exponent: u32) -> Result<i128, DecimalError> {
+    10i128.checked_pow(exponent).ok_or(DecimalError::Overflow)
+}
+
+fn align(a: &Decimal, b: &Decimal) -> Result<(i128, i128, u32), DecimalError> {
+    let scale = a.scale.max(b.scale);
+    let a_mantissa = a
+        .mantissa
+        .checked_mul(pow10(scale - a.scale)?)
+        .ok_or(DecimalError::Overflow)?;
+    let b_mantissa = b
+        .mantissa
+        .checked_mul(pow10(scale - b.scale)?)
+        .ok_or(DecimalError::Overflow)?;
+
+    Ok((a_mantissa, b_mantissa, scale))
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match align(self, other) {
+            Ok((a, b, _)) => a.cmp(&b),
+            // only reachable for huge values at high scale, where the integer part decides
+            Err(_) => self.floor().cmp(&other.floor()),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (int_part, frac_part) = padded.split_at(padded.len() - scale);
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+/// Parses `0.19`, `-1.5`, `42` and percentages like `19%`
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DecimalError::InvalidFormat(s.to_string());
+        let trimmed = s.trim();
+
+        let (number, is_percent) = match trimmed.strip_suffix('%') {
+            Some(number) => (number.trim_end(), true),
+            None => (trimmed, false),
+        };
+
+        let (negative, unsigned) = match number.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, number.strip_prefix('+').unwrap_or(number)),
+        };
+
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if int_part.is_empty() && frac_part.is_empty()
+            || !all_digits(int_part)
+            || !all_digits(frac_part)
+            || unsigned.ends_with('.')
+        {
+            return Err(invalid());
+        }
+
+        let scale = u32::try_from(frac_part.len()).map_err(|_| invalid())?;
+        if scale > MAX_SCALE {
+            return Err(invalid());
+        }
+
+        let mantissa: i128 = format!("{}{}", int_part, frac_part)
+            .parse()
+            .map_err(|_| DecimalError::Overflow)?;
+        let mantissa = if negative { -mantissa } else { mantissa };
+
+        let decimal = Decimal::new(mantissa, scale);
+
+        if is_percent {
+            decimal.shift(-2)
+        } else {
+            Ok(decimal)
+        }
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DecimalVisitor;
+
+        impl Visitor<'_> for DecimalVisitor {
+            type Value = Decimal;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a decimal number like 0.19 or a percentage like '19%'")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Decimal::from_str(value).map_err(de::Error::custom)
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Decimal::from_integer(value))
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Decimal::new(i128::from(value), 0))
+            }
+
+            // YAML numbers arrive as floats; their shortest representation is the written one
+            fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Decimal::from_str(&value.to_string()).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(dec("0.190").to_string(), "0.19");
+        assert_eq!(dec("-1.5").to_string(), "-1.5");
+        assert_eq!(dec("19%"), dec("0.19"));
+        assert_eq!(dec("0.05").to_string(), "0.05");
+        assert!(Decimal::from_str("1.").is_err());
+        assert!(Decimal::from_str("1,5").is_err());
+        assert!(Decimal::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(dec("0.1").checked_add(&dec("0.2")).unwrap(), dec("0.3"));
+        assert_eq!(dec("1.25").checked_mul(&dec("0.8")).unwrap(), dec("1"));
+        assert_eq!(dec("1").checked_div(&dec("8")).unwrap(), dec("0.125"));
+        assert_eq!(
+            dec("1").checked_div(&dec("3")).unwrap(),
+            dec("0.333333333333333333")
+        );
+        assert_eq!(
+            dec("2").checked_div(&dec("0")),
+            Err(DecimalError::DivisionByZero)
+        );
+        assert_eq!(dec("-1").checked_div(&dec("-8")).unwrap(), dec("0.125"));
+        assert_eq!(dec("-1").checked_div(&dec("8")).unwrap(), dec("-0.125"));
+    }
+
+    #[test]
+    fn test_division_by_fine_scaled_divisor() {
+        let third = dec("1").checked_div(&dec("3")).unwrap();
+
+        assert_eq!(
+            dec("100").checked_div(&third).unwrap(),
+            dec("300.0000000000000003")
+        );
+        assert_eq!(
+            dec("1").checked_div(&Decimal::new(i128::MAX, 0)).unwrap(),
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(dec("2.5").round(), 3);
+        assert_eq!(dec("-2.5").round(), -3);
+        assert_eq!(dec("2.49").round(), 2);
+        assert_eq!(dec("-2.1").floor(), -3);
+        assert_eq!(Decimal::new(1, 40).floor(), 0);
+        assert_eq!(Decimal::new(-1, 40).floor(), -1);
+        assert_eq!(dec("1.005").round_to_scale(2), dec("1.01"));
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(dec("0.5") < dec("0.51"));
+        assert!(dec("-1") < dec("0.001"));
+    }
+
+    #[test]
+    fn test_deserialize_yaml_number() {
+        let rate: Decimal = serde_yml::from_str("0.92").unwrap();
+        assert_eq!(rate, dec("0.92"));
+
+        let rate: Decimal = serde_yml::from_str("\"7.7%\"").unwrap();
+        assert_eq!(rate, dec("0.077"));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_to_scale_at_precision_limit(
+            mantissa in (i128::MIN + 1)..=i128::MAX,
+            scale in 0u32..38,
+        ) {
+            let decimal = Decimal { mantissa, scale: 38 };
+            let divisor = 10u128.pow(38 - scale);
+            let quotient = mantissa.unsigned_abs() / divisor;
+            let remainder = mantissa.unsigned_abs() % divisor;
+            let expected = (quotient + u128::from(remainder * 2 >= divisor)) as i128;
+
+            prop_assert_eq!(
+                decimal.round_to_scale(scale),
+                Decimal::new(mantissa.signum() * expected, scale)
+            );
+        }
+    }
+}
@@ -0,0 +1,530 @@
+pub mod currency_code;
+pub mod decimal;
+
+pub use currency_code::{CurrencyCode, CurrencyCodeError};
+pub use decimal::{Decimal, DecimalError};
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Amount of money stored as an integer of minor units (e.g. cents) with its ISO-4217 currency
+///
+/// Parsing is strict and accepts `$1.25`, `1,25 €`, `EUR 1.25`, `1.25 EUR` and grouped amounts
+/// like `$1,000.00` or `1.000,00 €`. A single separator followed by exactly three digits is
+/// ambiguous and rejected (`$1,000`), unless the currency has three decimal places, none
+/// (`¥1,000`) or the integer part is zero (`$0.999` has too many decimals). [Display](fmt::Display)
+/// writes the canonical form that parses back to the same value
+/// # Examples
+/// ```
+/// use rustwall::money::Money;
+///
+/// let price: Money = "1,25 €".parse().unwrap();
+/// assert_eq!(price.minor_units(), 125);
+/// assert_eq!(price.to_string(), "€1.25");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor_units: i64,
+    currency_code: CurrencyCode,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MoneyError {
+    #[error("Invalid amount '{value}': {reason}")]
+    InvalidFormat { value: String, reason: String },
+    #[error("Amount '{0}' has no currency symbol or code")]
+    MissingCurrency(String),
+    #[error("Currency mismatch: {0} and {1}")]
+    CurrencyMismatch(CurrencyCode, CurrencyCode),
+    #[error("Amount overflow")]
+    Overflow,
+}
+
+impl Money {
+    pub fn from_minor_units(minor_units: i64, currency_code: CurrencyCode) -> Money {
+        Money {
+            minor_units,
+            currency_code,
+        }
+    }
+
+    pub fn zero(currency_code: CurrencyCode) -> Money {
+        Money::from_minor_units(0, currency_code)
+    }
+
+    /// Round an amount in major units half away from zero to minor units
+    pub fn from_decimal(
+        amount: &Decimal,
+        currency_code: CurrencyCode,
+    ) -> Result<Money, MoneyError> {
+        let minor = amount
+            .shift(currency_code.minor_unit_digits() as i32)
+            .map_err(|_| MoneyError::Overflow)?
+            .round();
+
+        let minor_units = i64::try_from(minor).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money::from_minor_units(minor_units, currency_code))
+    }
+
+    pub fn minor_units(&self) -> i64 {
+        self.minor_units
+    }
+
+    pub fn currency_code(&self) -> &CurrencyCode {
+        &self.currency_code
+    }
+
+    /// Amount in major units, e.g. `1.25` for `$1.25`
+    pub fn to_decimal(&self) -> Decimal {
+        Decimal::new(
+            i128::from(self.minor_units),
+            self.currency_code.minor_unit_digits(),
+        )
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor_units == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor_units < 0
+    }
+
+    /// Same currency, different amount
+    pub fn with_minor_units(&self, minor_units: i64) -> Money {
+        Money::from_minor_units(minor_units, self.currency_code.clone())
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.check_same_currency(other)?;
+        self.minor_units
+            .checked_add(other.minor_units)
+            .map(|m| self.with_minor_units(m))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.check_same_currency(other)?;
+        self.minor_units
+            .checked_sub(other.minor_units)
+            .map(|m| self.with_minor_units(m))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Multiply by a factor such as a tax rate or discount percentage, rounding half away
+    /// from zero to whole minor units
+    pub fn checked_mul(&self, factor: &Decimal) -> Result<Money, MoneyError> {
+        let product = Decimal::from_integer(self.minor_units)
+            .checked_mul(factor)
+            .map_err(|_| MoneyError::Overflow)?;
+
+        i64::try_from(product.round())
+            .map(|m| self.with_minor_units(m))
+            .map_err(|_| MoneyError::Overflow)
+    }
+
+    fn check_same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency_code != other.currency_code {
+            return Err(MoneyError::CurrencyMismatch(
+                self.currency_code.clone(),
+                other.currency_code.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Parse a price; amounts without a currency marker use `default_currency`
+    pub fn parse_with_default(
+        value: &str,
+        default_currency: Option<&CurrencyCode>,
+    ) -> Result<Money, MoneyError> {
+        let invalid = |reason: &str| MoneyError::InvalidFormat {
+            value: value.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let trimmed = value.trim();
+        let first_digit = trimmed
+            .find(|c: char| c.is_ascii_digit())
+            .ok_or_else(|| invalid("no digits"))?;
+        let last_digit = trimmed
+            .rfind(|c: char| c.is_ascii_digit())
+            .ok_or_else(|| invalid("no digits"))?;
+
+        let mut prefix = trimmed[..first_digit].trim();
+        let number = &trimmed[first_digit..=last_digit];
+        let mut suffix = trimmed[last_digit + 1..].trim();
+
+        let mut negative = false;
+        for part in [&mut prefix, &mut suffix] {
+            if let Some(rest) = part.strip_prefix('-').or(part.strip_suffix('-')) {
+                if negative {
+                    return Err(invalid("more than one sign"));
+                }
+                negative = true;
+                *part = rest.trim();
+            }
+        }
+
+        let currency_code = match (parse_currency_marker(prefix), parse_currency_marker(suffix)) {
+            (Some(Err(())), _) | (_, Some(Err(()))) => {
+                return Err(invalid("unknown currency symbol or code"));
+            }
+            (Some(Ok(_)), Some(Ok(_))) => return Err(invalid("more than one currency")),
+            (Some(Ok(code)), None) | (None, Some(Ok(code))) => code,
+            (None, None) => default_currency
+                .cloned()
+                .ok_or_else(|| MoneyError::MissingCurrency(value.to_string()))?,
+        };
+
+        let minor_unit_digits = currency_code.minor_unit_digits();
+        let (int_digits, frac_digits) =
+            split_number(number, minor_unit_digits).map_err(|reason| invalid(&reason))?;
+
+        if frac_digits.len() > minor_unit_digits as usize {
+            return Err(invalid(&format!(
+                "{} allows at most {} decimal places",
+                currency_code, minor_unit_digits
+            )));
+        }
+
+        let padded = format!(
+            "{}{:0<width$}",
+            int_digits,
+            frac_digits,
+            width = minor_unit_digits as usize
+        );
+        let magnitude: i64 = padded.parse().map_err(|_| MoneyError::Overflow)?;
+        let minor_units = if negative { -magnitude } else { magnitude };
+
+        Ok(Money::from_minor_units(minor_units, currency_code))
+    }
+}
+
+/// `None` if there is no marker, `Some(Err(()))` if it is not a known symbol or code
+fn parse_currency_marker(marker: &str) -> Option<Result<CurrencyCode, ()>> {
+    if marker.is_empty() {
+        return None;
+    }
+
+    let mut chars = marker.chars();
+    if let (Some(symbol), None) = (chars.next(), chars.next()) {
+        return Some(CurrencyCode::from_symbol(symbol).ok_or(()));
+    }
+
+    Some(CurrencyCode::new(marker).map_err(|_| ()))
+}
+
+/// Split `1,000.50` or `1.000,50` into integer and fraction digits
+fn split_number(number: &str, minor_unit_digits: u32) -> Result<(String, String), String> {
+    if let Some(c) = number
+        .chars()
+        .find(|c| !c.is_ascii_digit() && *c != '.' && *c != ',')
+    {
+        return Err(format!("unexpected character '{}'", c));
+    }
+
+    let dots = number.matches('.').count();
+    let commas = number.matches(',').count();
+
+    let decimal_separator = match (dots, commas) {
+        (0, 0) => None,
+        (_, 0) | (0, _) => {
+            let separator = if dots > 0 { '.' } else { ',' };
+            let position = number.rfind(separator).unwrap_or(0);
+            let digits_after = number.len() - position - 1;
+            let zero_before = number[..position].trim_start_matches('0').is_empty();
+
+            if dots + commas > 1 {
+                None
+            } else if digits_after != 3 || minor_unit_digits == 3 || zero_before {
+                Some(separator)
+            } else if minor_unit_digits == 0 {
+                None
+            } else {
+                // `1,250` could be 1250 or 1.25
+                return Err(format!(
+                    "ambiguous separator '{}', write the amount like 1,250.00 or 1.25",
+                    separator
+                ));
+            }
+        }
+        _ => {
+            let last_dot = number.rfind('.').unwrap_or(0);
+            let last_comma = number.rfind(',').unwrap_or(0);
+            Some(if last_dot > last_comma { '.' } else { ',' })
+        }
+    };
+
+    let (int_part, frac_part) = match decimal_separator {
+        Some(separator) => {
+            let (int_part, frac_part) = number.rsplit_once(separator).unwrap_or((number, ""));
+            if frac_part.contains(['.', ',']) || frac_part.is_empty() {
+                return Err("misplaced decimal separator".to_string());
+            }
+            (int_part, frac_part)
+        }
+        None => (number, ""),
+    };
+
+    let groups: Vec<&str> = int_part.split(['.', ',']).collect();
+    if groups.len() > 1 {
+        let group_separators: Vec<char> =
+            int_part.chars().filter(|c| !c.is_ascii_digit()).collect();
+        let consistent = group_separators.windows(2).all(|w| w[0] == w[1]);
+        let well_formed =
+            (1..=3).contains(&groups[0].len()) && groups[1..].iter().all(|g| g.len() == 3);
+
+        if !consistent || !well_formed {
+            return Err("digit groups must have three digits".to_string());
+        }
+    }
+
+    Ok((groups.concat(), frac_part.to_string()))
+}
+
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Money::parse_with_default(s, None)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.minor_units < 0 { "-" } else { "" };
+        let digits = self.currency_code.minor_unit_digits() as usize;
+        let magnitude = self.minor_units.unsigned_abs().to_string();
+        let padded = format!("{:0>width$}", magnitude, width = digits + 1);
+        let (int_part, frac_part) = padded.split_at(padded.len() - digits);
+
+        let amount = if digits == 0 {
+            int_part.to_string()
+        } else {
+            format!("{}.{}", int_part, frac_part)
+        };
+
+        match self.currency_code.symbol() {
+            Some(symbol) => write!(f, "{}{}{}", sign, symbol, amount),
+            None => write!(f, "{}{} {}", sign, self.currency_code, amount),
+        }
+    }
+}
+
+/// Only amounts in the same currency are comparable
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.currency_code != other.currency_code {
+            return None;
+        }
+
+        Some(self.minor_units.cmp(&other.minor_units))
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MoneyVisitor;
+
+        impl Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an amount with currency like '$1.25', '1,25 €' or 'EUR 1.25'")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Money::from_str(value).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_string(MoneyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn code(code: &str) -> CurrencyCode {
+        CurrencyCode::new(code).unwrap()
+    }
+
+    fn money(minor_units: i64, currency: &str) -> Money {
+        Money::from_minor_units(minor_units, code(currency))
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!(Money::from_str("$1.25").unwrap(), money(125, "USD"));
+        assert_eq!(Money::from_str("1,25 €").unwrap(), money(125, "EUR"));
+        assert_eq!(Money::from_str("EUR 1.25").unwrap(), money(125, "EUR"));
+        assert_eq!(Money::from_str("1.25 CHF").unwrap(), money(125, "CHF"));
+        assert_eq!(Money::from_str("£0.5").unwrap(), money(50, "GBP"));
+        assert_eq!(Money::from_str("-$3").unwrap(), money(-300, "USD"));
+        assert_eq!(Money::from_str("¥1,000").unwrap(), money(1000, "JPY"));
+    }
+
+    #[test]
+    fn test_parse_grouping() {
+        assert_eq!(Money::from_str("$1,000.50").unwrap(), money(100050, "USD"));
+        assert_eq!(Money::from_str("1.000,50 €").unwrap(), money(100050, "EUR"));
+        assert_eq!(
+            Money::from_str("$1,000,000").unwrap(),
+            money(100000000, "USD")
+        );
+        assert_eq!(Money::from_str("KWD 1.000").unwrap(), money(1000, "KWD"));
+    }
+
+    #[test]
+    fn test_parse_rejects_ambiguous_grouping() {
+        for value in ["$1,000", "$1.250", "€1,250", "$0.999"] {
+            assert!(
+                Money::from_str(value).is_err(),
+                "{} should not parse",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        for value in [
+            "1.25",
+            "$1.2555",
+            "$1,00,0.00",
+            "$ 1.2.3,4",
+            "$1.",
+            "€1.25 EUR",
+            "XY 1.00",
+            "¥1.5",
+            "$1x25",
+            "--$1",
+            "",
+        ] {
+            assert!(
+                Money::from_str(value).is_err(),
+                "{} should not parse",
+                value
+            );
+        }
+
+        assert_eq!(
+            Money::from_str("1.25"),
+            Err(MoneyError::MissingCurrency("1.25".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_with_default() {
+        let default = code("GBP");
+
+        assert_eq!(
+            Money::parse_with_default("1.25", Some(&default)).unwrap(),
+            money(125, "GBP")
+        );
+        assert_eq!(
+            Money::parse_with_default("$1.25", Some(&default)).unwrap(),
+            money(125, "USD")
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(money(125, "USD").to_string(), "$1.25");
+        assert_eq!(money(-5, "EUR").to_string(), "-€0.05");
+        assert_eq!(money(1000, "JPY").to_string(), "¥1000");
+        assert_eq!(money(12345, "CHF").to_string(), "CHF 123.45");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let price = money(1000, "EUR");
+        let vat = Decimal::from_str("19%").unwrap();
+
+        assert_eq!(price.checked_mul(&vat).unwrap(), money(190, "EUR"));
+        assert_eq!(
+            money(999, "EUR")
+                .checked_mul(&Decimal::from_str("0.5").unwrap())
+                .unwrap(),
+            money(500, "EUR")
+        );
+        assert_eq!(
+            price.checked_sub(&money(250, "EUR")).unwrap(),
+            money(750, "EUR")
+        );
+        assert_eq!(
+            price.checked_add(&money(1, "USD")),
+            Err(MoneyError::CurrencyMismatch(code("EUR"), code("USD")))
+        );
+        assert!(money(1, "EUR") < money(2, "EUR"));
+        assert_eq!(money(1, "EUR").partial_cmp(&money(2, "USD")), None);
+    }
+
+    #[test]
+    fn test_decimal_conversion() {
+        let price = money(125, "USD");
+
+        assert_eq!(price.to_decimal(), Decimal::from_str("1.25").unwrap());
+        assert_eq!(
+            Money::from_decimal(&Decimal::from_str("1.255").unwrap(), code("USD")).unwrap(),
+            money(126, "USD")
+        );
+    }
+
+    fn currency_strategy() -> impl Strategy<Value = CurrencyCode> {
+        prop::sample::select(vec!["USD", "EUR", "GBP", "JPY", "CHF", "KWD", "SEK"])
+            .prop_map(|c| CurrencyCode::new(c).unwrap())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_display_parse_round_trip(
+            minor_units in -1_000_000_000_000i64..1_000_000_000_000i64,
+            currency_code in currency_strategy(),
+        ) {
+            let original = Money::from_minor_units(minor_units, currency_code);
+            let parsed = Money::from_str(&original.to_string()).unwrap();
+
+            prop_assert_eq!(parsed, original);
+        }
+
+        #[test]
+        fn prop_decimal_comma_suffix_parses(units in 0u64..1_000_000, cents in 0u64..100) {
+            let formatted = format!("{},{:02} €", units, cents);
+            let parsed = Money::from_str(&formatted).unwrap();
+
+            prop_assert_eq!(parsed.minor_units(), (units * 100 + cents) as i64);
+        }
+
+        #[test]
+        fn prop_code_prefix_parses_like_symbol(units in 0u64..1_000_000, cents in 0u64..100) {
+            let with_code = Money::from_str(&format!("USD {}.{:02}", units, cents)).unwrap();
+            let with_symbol = Money::from_str(&format!("${}.{:02}", units, cents)).unwrap();
+
+            prop_assert_eq!(with_code, with_symbol);
+        }
+    }
+}
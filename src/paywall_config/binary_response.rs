@@ -0,0 +1,186 @@
+use thiserror::Error;
+
+/// Inclusive byte range of a binary document, parsed from a `Range` header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum ByteRangeError {
+    #[error("Unsupported range '{0}': only a single 'bytes=' range is supported")]
+    Unsupported(String),
+    #[error("Range '{range}' not satisfiable for {size} bytes")]
+    NotSatisfiable { range: String, size: u64 },
+}
+
+impl ByteRange {
+    /// Parse `bytes=start-end`, `bytes=start-` or `bytes=-suffix_length` for a document of `size`
+    /// bytes; the end is clamped to the last byte
+    pub fn parse(header: &str, size: u64) -> Result<ByteRange, ByteRangeError> {
+        let unsupported = || ByteRangeError::Unsupported(header.to_string());
+        let not_satisfiable = || ByteRangeError::NotSatisfiable {
+            range: header.to_string(),
+            size,
+        };
+
+        let spec = header
+            .trim()
+            .strip_prefix("bytes=")
+            .filter(|s| !s.contains(','))
+            .ok_or_else(unsupported)?;
+        let (start, end) = spec.split_once('-').ok_or_else(unsupported)?;
+        let parse = |s: &str| s.trim().parse::<u64>().map_err(|_| unsupported());
+
+        let (start, end) = match (start.trim().is_empty(), end.trim().is_empty()) {
+            (true, true) => return Err(unsupported()),
+            (true, false) => {
+                let suffix = parse(end)?;
+                if suffix == 0 {
+                    return Err(not_satisfiable());
+                }
+                (size.saturating_sub(suffix), size.saturating_sub(1))
+            }
+            (false, true) => (parse(start)?, size.saturating_sub(1)),
+            (false, false) => (parse(start)?, parse(end)?.min(size.saturating_sub(1))),
+        };
+
+        if size == 0 || start >= size || start > end {
+            return Err(not_satisfiable());
+        }
+
+        Ok(ByteRange { start, end })
+    }
+
+    /// Number of bytes in the range, the `Content-Length` of the partial response
+    pub fn get_length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Value of the `Content-Range` header of the partial response
+    pub fn content_range(&self, size: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, size)
+    }
+}
+
+/// How the server should answer a request for a binary document: gated readers are sent to the
+/// checkout (or get a `402 Payment Required`) instead of any bytes, entitled readers get the
+/// whole file or the requested range; unsupported `Range` headers are ignored as HTTP allows,
+/// only unsatisfiable ranges get a `416`
+#[derive(Clone, Debug, PartialEq)]
+pub enum BinaryResponse {
+    PaymentRequired,
+    RedirectToCheckout(String),
+    Full,
+    Partial(ByteRange),
+    RangeNotSatisfiable(ByteRangeError),
+}
+
+impl BinaryResponse {
+    pub fn new(
+        gated: bool,
+        checkout_url: Option<String>,
+        range_header: Option<&str>,
+        size: u64,
+    ) -> BinaryResponse {
+        match (gated, checkout_url, range_header) {
+            (true, Some(url), _) => BinaryResponse::RedirectToCheckout(url),
+            (true, None, _) => BinaryResponse::PaymentRequired,
+            (false, _, None) => BinaryResponse::Full,
+            (false, _, Some(range)) => match ByteRange::parse(range, size) {
+                Ok(range) => BinaryResponse::Partial(range),
+                Err(ByteRangeError::Unsupported(_)) => BinaryResponse::Full,
+                Err(e) => BinaryResponse::RangeNotSatisfiable(e),
+            },
+        }
+    }
+
+    pub fn status_code(&self) -> u16 {
+        match self {
+            BinaryResponse::PaymentRequired => 402,
+            BinaryResponse::RedirectToCheckout(_) => 303,
+            BinaryResponse::Full => 200,
+            BinaryResponse::Partial(_) => 206,
+            BinaryResponse::RangeNotSatisfiable(_) => 416,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_byte_ranges() {
+        assert_eq!(
+            ByteRange::parse("bytes=0-99", 1000),
+            Ok(ByteRange { start: 0, end: 99 })
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=900-", 1000),
+            Ok(ByteRange {
+                start: 900,
+                end: 999
+            })
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=-100", 1000),
+            Ok(ByteRange {
+                start: 900,
+                end: 999
+            })
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=500-5000", 1000)
+                .unwrap()
+                .get_length(),
+            500
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_byte_ranges() {
+        assert!(matches!(
+            ByteRange::parse("bytes=1000-", 1000),
+            Err(ByteRangeError::NotSatisfiable { .. })
+        ));
+        assert!(matches!(
+            ByteRange::parse("bytes=0-1,5-6", 1000),
+            Err(ByteRangeError::Unsupported(_))
+        ));
+        assert!(matches!(
+            ByteRange::parse("items=0-1", 1000),
+            Err(ByteRangeError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_binary_response() {
+        let checkout = Some("/checkout".to_string());
+
+        assert_eq!(
+            BinaryResponse::new(true, checkout.clone(), Some("bytes=0-1"), 10),
+            BinaryResponse::RedirectToCheckout("/checkout".to_string())
+        );
+        assert_eq!(BinaryResponse::new(true, None, None, 10).status_code(), 402);
+        assert_eq!(
+            BinaryResponse::new(false, checkout, None, 10),
+            BinaryResponse::Full
+        );
+        assert_eq!(
+            BinaryResponse::new(false, None, Some("bytes=2-"), 10),
+            BinaryResponse::Partial(ByteRange { start: 2, end: 9 })
+        );
+    }
+
+    #[test]
+    fn test_binary_response_ignores_unsupported_ranges() {
+        let multiple = BinaryResponse::new(false, None, Some("bytes=0-1,5-6"), 10);
+        let unsatisfiable = BinaryResponse::new(false, None, Some("bytes=10-"), 10);
+
+        assert_eq!(multiple, BinaryResponse::Full);
+        assert_eq!(multiple.status_code(), 200);
+        assert_eq!(unsatisfiable.status_code(), 416);
+    }
+}
@@ -0,0 +1,785 @@
+use serde::de::IgnoredAny;
+use std::sync::Arc;
+
+use super::{
+    CurrencySelection, ErrorPolicy, JsonRedaction, MarkdownTeaser, PaywallCondition,
+    PaywallConfigError, PaywallConfigV1, PaywallElement, PaywallEventSink, PriceSource,
+    default_event_sink,
+};
+use crate::pricing::{DiscountConfig, TaxConfig};
+
+/// Builds a [paywall element](PaywallElement) in code instead of deserializing it
+/// # Examples
+/// ```
+/// use rustwall::paywall_config::{PaywallCondition, PaywallConfigV1, PaywallElement, PriceSource};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let element = PaywallElement::builder()
+///     .id("premium")
+///     .condition(PaywallCondition::path_regex("^/premium/")?)
+///     .price(PriceSource::hard("$1.00")?)
+///     .build()?;
+/// let config = PaywallConfigV1::builder().element(element).build()?;
+///
+/// assert!(config.to_yaml_string()?.contains("!Hard $1.00"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct PaywallElementBuilder {
+    id: Option<String>,
+    tags: Vec<String>,
+    teaser: Option<MarkdownTeaser>,
+    redact: Vec<JsonRedaction>,
+    checkout_url: Option<String>,
+    on_error: ErrorPolicy,
+    paywall_conditions: Vec<PaywallCondition>,
+    price_source: Option<PriceSource>,
+}
+
+/// Builds a [config](PaywallConfigV1) in code instead of deserializing it
+#[derive(Default)]
+pub struct PaywallConfigBuilder {
+    elements: Vec<PaywallElement>,
+    currency_selection: CurrencySelection,
+    discounts: Option<DiscountConfig>,
+    tax: Option<TaxConfig>,
+    explain_header: bool,
+    event_sink: Option<Arc<dyn PaywallEventSink>>,
+}
+
+impl PaywallElement {
+    pub fn builder() -> PaywallElementBuilder {
+        PaywallElementBuilder::default()
+    }
+}
+
+impl PaywallConfigV1 {
+    pub fn builder() -> PaywallConfigBuilder {
+        PaywallConfigBuilder::default()
+    }
+}
+
+impl PaywallElementBuilder {
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    pub fn teaser(mut self, teaser: MarkdownTeaser) -> Self {
+        self.teaser = Some(teaser);
+        self
+    }
+
+    pub fn redact(mut self, redaction: JsonRedaction) -> Self {
+        self.redact.push(redaction);
+        self
+    }
+
+    pub fn checkout_url(mut self, checkout_url: &str) -> Self {
+        self.checkout_url = Some(checkout_url.to_string());
+        self
+    }
+
+    pub fn on_error(mut self, on_error: ErrorPolicy) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    /// Add a condition; all conditions must be met
+    pub fn condition(mut self, condition: PaywallCondition) -> Self {
+        self.paywall_conditions.push(condition);
+        self
+    }
+
+    pub fn price(mut self, price_source: PriceSource) -> Self {
+        self.price_source = Some(price_source);
+        self
+    }
+
+    /// Check the id and that a price source is set
+    pub fn build(self) -> Result<PaywallElement, PaywallConfigError> {
+        if let Some(id) = &self.id
+            && !PaywallConfigV1::is_valid_id(id)
+        {
+            return Err(PaywallConfigError::InvalidElementId(id.clone()));
+        }
+        let price_source = self
+            .price_source
+            .ok_or(PaywallConfigError::MissingPriceSource)?;
+
+        Ok(PaywallElement {
+            id: self.id,
+            tags: self.tags,
+            teaser: self.teaser,
+            redact: self.redact,
+            checkout_url: self.checkout_url,
+            on_error: self.on_error,
+            paywall_conditions: self.paywall_conditions,
+            price_source,
+        })
+    }
+}
+
+impl PaywallConfigBuilder {
+    /// Add an element; elements are checked in order
+    pub fn element(mut self, element: PaywallElement) -> Self {
+        self.elements.push(element);
+        self
+    }
+
+    pub fn currency_selection(mut self, currency_selection: CurrencySelection) -> Self {
+        self.currency_selection = currency_selection;
+        self
+    }
+
+    pub fn discounts(mut self, discounts: DiscountConfig) -> Self {
+        self.discounts = Some(discounts);
+        self
+    }
+
+    pub fn tax(mut self, tax: TaxConfig) -> Self {
+        self.tax = Some(tax);
+        self
+    }
+
+    pub fn explain_header(mut self, explain_header: bool) -> Self {
+        self.explain_header = explain_header;
+        self
+    }
+
+    pub fn event_sink(mut self, event_sink: Arc<dyn PaywallEventSink>) -> Self {
+        self.event_sink = Some(event_sink);
+        self
+    }
+
+    /// Check the element ids like [deserialization](PaywallConfigV1::from_yaml_str) does
+    pub fn build(self) -> Result<PaywallConfigV1, PaywallConfigError> {
+        let config = PaywallConfigV1 {
+            paths: self.elements,
+            currency_selection: self.currency_selection,
+            discounts: self.discounts,
+            tax: self.tax,
+            explain_header: self.explain_header,
+            event_sink: self.event_sink.unwrap_or_else(default_event_sink),
+            _version: IgnoredAny,
+            _include: IgnoredAny,
+        };
+        config.validate_ids()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::{CurrencyCode, Money};
+    use crate::paywall_config::{
+        CurrencyConversion, CurrencyTable, CustomCondition, CustomPriceSource, DocumentAndPath,
+        ExpressionVariable, InMemoryPaywallEventSink, PaywallConfigV2, PriceExpression,
+        PriceSourceExtractError, Rounding, register_condition, register_price_source,
+    };
+    use crate::utils::HtmlAttributeSelector;
+    use async_trait::async_trait;
+    use proptest::prelude::*;
+    use serde::Deserialize;
+    use serde::de::DeserializeOwned;
+    use std::sync::{Once, OnceLock};
+
+    fn premium() -> PaywallElement {
+        PaywallElement::builder()
+            .id("premium")
+            .tag("subscription")
+            .condition(PaywallCondition::path_regex("^/premium/.*$").unwrap())
+            .condition(PaywallCondition::css_selector("div.paywall").unwrap())
+            .price(PriceSource::hard("$1.25").unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_build_element() {
+        let element = premium();
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            r#"<html><body><div class="paywall"></div></body></html>"#,
+            "/premium/a",
+        )
+        .unwrap();
+
+        assert_eq!(element.get_id(), Some("premium"));
+        assert!(element.has_tag("subscription"));
+        assert!(element.is_paywalled(&doc_and_path));
+        assert_eq!(
+            element
+                .get_price(&doc_and_path)
+                .into_result()
+                .unwrap()
+                .to_string(),
+            "$1.25"
+        );
+    }
+
+    #[test]
+    fn test_build_checks_element() {
+        assert!(matches!(
+            PaywallElement::builder().id("premium").build(),
+            Err(PaywallConfigError::MissingPriceSource)
+        ));
+        assert!(matches!(
+            PaywallElement::builder()
+                .id("bad id")
+                .price(PriceSource::from_meta("price"))
+                .build(),
+            Err(PaywallConfigError::InvalidElementId(_))
+        ));
+        assert!(PaywallCondition::path_regex("(").is_err());
+        assert!(PriceSource::hard("1.25").is_err());
+    }
+
+    #[test]
+    fn test_build_config_and_serialize() {
+        let config = PaywallConfigV1::builder()
+            .element(premium())
+            .element(
+                PaywallElement::builder()
+                    .condition(PaywallCondition::file_extension("pdf"))
+                    .on_error(ErrorPolicy::Allow)
+                    .price(PriceSource::from_json_ld("Product"))
+                    .build()
+                    .unwrap(),
+            )
+            .event_sink(Arc::new(InMemoryPaywallEventSink::default()))
+            .build()
+            .unwrap();
+
+        let config_yml = config.to_yaml_string().unwrap();
+
+        assert_eq!(
+            config_yml,
+            r#"version: 2
+paths:
+- id: premium
+  tags:
+  - subscription
+  paywall_conditions:
+  - !HasRegexPath ^/premium/.*$
+  - !MatchesCssSelector div.paywall
+  price_source: !Hard $1.25
+- on_error: allow
+  paywall_conditions:
+  - !HasFileExtension pdf
+  price_source: !FromJsonLd Product
+"#
+        );
+        let reloaded = PaywallConfigV2::from_yaml_str(&config_yml).unwrap();
+        assert_eq!(reloaded.get_config().get_elements().len(), 2);
+        assert_eq!(reloaded.get_config().to_yaml_string().unwrap(), config_yml);
+    }
+
+    #[test]
+    fn test_build_config_rejects_duplicate_ids() {
+        assert!(matches!(
+            PaywallConfigV1::builder()
+                .element(premium())
+                .element(premium())
+                .build(),
+            Err(PaywallConfigError::DuplicateElementId(_))
+        ));
+    }
+
+    #[test]
+    fn test_serialize_nested_sources() {
+        let config_yml = r#"
+version: 2
+currency_selection:
+  header: X-Currency
+  cookie: null
+  accept_language: false
+  fallback: EUR
+  document_currency: EUR
+paths:
+- id: research
+  paywall_conditions:
+  - !MetaEquals
+    key: paywall
+    value: 'true'
+  - !JsonPathMatches $.article.premium == true
+  price_source: !FirstOf
+    sources:
+    - !FromHtmlAttribute div#price:::data-price
+    - !Expression
+      expression: base * 2
+      currency: USD
+      variables:
+        base: !HtmlNumber div#base:::data-base
+    - !Hard
+      USD: $5.00
+      EUR: €4.50
+    min: $1.00
+"#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+
+        assert_eq!(config.to_yaml_string().unwrap(), config_yml.trim_start());
+    }
+
+    #[test]
+    fn test_serialize_canonical_forms() {
+        let config_yml = r#"
+paths:
+  - paywall_conditions:
+      - !HasRegexPath '^/(premium|research)/\d+$'
+      - !MatchesCssSelector 'div.paywall,  h1#title'
+    price_source: !Hard { EUR: "1,25 €", CHF: "1.5 CHF", JPY: "¥1,000" }
+"#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let saved = config.to_yaml_string().unwrap();
+
+        assert!(saved.contains(r"!HasRegexPath ^/(premium|research)/\d+$"));
+        assert!(saved.contains("!MatchesCssSelector div.paywall,  h1#title"));
+        assert!(saved.contains("EUR: €1.25\n"));
+        assert!(saved.contains("CHF: CHF 1.50\n"));
+        assert!(saved.contains("JPY: ¥1000\n"));
+        assert_eq!(PaywallConfigV1::from_yaml_str(&saved).unwrap(), config);
+    }
+
+    fn money_strategy() -> impl Strategy<Value = Money> {
+        (
+            0i64..10_000_000,
+            prop::sample::select(vec!["USD", "EUR", "GBP", "JPY", "CHF", "KWD"]),
+        )
+            .prop_map(|(minor_units, code)| {
+                Money::from_minor_units(minor_units, CurrencyCode::new(code).unwrap())
+            })
+    }
+
+    fn selector_strategy() -> impl Strategy<Value = String> {
+        ("[a-z]{1,6}", prop::option::of("[.#][a-z][a-z0-9-]{0,8}"))
+            .prop_map(|(tag, suffix)| tag + suffix.as_deref().unwrap_or_default())
+    }
+
+    fn condition_strategy() -> impl Strategy<Value = PaywallCondition> {
+        prop_oneof![
+            ("[a-z]{1,8}", any::<bool>()).prop_map(|(section, exact)| {
+                let end = if exact { "$" } else { "/.*$" };
+                PaywallCondition::path_regex(&format!("^/{}{}", section, end)).unwrap()
+            }),
+            selector_strategy().prop_map(|s| PaywallCondition::css_selector(&s).unwrap()),
+            "[a-z_]{1,10}".prop_map(|key| PaywallCondition::front_matter_key(&key)),
+            ("[a-z]{1,8}", "[a-z0-9 .]{0,10}")
+                .prop_map(|(key, value)| PaywallCondition::meta_equals(&key, &value)),
+            "[a-z]{1,8}".prop_map(|key| {
+                PaywallCondition::json_path_matches(&format!("$.article.{} == true", key)).unwrap()
+            }),
+            prop::sample::select(vec!["audio/*", "application/pdf", "text/markdown"])
+                .prop_map(PaywallCondition::content_type),
+            "[a-z0-9]{1,4}".prop_map(|extension| PaywallCondition::file_extension(&extension)),
+            any::<u64>().prop_map(PaywallCondition::MinSize),
+        ]
+    }
+
+    /// Types without a public constructor are generated from their YAML form
+    fn from_yaml<T: DeserializeOwned>(yaml: &str) -> T {
+        serde_yml::from_str(yaml).unwrap()
+    }
+
+    /// Tier from a cookie, registered to generate custom conditions
+    #[derive(Deserialize)]
+    struct PropTier(String);
+
+    #[async_trait]
+    impl CustomCondition for PropTier {
+        async fn is_paywalled(&self, doc_and_path: &DocumentAndPath) -> bool {
+            doc_and_path.get_request_context().get_cookie("tier") == Some(self.0.as_str())
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct PropPrice {
+        base: Money,
+    }
+
+    #[async_trait]
+    impl CustomPriceSource for PropPrice {
+        async fn get_price(
+            &self,
+            _doc_and_path: &DocumentAndPath,
+        ) -> Result<Money, PriceSourceExtractError> {
+            Ok(self.base.clone())
+        }
+    }
+
+    fn register() {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            register_condition::<PropTier>("TestPropTier").unwrap();
+            register_price_source::<PropPrice>("TestPropPrice").unwrap();
+        });
+    }
+
+    /// Fresh rates file shared by all generated conversions
+    fn rates_file() -> &'static str {
+        static RATES_FILE: OnceLock<String> = OnceLock::new();
+        RATES_FILE.get_or_init(|| {
+            let path = std::env::temp_dir()
+                .join(format!("rustwall-builder-{}-rates.yml", std::process::id()));
+            std::fs::write(
+                &path,
+                format!(
+                    "base: USD\ntimestamp: {}\nrates:\n  EUR: 0.92\n",
+                    crate::utils::unix_now()
+                ),
+            )
+            .unwrap();
+            path.display().to_string()
+        })
+    }
+
+    fn custom_condition_strategy() -> impl Strategy<Value = PaywallCondition> {
+        "[a-z]{1,8}".prop_map(|tier| {
+            register();
+            from_yaml(&format!("!TestPropTier {}", tier))
+        })
+    }
+
+    fn expression_strategy() -> impl Strategy<Value = PriceExpression> {
+        (
+            0u32..1000,
+            prop::option::of(prop::sample::select(vec!["USD", "EUR", "JPY"])),
+            any::<bool>(),
+            prop::collection::btree_map("v_[a-z]{1,6}", variable_strategy(), 0..4),
+        )
+            .prop_map(|(cents, currency, with_path_regex, variables)| {
+                let mut expression = format!("{}.{:02} + words * 0.001", cents / 100, cents % 100);
+                let mut yaml = String::new();
+                if let Some(currency) = currency {
+                    yaml += &format!("currency: {}\n", currency);
+                }
+                if with_path_regex {
+                    expression += r#" + (if section == "research" then 1 else 0)"#;
+                    yaml += "path_regex: '^/(?P<section>[a-z]+)/'\n";
+                }
+                if !variables.is_empty() {
+                    yaml += "variables:\n";
+                }
+                for (name, variable) in &variables {
+                    match variable {
+                        ExpressionVariable::HtmlNumber(_) => expression += &format!(" + {}", name),
+                        _ => expression += &format!(r#" + (if {} == "gold" then 1 else 0)"#, name),
+                    }
+                    yaml += &format!("  {}: {}", name, serde_yml::to_string(variable).unwrap());
+                }
+                from_yaml(&format!("expression: '{}'\n{}", expression, yaml))
+            })
+    }
+
+    fn variable_strategy() -> impl Strategy<Value = ExpressionVariable> {
+        prop_oneof![
+            (selector_strategy(), "data-[a-z]{1,8}").prop_map(|(selector, attribute)| {
+                ExpressionVariable::HtmlNumber(
+                    HtmlAttributeSelector::new(&selector, &attribute).unwrap(),
+                )
+            }),
+            (selector_strategy(), "data-[a-z]{1,8}").prop_map(|(selector, attribute)| {
+                ExpressionVariable::HtmlText(
+                    HtmlAttributeSelector::new(&selector, &attribute).unwrap(),
+                )
+            }),
+            "[a-z:_]{1,12}".prop_map(ExpressionVariable::Meta),
+            "X-[A-Z][a-z]{1,8}".prop_map(ExpressionVariable::Header),
+            "[a-z_]{1,8}".prop_map(ExpressionVariable::Cookie),
+            "[a-z_]{1,8}".prop_map(ExpressionVariable::Query),
+        ]
+    }
+
+    fn rounding_strategy() -> impl Strategy<Value = Rounding> {
+        prop_oneof![
+            Just(Rounding::Cent),
+            (0u32..100).prop_map(Rounding::Ending),
+            prop::sample::select(vec!["0.05", "0.10", "1", "10"])
+                .prop_map(|step| Rounding::Nearest(step.parse().unwrap())),
+        ]
+    }
+
+    fn leaf_price_strategy() -> impl Strategy<Value = PriceSource> {
+        prop_oneof![
+            money_strategy().prop_map(|price| PriceSource::Hard(CurrencyTable::from(price))),
+            (selector_strategy(), "data-[a-z]{1,8}").prop_map(|(selector, attribute)| {
+                PriceSource::from_html_attribute(&selector, &attribute).unwrap()
+            }),
+            "[a-z:_]{1,12}".prop_map(|key| PriceSource::from_meta(&key)),
+            "[a-z]{1,8}".prop_map(|key| {
+                PriceSource::from_json_path(&format!("$.offers.{}", key)).unwrap()
+            }),
+            "[A-Z][a-z]{0,10}".prop_map(|json_ld_type| PriceSource::from_json_ld(&json_ld_type)),
+            expression_strategy().prop_map(PriceSource::Expression),
+            money_strategy().prop_map(|base| {
+                register();
+                from_yaml(&format!("!TestPropPrice {{ base: \"{}\" }}", base))
+            }),
+        ]
+    }
+
+    fn price_strategy() -> impl Strategy<Value = PriceSource> {
+        leaf_price_strategy().prop_recursive(2, 8, 3, |inner| {
+            prop_oneof![
+                (
+                    prop::collection::vec(inner.clone(), 1..3),
+                    money_strategy(),
+                    0i64..1_000_000,
+                    any::<(bool, bool)>(),
+                )
+                    .prop_map(|(sources, min, spread, (with_min, with_max))| {
+                        let max = min.with_minor_units(min.minor_units() + spread);
+                        let mut fallback = serde_yml::Mapping::new();
+                        fallback.insert("sources".into(), serde_yml::to_value(sources).unwrap());
+                        for (key, bound, with_bound) in
+                            [("min", min, with_min), ("max", max, with_max)]
+                        {
+                            if with_bound {
+                                let table = CurrencyTable::from(bound);
+                                fallback.insert(key.into(), serde_yml::to_value(table).unwrap());
+                            }
+                        }
+                        PriceSource::FirstOf(serde_yml::from_value(fallback.into()).unwrap())
+                    }),
+                (inner, rounding_strategy(), 1u64..1000).prop_map(
+                    |(source, rounding, max_age_hours)| {
+                        PriceSource::Converted(
+                            CurrencyConversion::from_rates_file(
+                                Box::new(source),
+                                rates_file(),
+                                rounding,
+                                max_age_hours,
+                            )
+                            .unwrap(),
+                        )
+                    }
+                ),
+            ]
+        })
+    }
+
+    fn teaser_strategy() -> impl Strategy<Value = MarkdownTeaser> {
+        (0usize..5, 1usize..7).prop_map(|(sections, max_level)| {
+            from_yaml(&format!(
+                "{{ sections: {}, max_level: {} }}",
+                sections, max_level
+            ))
+        })
+    }
+
+    fn redaction_strategy() -> impl Strategy<Value = JsonRedaction> {
+        ("[a-z]{1,8}", prop::option::of(0usize..500)).prop_map(|(field, truncate)| {
+            let truncate = truncate.map(|n| format!(", truncate: {}", n));
+            from_yaml(&format!(
+                "{{ path: $.article.{}{} }}",
+                field,
+                truncate.unwrap_or_default()
+            ))
+        })
+    }
+
+    fn element_strategy() -> impl Strategy<Value = PaywallElementBuilder> {
+        (
+            prop::collection::vec("[a-z]{1,8}", 0..3),
+            prop::option::of(teaser_strategy()),
+            prop::collection::vec(redaction_strategy(), 0..3),
+            prop::option::of("https://shop\\.example/[a-z]{1,8}\\?path=\\{path\\}"),
+            prop_oneof![
+                Just(ErrorPolicy::Deny),
+                Just(ErrorPolicy::Allow),
+                money_strategy().prop_map(|price| ErrorPolicy::FixedPrice(price.into())),
+            ],
+            prop::collection::vec(
+                prop_oneof![4 => condition_strategy(), 1 => custom_condition_strategy()],
+                0..4,
+            ),
+            price_strategy(),
+        )
+            .prop_map(
+                |(tags, teaser, redact, checkout_url, on_error, conditions, price)| {
+                    let mut builder = PaywallElement::builder().on_error(on_error).price(price);
+                    for tag in tags {
+                        builder = builder.tag(&tag);
+                    }
+                    if let Some(teaser) = teaser {
+                        builder = builder.teaser(teaser);
+                    }
+                    for redaction in redact {
+                        builder = builder.redact(redaction);
+                    }
+                    if let Some(checkout_url) = checkout_url {
+                        builder = builder.checkout_url(&checkout_url);
+                    }
+                    for condition in conditions {
+                        builder = builder.condition(condition);
+                    }
+                    builder
+                },
+            )
+    }
+
+    fn currency_code_strategy() -> impl Strategy<Value = String> {
+        prop::sample::select(vec!["USD", "EUR", "GBP", "JPY"]).prop_map(str::to_string)
+    }
+
+    fn currency_selection_strategy() -> impl Strategy<Value = CurrencySelection> {
+        (
+            prop::option::of("X-[A-Z][a-z]{1,8}"),
+            prop::option::of("[a-z_]{1,8}"),
+            any::<bool>(),
+            prop::option::of(currency_code_strategy()),
+            prop::option::of(currency_code_strategy()),
+        )
+            .prop_map(
+                |(header, cookie, accept_language, fallback, document_currency)| {
+                    let value = |v: Option<String>| v.unwrap_or_else(|| "null".to_string());
+                    from_yaml(&format!(
+                        "{{ header: {}, cookie: {}, accept_language: {}, fallback: {}, document_currency: {} }}",
+                        value(header),
+                        value(cookie),
+                        accept_language,
+                        value(fallback),
+                        value(document_currency)
+                    ))
+                },
+            )
+    }
+
+    fn discount_strategy() -> impl Strategy<Value = String> {
+        prop_oneof![
+            (0u32..=100).prop_map(|percent| format!("!Percent {}%", percent)),
+            money_strategy().prop_map(|amount| format!("!Fixed \"{}\"", amount)),
+        ]
+    }
+
+    fn timestamp_strategy() -> impl Strategy<Value = Option<u64>> {
+        prop::option::of(1_600_000_000u64..2_000_000_000)
+    }
+
+    fn discounts_strategy() -> impl Strategy<Value = DiscountConfig> {
+        let code = (
+            discount_strategy(),
+            prop::collection::vec("[a-z]{1,8}", 0..2),
+            prop::option::of(1u64..1000),
+            timestamp_strategy(),
+        );
+        let promotion = (
+            "[a-z-]{1,10}",
+            discount_strategy(),
+            prop::collection::vec("/[a-z]{1,8}/\\*\\*", 0..2),
+            timestamp_strategy(),
+            timestamp_strategy(),
+        );
+        (
+            "[a-z_]{1,8}",
+            "[a-z_]{1,12}",
+            prop::collection::btree_map("[A-Z0-9]{3,10}", code, 0..3),
+            prop::collection::vec(promotion, 0..3),
+        )
+            .prop_map(|(query_param, cookie, codes, promotions)| {
+                let mut yaml = format!(
+                    "code_query_param: {}\ncode_cookie: {}\ncodes:\n",
+                    query_param, cookie
+                );
+                for (code, (discount, elements, max_uses, expires)) in codes {
+                    yaml += &format!(
+                        "  - {{ code: {}, discount: {}, elements: {:?}",
+                        code, discount, elements
+                    );
+                    if let Some(max_uses) = max_uses {
+                        yaml += &format!(", max_uses: {}", max_uses);
+                    }
+                    if let Some(expires) = expires {
+                        yaml += &format!(", expires: {}", expires);
+                    }
+                    yaml += " }\n";
+                }
+                yaml += "promotions:\n";
+                for (name, discount, paths, starts, until) in promotions {
+                    yaml += &format!(
+                        "  - {{ name: {}, discount: {}, paths: {:?}",
+                        name, discount, paths
+                    );
+                    for (key, timestamp) in [("starts", starts), ("until", until)] {
+                        if let Some(timestamp) = timestamp {
+                            yaml += &format!(", {}: {}", key, timestamp);
+                        }
+                    }
+                    yaml += " }\n";
+                }
+                from_yaml(&yaml)
+            })
+    }
+
+    fn tax_strategy() -> impl Strategy<Value = TaxConfig> {
+        let country = || prop::sample::select(vec!["DE", "FR", "AT", "CH", "US"]);
+        let display = || prop::sample::select(vec!["inclusive", "exclusive"]);
+        (
+            "X-[A-Z][a-z]{1,8}",
+            prop::option::of("[a-z_]{1,8}"),
+            prop::option::of(country()),
+            display(),
+            display(),
+            prop::collection::btree_map(country(), 0u32..=100, 0..4),
+        )
+            .prop_map(
+                |(header, cookie, default_country, overlay, checkout, rates)| {
+                    let mut yaml = format!(
+                        "country_header: {}\noverlay_display: {}\ncheckout_display: {}\nrates: {{",
+                        header, overlay, checkout
+                    );
+                    for (country, percent) in rates {
+                        yaml += &format!(" {}: {}%,", country, percent);
+                    }
+                    yaml += " }\n";
+                    if let Some(cookie) = cookie {
+                        yaml += &format!("country_cookie: {}\n", cookie);
+                    }
+                    if let Some(default_country) = default_country {
+                        yaml += &format!("default_country: {}\n", default_country);
+                    }
+                    from_yaml(&yaml)
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_save_load_round_trip(
+            elements in prop::collection::vec((any::<bool>(), element_strategy()), 1..4),
+            currency_selection in currency_selection_strategy(),
+            discounts in prop::option::of(discounts_strategy()),
+            tax in prop::option::of(tax_strategy()),
+            explain_header in any::<bool>(),
+        ) {
+            let mut builder = PaywallConfigV1::builder()
+                .currency_selection(currency_selection)
+                .explain_header(explain_header);
+            if let Some(discounts) = discounts {
+                builder = builder.discounts(discounts);
+            }
+            if let Some(tax) = tax {
+                builder = builder.tax(tax);
+            }
+            for (i, (with_id, element)) in elements.into_iter().enumerate() {
+                let element = if with_id { element.id(&format!("element-{}", i)) } else { element };
+                builder = builder.element(element.build().unwrap());
+            }
+            let config = builder.build().unwrap();
+
+            let saved = config.to_yaml_string().unwrap();
+            let loaded = PaywallConfigV1::from_yaml_str(&saved).unwrap();
+
+            prop_assert_eq!(&loaded, &config);
+            prop_assert_eq!(loaded.to_yaml_string().unwrap(), saved);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::Path;
+
+use serde_yml::Value;
+use serde_yml::value::{Tag, TaggedValue};
+use thiserror::Error;
+
+/// File format of a config, selected by file extension: `.json` and `.toml` files are read as
+/// JSON and TOML, everything else as YAML
+///
+/// JSON and TOML have no tags, so a YAML tag is written as a map with the tag as its only key:
+/// `!Hard $1.25` becomes `{ "!Hard": "$1.25" }` in JSON and `{ "!Hard" = "$1.25" }` in TOML
+/// # Examples
+/// ```json
+/// {
+///   "paths": [
+///     {
+///       "id": "premium",
+///       "paywall_conditions": [{ "!HasRegexPath": "^/premium/.*$" }],
+///       "price_source": { "!Hard": "$1.25" }
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigFormatError {
+    #[error("{0}")]
+    Yaml(#[from] serde_yml::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+impl ConfigFormatError {
+    /// 1-based line and column of the error, if known
+    pub fn get_location(&self, config: &str) -> Option<(usize, usize)> {
+        match self {
+            ConfigFormatError::Yaml(e) => e.location().map(|l| (l.line(), l.column())),
+            ConfigFormatError::Json(e) => (e.line() > 0).then(|| (e.line(), e.column())),
+            ConfigFormatError::Toml(e) => e.span().map(|span| {
+                let before = &config[..span.start.min(config.len())];
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                (
+                    before.matches('\n').count() + 1,
+                    before[line_start..].chars().count() + 1,
+                )
+            }),
+        }
+    }
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> ConfigFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    /// Parse a config into a YAML value, with `{ "!Tag": value }` maps of JSON and TOML turned
+    /// into tagged values
+    pub fn parse(&self, config: &str) -> Result<Value, ConfigFormatError> {
+        match self {
+            ConfigFormat::Yaml => Ok(serde_yml::from_str(config)?),
+            ConfigFormat::Json => Ok(untag_maps(serde_json::from_str(config)?)),
+            ConfigFormat::Toml => Ok(untag_maps(toml::from_str(config)?)),
+        }
+    }
+}
+
+/// Replace maps with a single `!Tag` key by the tagged value
+fn untag_maps(value: Value) -> Value {
+    match value {
+        Value::Mapping(map) => {
+            if map.len() == 1
+                && let Some((Value::String(key), _)) = map.iter().next()
+                && key.len() > 1
+                && key.starts_with('!')
+            {
+                let tag = Tag::new(&key[1..]);
+                let (_, value) = map.into_iter().next().unwrap_or_default();
+                return Value::Tagged(Box::new(TaggedValue {
+                    tag,
+                    value: untag_maps(value),
+                }));
+            }
+
+            Value::Mapping(
+                map.into_iter()
+                    .map(|(key, value)| (key, untag_maps(value)))
+                    .collect(),
+            )
+        }
+        Value::Sequence(values) => Value::Sequence(values.into_iter().map(untag_maps).collect()),
+        Value::Tagged(mut tagged) => {
+            tagged.value = untag_maps(tagged.value);
+            Value::Tagged(tagged)
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paywall_config::{DocumentAndPath, PaywallConfigV1};
+
+    const YAML_CONFIG: &str = r#"
+paths:
+  - id: premium
+    paywall_conditions:
+      - !HasRegexPath "^/premium/.*$"
+      - !MetaEquals { key: paywall, value: true }
+    price_source: !FirstOf
+      - !FromMeta { key: price }
+      - !Hard { USD: "$1.25", EUR: "€1.10" }
+"#;
+
+    const JSON_CONFIG: &str = r#"{
+  "paths": [
+    {
+      "id": "premium",
+      "paywall_conditions": [
+        { "!HasRegexPath": "^/premium/.*$" },
+        { "!MetaEquals": { "key": "paywall", "value": true } }
+      ],
+      "price_source": {
+        "!FirstOf": [
+          { "!FromMeta": { "key": "price" } },
+          { "!Hard": { "USD": "$1.25", "EUR": "€1.10" } }
+        ]
+      }
+    }
+  ]
+}"#;
+
+    const TOML_CONFIG: &str = r#"
+[[paths]]
+id = "premium"
+paywall_conditions = [
+  { "!HasRegexPath" = "^/premium/.*$" },
+  { "!MetaEquals" = { key = "paywall", value = true } },
+]
+price_source = { "!FirstOf" = [
+  { "!FromMeta" = { key = "price" } },
+  { "!Hard" = { USD = "$1.25", EUR = "€1.10" } },
+] }
+"#;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("paywall.json")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("paywall.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("paywall.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("paywall")),
+            ConfigFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn test_formats_are_equivalent() {
+        let yaml = ConfigFormat::Yaml.parse(YAML_CONFIG).unwrap();
+
+        assert_eq!(ConfigFormat::Json.parse(JSON_CONFIG).unwrap(), yaml);
+        assert_eq!(ConfigFormat::Toml.parse(TOML_CONFIG).unwrap(), yaml);
+
+        let config: PaywallConfigV1 =
+            serde_yml::from_value(ConfigFormat::Toml.parse(TOML_CONFIG).unwrap()).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            r#"<html><head><meta name="rustwall:paywall" content="true"></head></html>"#,
+            "/premium/a",
+        )
+        .unwrap();
+        assert_eq!(
+            config.get_elements()[0]
+                .get_price(&doc_and_path)
+                .into_result()
+                .unwrap()
+                .to_string(),
+            "$1.25"
+        );
+    }
+
+    #[test]
+    fn test_error_location() {
+        let config = "[[paths]]\nid = \"a\"\nprice_source = \n";
+        let error = ConfigFormat::Toml.parse(config).unwrap_err();
+        assert_eq!(error.get_location(config).map(|l| l.0), Some(3));
+
+        let config = "{\n  \"paths\": [,]\n}";
+        let error = ConfigFormat::Json.parse(config).unwrap_err();
+        assert_eq!(error.get_location(config), Some((2, 13)));
+    }
+}
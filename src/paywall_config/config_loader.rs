@@ -0,0 +1,1052 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yml::{Mapping, Value};
+use thiserror::Error;
+
+use super::config_version::get_version;
+use super::validation::validate_source;
+use super::{ConfigFormat, ConfigFormatError, ConfigIssue};
+use super::{PaywallConfigError, PaywallConfigV1, PaywallElement};
+use crate::utils::{PathGlob, with_config_dir};
+
+/// Loads a [config](PaywallConfigV1) spread over several files, resolving everything before
+/// deserialization:
+/// - `include: [sections/*.yml]` appends the `paths` of other files, relative to the including
+///   file; included files contain a list of elements or a map with `paths` and `include`
+/// - files of any supported `version` can be mixed, see [PaywallConfig](super::PaywallConfig)
+/// - `${ENV_VAR}` and `${ENV_VAR:-default}` in string values are replaced by environment
+///   variables, `$${` is a literal `${`
+/// - overlays, e.g. per environment, are merged over the config in order: maps are merged key
+///   by key and elements with the id of an existing element are merged into it
+/// - relative `rates_file` paths are resolved against the directory of the file they are in
+/// - YAML anchors and `<<` merge keys work within each file
+/// - files may also be JSON or TOML, see [ConfigFormat](super::ConfigFormat)
+/// # Examples
+/// ```yaml
+/// include: [sections/*.yml]
+/// paths:
+///   - id: premium
+///     paywall_conditions: [ !HasRegexPath "^/premium/.*$" ]
+///     price_source: !Hard "${PREMIUM_PRICE:-$1.25}"
+/// ```
+pub struct ConfigLoader {
+    file: PathBuf,
+    overlays: Vec<PathBuf>,
+    env: HashMap<String, String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigLoadError {
+    #[error("{}: cannot read config: {error}", .file.display())]
+    Read { file: PathBuf, error: String },
+    #[error("{}: {error}", .file.display())]
+    Parse {
+        file: PathBuf,
+        error: ConfigFormatError,
+    },
+    #[error("{}: undefined environment variable '{name}'", .file.display())]
+    UndefinedVariable { file: PathBuf, name: String },
+    #[error("{}: {error}", .file.display())]
+    Include { file: PathBuf, error: String },
+    #[error("{}: paths element {element}: {error}", .file.display())]
+    Element {
+        file: PathBuf,
+        element: String,
+        error: String,
+    },
+    #[error("{}: '{key}': {error}", .file.display())]
+    Setting {
+        file: PathBuf,
+        key: String,
+        error: String,
+    },
+}
+
+impl ConfigLoadError {
+    /// File at fault
+    pub fn get_file(&self) -> &Path {
+        match self {
+            ConfigLoadError::Read { file, .. }
+            | ConfigLoadError::Parse { file, .. }
+            | ConfigLoadError::UndefinedVariable { file, .. }
+            | ConfigLoadError::Include { file, .. }
+            | ConfigLoadError::Element { file, .. }
+            | ConfigLoadError::Setting { file, .. } => file,
+        }
+    }
+}
+
+/// Paywall element with the file it was last defined in
+#[derive(Debug, Clone)]
+struct ElementSource {
+    value: Value,
+    file: PathBuf,
+}
+
+/// All files merged into one config, not yet deserialized
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    settings: Mapping,
+    setting_files: HashMap<String, PathBuf>,
+    elements: Vec<ElementSource>,
+    files: Vec<PathBuf>,
+}
+
+impl ConfigLoader {
+    pub fn new(file: &Path) -> ConfigLoader {
+        ConfigLoader {
+            file: file.to_path_buf(),
+            overlays: Vec::new(),
+            env: HashMap::new(),
+        }
+    }
+
+    /// Merge `file` over the config; later overlays win
+    pub fn with_overlay(mut self, file: &Path) -> ConfigLoader {
+        self.overlays.push(file.to_path_buf());
+        self
+    }
+
+    /// Add the overlay for an environment next to the config, `paywall.staging.yml` for
+    /// `paywall.yml` and `staging`, if it exists
+    pub fn with_environment(self, environment: &str) -> ConfigLoader {
+        let overlay = match self.file.extension().and_then(|e| e.to_str()) {
+            Some(extension) => self
+                .file
+                .with_extension(format!("{}.{}", environment, extension)),
+            None => self.file.with_extension(environment),
+        };
+
+        if overlay.is_file() {
+            self.with_overlay(&overlay)
+        } else {
+            self
+        }
+    }
+
+    /// Set a variable for interpolation, taking precedence over the process environment
+    pub fn with_env(mut self, name: &str, value: &str) -> ConfigLoader {
+        self.env.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn load(&self) -> Result<PaywallConfigV1, ConfigLoadError> {
+        self.resolve()?.into_config()
+    }
+
+    /// Check all files of the config and report every problem found, each in the file it is
+    /// in; variables are interpolated before the files are [validated](super::validate) one by
+    /// one, overlays are only checked once merged as they may hold partial elements
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let resolved = match self.resolve() {
+            Ok(resolved) => resolved,
+            Err(e) => return vec![ConfigIssue::from_load_error(&e)],
+        };
+
+        let mut issues = Vec::new();
+        for file in resolved.get_files() {
+            if self.overlays.contains(file) {
+                continue;
+            }
+            let Ok(source) = fs::read_to_string(file) else {
+                continue;
+            };
+            let config = match self.interpolate_source(&source) {
+                Ok(config) => config,
+                Err(issue) => {
+                    issues.push(ConfigIssue {
+                        file: Some(file.clone()),
+                        ..issue
+                    });
+                    continue;
+                }
+            };
+
+            let format = ConfigFormat::from_path(file);
+            issues.extend(
+                with_config_dir(base_dir(file), || validate_source(&config, format))
+                    .into_iter()
+                    .map(|issue| ConfigIssue {
+                        file: Some(file.clone()),
+                        ..issue
+                    }),
+            );
+        }
+
+        // problems across files, like duplicate ids, or in overlays
+        if let Err(e) = resolved.into_config() {
+            let issue = ConfigIssue::from_load_error(&e);
+            if !issues.iter().any(|i| i.file == issue.file) {
+                issues.push(issue);
+            }
+        }
+
+        issues
+    }
+
+    /// Read, interpolate and merge all files
+    pub fn resolve(&self) -> Result<ResolvedConfig, ConfigLoadError> {
+        let mut resolved = ResolvedConfig {
+            settings: Mapping::new(),
+            setting_files: HashMap::new(),
+            elements: Vec::new(),
+            files: Vec::new(),
+        };
+
+        for (i, file) in std::iter::once(&self.file)
+            .chain(&self.overlays)
+            .enumerate()
+        {
+            let (settings, elements) =
+                self.load_file(file, &mut Vec::new(), &mut resolved.files)?;
+
+            for (key, value) in settings {
+                if let Value::String(name) = &key {
+                    resolved.setting_files.insert(name.clone(), file.clone());
+                }
+                match resolved.settings.get_mut(&key) {
+                    Some(base) => merge_value(base, value),
+                    None => {
+                        resolved.settings.insert(key, value);
+                    }
+                }
+            }
+
+            if i == 0 {
+                resolved.elements = elements;
+                continue;
+            }
+            for element in elements {
+                let id = element.value.get("id").cloned();
+                let existing = id.and_then(|id| {
+                    resolved
+                        .elements
+                        .iter_mut()
+                        .find(|e| e.value.get("id") == Some(&id))
+                });
+
+                match existing {
+                    Some(existing) => {
+                        merge_value(&mut existing.value, element.value);
+                        existing.file = element.file;
+                    }
+                    None => resolved.elements.push(element),
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Settings and elements of a file and the files it includes; `stack` holds the including
+    /// files to detect cycles
+    fn load_file(
+        &self,
+        file: &Path,
+        stack: &mut Vec<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<(Mapping, Vec<ElementSource>), ConfigLoadError> {
+        let canonical = file.canonicalize().map_err(|e| ConfigLoadError::Read {
+            file: file.to_path_buf(),
+            error: e.to_string(),
+        })?;
+        if stack.contains(&canonical) {
+            return Err(ConfigLoadError::Include {
+                file: file.to_path_buf(),
+                error: "file includes itself".to_string(),
+            });
+        }
+
+        let config = fs::read_to_string(file).map_err(|e| ConfigLoadError::Read {
+            file: file.to_path_buf(),
+            error: e.to_string(),
+        })?;
+        let parse_error = |error| ConfigLoadError::Parse {
+            file: file.to_path_buf(),
+            error,
+        };
+        let mut value = ConfigFormat::from_path(file)
+            .parse(&config)
+            .map_err(parse_error)?;
+        value
+            .apply_merge()
+            .map_err(|e| parse_error(ConfigFormatError::Yaml(e)))?;
+        self.interpolate(&mut value)
+            .map_err(|name| ConfigLoadError::UndefinedVariable {
+                file: file.to_path_buf(),
+                name,
+            })?;
+        files.push(file.to_path_buf());
+
+        let include_error = |error: String| ConfigLoadError::Include {
+            file: file.to_path_buf(),
+            error,
+        };
+        let is_included = !stack.is_empty();
+        let (mut settings, paths) = match value {
+            Value::Sequence(paths) if is_included => (Mapping::new(), paths),
+            Value::Mapping(mut settings) => {
+                get_version(&Value::Mapping(settings.clone()))
+                    .map_err(|e| include_error(e.to_string()))?;
+                settings.remove("version");
+
+                let paths = match settings.remove("paths") {
+                    Some(Value::Sequence(paths)) => paths,
+                    None | Some(Value::Null) => Vec::new(),
+                    Some(_) => return Err(include_error("'paths' must be a list".to_string())),
+                };
+                (settings, paths)
+            }
+            Value::Null => (Mapping::new(), Vec::new()),
+            _ if is_included => {
+                return Err(include_error(
+                    "included file must be a list of elements or a map".to_string(),
+                ));
+            }
+            _ => return Err(include_error("config must be a map".to_string())),
+        };
+
+        let mut elements: Vec<ElementSource> = paths
+            .into_iter()
+            .map(|value| ElementSource {
+                value,
+                file: file.to_path_buf(),
+            })
+            .collect();
+
+        let patterns = match settings.remove("include") {
+            Some(Value::String(pattern)) => vec![pattern],
+            Some(Value::Sequence(patterns)) => patterns
+                .into_iter()
+                .map(|p| match p {
+                    Value::String(pattern) => Ok(pattern),
+                    _ => Err(include_error(
+                        "include patterns must be strings".to_string(),
+                    )),
+                })
+                .collect::<Result<_, _>>()?,
+            None | Some(Value::Null) => Vec::new(),
+            Some(_) => {
+                return Err(include_error(
+                    "'include' must be a pattern or a list of patterns".to_string(),
+                ));
+            }
+        };
+        if is_included && !settings.is_empty() {
+            return Err(include_error(
+                "included files may only contain 'version', 'paths' and 'include'".to_string(),
+            ));
+        }
+
+        stack.push(canonical);
+        for pattern in patterns {
+            for included in expand_include(base_dir(file), &pattern).map_err(include_error)? {
+                let (_, included_elements) = self.load_file(&included, stack, files)?;
+                elements.extend(included_elements);
+            }
+        }
+        stack.pop();
+
+        Ok((settings, elements))
+    }
+
+    fn lookup_env(&self, name: &str) -> Option<String> {
+        self.env
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    }
+
+    /// Interpolate the text of a file line by line for validation, leaving comment lines as
+    /// they are; the first undefined variable is returned as issue at its position
+    fn interpolate_source(&self, source: &str) -> Result<String, ConfigIssue> {
+        source
+            .split_inclusive('\n')
+            .enumerate()
+            .map(
+                |(i, line)| match interpolate_str(line, |name| self.lookup_env(name)) {
+                    Ok(line) => Ok(line),
+                    Err(_) if line.trim_start().starts_with('#') => Ok(line.to_string()),
+                    Err(name) => Err(ConfigIssue {
+                        file: None,
+                        line: i + 1,
+                        column: line.find(&format!("${{{}", name)).map_or(1, |c| c + 1),
+                        message: format!("undefined environment variable '{}'", name),
+                    }),
+                },
+            )
+            .collect()
+    }
+
+    /// Interpolate all string values, returning the name of the first undefined variable
+    fn interpolate(&self, value: &mut Value) -> Result<(), String> {
+        match value {
+            Value::String(s) if s.contains("${") => {
+                *s = interpolate_str(s, |name| self.lookup_env(name))?;
+                Ok(())
+            }
+            Value::Sequence(items) => items.iter_mut().try_for_each(|v| self.interpolate(v)),
+            Value::Mapping(map) => map.values_mut().try_for_each(|v| self.interpolate(v)),
+            Value::Tagged(tagged) => self.interpolate(&mut tagged.value),
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => Ok(()),
+        }
+    }
+}
+
+impl ResolvedConfig {
+    /// Every file that was read, including overlays and included files
+    pub fn get_files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Deserialize the merged config, attributing errors to the file that defined the bad value
+    pub fn into_config(self) -> Result<PaywallConfigV1, ConfigLoadError> {
+        let mut paths = Vec::new();
+        let mut seen: HashMap<String, &Path> = HashMap::new();
+
+        for (i, source) in self.elements.iter().enumerate() {
+            let id = source.value.get("id").and_then(Value::as_str);
+            let element_error = |error: String| ConfigLoadError::Element {
+                file: source.file.clone(),
+                element: id.map_or(format!("#{}", i), |id| format!("'{}'", id)),
+                error,
+            };
+
+            let element: PaywallElement = with_config_dir(base_dir(&source.file), || {
+                serde_yml::from_value(source.value.clone())
+            })
+            .map_err(|e| element_error(e.to_string()))?;
+
+            if let Some(id) = element.get_id() {
+                if !PaywallConfigV1::is_valid_id(id) {
+                    return Err(element_error(
+                        PaywallConfigError::InvalidElementId(id.to_string()).to_string(),
+                    ));
+                }
+                if let Some(first) = seen.insert(id.to_string(), &source.file) {
+                    return Err(element_error(format!(
+                        "{}, first defined in {}",
+                        PaywallConfigError::DuplicateElementId(id.to_string()),
+                        first.display()
+                    )));
+                }
+            }
+            paths.push(element);
+        }
+
+        let deserialize_settings = |settings: Mapping| {
+            let mut settings = settings;
+            settings.insert(Value::from("paths"), Value::Sequence(Vec::new()));
+            serde_yml::from_value::<PaywallConfigV1>(Value::Mapping(settings))
+        };
+
+        // `tax` is the only setting naming a file
+        let settings_dir = self
+            .setting_files
+            .get("tax")
+            .or(self.files.first())
+            .map_or(Path::new("."), |file| base_dir(file));
+        let deserialize_settings =
+            |settings: Mapping| with_config_dir(settings_dir, || deserialize_settings(settings));
+
+        let mut config = match deserialize_settings(self.settings.clone()) {
+            Ok(config) => config,
+            Err(error) => {
+                // find the setting at fault by deserializing each on its own
+                let (key, error) = self
+                    .settings
+                    .iter()
+                    .find_map(|(key, value)| {
+                        let mut single = Mapping::new();
+                        single.insert(key.clone(), value.clone());
+                        deserialize_settings(single)
+                            .err()
+                            .map(|e| (key.as_str().unwrap_or_default().to_string(), e))
+                    })
+                    .unwrap_or((String::new(), error));
+                let file = self
+                    .setting_files
+                    .get(&key)
+                    .or(self.files.first())
+                    .cloned()
+                    .unwrap_or_default();
+
+                return Err(ConfigLoadError::Setting {
+                    file,
+                    key,
+                    error: error.to_string(),
+                });
+            }
+        };
+
+        config.paths = paths;
+        Ok(config)
+    }
+}
+
+/// Directory relative paths in a config file, like `rates_file`, are resolved against
+fn base_dir(file: &Path) -> &Path {
+    file.parent().unwrap_or(Path::new("."))
+}
+
+/// Merge `overlay` into `base`: maps key by key, any other value is replaced
+fn merge_value(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Files matching an include pattern relative to `base_dir`, sorted by name; wildcards are
+/// supported in the file name only
+fn expand_include(base_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let pattern_path = base_dir.join(pattern);
+    let file_pattern = pattern_path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or_else(|| format!("invalid include pattern '{}'", pattern))?;
+    let dir = pattern_path.parent().unwrap_or(base_dir);
+
+    if dir.to_string_lossy().contains(['*', '?']) {
+        return Err(format!(
+            "include pattern '{}' may only use wildcards in the file name",
+            pattern
+        ));
+    }
+    if !file_pattern.contains(['*', '?']) {
+        return Ok(vec![pattern_path]);
+    }
+
+    let glob = PathGlob::new(file_pattern);
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("cannot read include directory '{}': {}", dir.display(), e))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .and_then(|f| f.to_str())
+                    .is_some_and(|f| glob.is_match(f))
+        })
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+/// Replace `${NAME}` and `${NAME:-default}`, returning the first undefined variable as error
+fn interpolate_str<F>(s: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result = String::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let Some(end) = rest[start..].find('}').map(|i| start + i) else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+
+        let expression = &rest[start + 2..end];
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+        let value = lookup(name)
+            .filter(|v| default.is_none() || !v.is_empty())
+            .or(default.map(str::to_string))
+            .ok_or_else(|| name.to_string())?;
+
+        result.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::paywall_config::DocumentAndPath;
+    use std::str::FromStr;
+
+    fn config_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rustwall-config-loader-{}-{}",
+            std::process::id(),
+            name
+        ));
+        for (file, content) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn price_at(config: &PaywallConfigV1, path: &str) -> String {
+        let doc_and_path =
+            DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", path)
+                .unwrap();
+        config.get_price(&doc_and_path).to_string()
+    }
+
+    #[test]
+    fn test_interpolate_str() {
+        let lookup = |name: &str| (name == "PRICE").then(|| "$2.00".to_string());
+
+        assert_eq!(interpolate_str("${PRICE}", lookup).unwrap(), "$2.00");
+        assert_eq!(
+            interpolate_str("a ${MISSING:-b} c", lookup).unwrap(),
+            "a b c"
+        );
+        assert_eq!(interpolate_str("$${PRICE}", lookup).unwrap(), "${PRICE}");
+        assert_eq!(
+            interpolate_str("${MISSING}", lookup).unwrap_err(),
+            "MISSING"
+        );
+    }
+
+    #[test]
+    fn test_includes_and_env_interpolation() {
+        let dir = config_dir(
+            "includes",
+            &[
+                (
+                    "paywall.yml",
+                    r#"
+include: [sections/*.yml]
+paths:
+  - id: premium
+    paywall_conditions: [ !HasRegexPath "^/premium/.*$" ]
+    price_source: !Hard "${PREMIUM_PRICE:-$1.25}"
+"#,
+                ),
+                (
+                    "sections/a-research.yml",
+                    r#"
+- id: research
+  paywall_conditions: [ !HasRegexPath "^/research/.*$" ]
+  price_source: !Hard $5.00
+"#,
+                ),
+                (
+                    "sections/b-archive.yml",
+                    r#"
+paths:
+  - &archive
+    id: archive
+    paywall_conditions: [ !HasRegexPath "^/archive/.*$" ]
+    price_source: !Hard $0.50
+  - <<: *archive
+    id: old-archive
+    paywall_conditions: [ !HasRegexPath "^/old/.*$" ]
+"#,
+                ),
+            ],
+        );
+
+        let loader = ConfigLoader::new(&dir.join("paywall.yml")).with_env("PREMIUM_PRICE", "$2.00");
+        let resolved = loader.resolve().unwrap();
+        assert_eq!(resolved.get_files().len(), 3);
+
+        let config = resolved.into_config().unwrap();
+        let ids: Vec<_> = config
+            .get_elements()
+            .iter()
+            .filter_map(|e| e.get_id())
+            .collect();
+
+        assert_eq!(ids, vec!["premium", "research", "archive", "old-archive"]);
+        assert_eq!(
+            price_at(&config, "/premium/a"),
+            "element 'premium': price $2.00"
+        );
+        assert_eq!(
+            price_at(&config, "/old/a"),
+            "element 'old-archive': price $0.50"
+        );
+    }
+
+    #[test]
+    fn test_overlay_merges_settings_and_elements() {
+        let dir = config_dir(
+            "overlay",
+            &[
+                (
+                    "paywall.yml",
+                    r#"
+currency_selection:
+  fallback: USD
+paths:
+  - id: premium
+    paywall_conditions: [ !HasRegexPath "^/premium/.*$" ]
+    price_source: !Hard $1.25
+"#,
+                ),
+                (
+                    "paywall.staging.yml",
+                    r#"
+paths:
+  - id: premium
+    price_source: !Hard $0.01
+  - id: test
+    paywall_conditions: [ !HasRegexPath "^/test/.*$" ]
+    price_source: !Hard $0.02
+"#,
+                ),
+            ],
+        );
+
+        let config = ConfigLoader::new(&dir.join("paywall.yml"))
+            .with_environment("staging")
+            .with_environment("production")
+            .load()
+            .unwrap();
+
+        assert_eq!(config.get_elements().len(), 2);
+        assert_eq!(
+            config.get_elements()[0]
+                .get_price(
+                    &DocumentAndPath::new_from_html_and_path_str("<html></html>", "/premium/a")
+                        .unwrap()
+                )
+                .into_result()
+                .unwrap(),
+            Money::from_str("$0.01").unwrap()
+        );
+        assert_eq!(price_at(&config, "/test/a"), "element 'test': price $0.02");
+    }
+
+    #[test]
+    fn test_errors_point_to_source_file() {
+        let dir = config_dir(
+            "errors",
+            &[
+                (
+                    "paywall.yml",
+                    r#"
+include: sections/*.yml
+paths:
+  - id: premium
+    paywall_conditions: []
+    price_source: !Hard $1.25
+"#,
+                ),
+                (
+                    "sections/bad.yml",
+                    r#"
+- id: bad
+  paywall_conditions: []
+  price_source: !Hard 1.25
+"#,
+                ),
+                ("env.yml", "tax: { rate: \"${UNDEFINED_RUSTWALL_VAR}\" }\n"),
+                ("settings.yml", "currency_selection: 5\n"),
+                (
+                    "dup.yml",
+                    "paths:\n  - id: premium\n    price_source: !Hard $1.00\n",
+                ),
+            ],
+        );
+        let main = dir.join("paywall.yml");
+
+        match ConfigLoader::new(&main).load() {
+            Err(ConfigLoadError::Element { file, element, .. }) => {
+                assert_eq!(file, dir.join("sections/bad.yml"));
+                assert_eq!(element, "'bad'");
+            }
+            _ => panic!("Expected Element error"),
+        }
+
+        fs::remove_file(dir.join("sections/bad.yml")).unwrap();
+        let with_overlay = |overlay: &str| {
+            ConfigLoader::new(&main)
+                .with_overlay(&dir.join(overlay))
+                .load()
+        };
+
+        match with_overlay("env.yml") {
+            Err(ConfigLoadError::UndefinedVariable { file, name }) => {
+                assert_eq!(file, dir.join("env.yml"));
+                assert_eq!(name, "UNDEFINED_RUSTWALL_VAR");
+            }
+            _ => panic!("Expected UndefinedVariable error"),
+        }
+        match with_overlay("settings.yml") {
+            Err(ConfigLoadError::Setting { file, key, .. }) => {
+                assert_eq!(file, dir.join("settings.yml"));
+                assert_eq!(key, "currency_selection");
+            }
+            _ => panic!("Expected Setting error"),
+        }
+        assert!(with_overlay("dup.yml").is_ok());
+
+        fs::write(
+            dir.join("sections/dup.yml"),
+            "- id: premium\n  paywall_conditions: []\n  price_source: !Hard $1.00\n",
+        )
+        .unwrap();
+        let error = ConfigLoader::new(&main).load().err().unwrap().to_string();
+        assert!(
+            error.contains("sections/dup.yml: paths element 'premium': Duplicate"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_issues_per_file() {
+        let dir = config_dir(
+            "validate",
+            &[
+                (
+                    "paywall.yml",
+                    r#"include: sections/*.yml
+paths:
+  - id: premium
+    paywall_conditions: []
+    price_source: !Hard "${RUSTWALL_TEST_UNSET_PRICE:-$1.25}"
+  - id: broken
+    paywall_conditions: []
+    price_source: !Hard 2.00
+"#,
+                ),
+                (
+                    "sections/a.yml",
+                    "- id: a\n  paywall_conditions: []\n  price_source: !Hard 1.00\n",
+                ),
+                (
+                    "sections/b.yml",
+                    "- id: premium\n  paywall_conditions: []\n  price_source: !Hard $1.00\n",
+                ),
+                ("overlay.yml", "currency_selection: 5\n"),
+            ],
+        );
+        let main = dir.join("paywall.yml");
+
+        let issues = ConfigLoader::new(&main).validate();
+        let located: Vec<(PathBuf, usize)> = issues
+            .iter()
+            .map(|i| (i.file.clone().unwrap(), i.line))
+            .collect();
+        assert_eq!(
+            located,
+            vec![(main.clone(), 8), (dir.join("sections/a.yml"), 3)],
+            "{:?}",
+            issues
+        );
+
+        fs::write(&main, "include: sections/*.yml\n").unwrap();
+        fs::remove_file(dir.join("sections/a.yml")).unwrap();
+        assert_eq!(ConfigLoader::new(&main).validate(), vec![]);
+
+        let issues = ConfigLoader::new(&main)
+            .with_overlay(&dir.join("overlay.yml"))
+            .validate();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].file, Some(dir.join("overlay.yml")));
+        assert!(issues[0].message.contains("currency_selection"));
+    }
+
+    #[test]
+    fn test_validate_reports_undefined_variables() {
+        let dir = config_dir(
+            "validate-env",
+            &[
+                ("paywall.yml", "include: section.yml\npaths: []\n"),
+                (
+                    "section.yml",
+                    r#"# price: ${RUSTWALL_TEST_UNSET_COMMENT}
+- id: premium
+  paywall_conditions: []
+  price_source: !Hard $1.25
+"#,
+                ),
+            ],
+        );
+        let main = dir.join("paywall.yml");
+
+        assert_eq!(ConfigLoader::new(&main).validate(), vec![]);
+
+        fs::write(
+            dir.join("section.yml"),
+            "- id: premium\n  paywall_conditions: [] # ${RUSTWALL_TEST_UNSET_TAIL}\n  price_source: !Hard $1.25\n",
+        )
+        .unwrap();
+        assert_eq!(
+            ConfigLoader::new(&main).validate(),
+            vec![ConfigIssue {
+                file: Some(dir.join("section.yml")),
+                line: 2,
+                column: 28,
+                message: "undefined environment variable 'RUSTWALL_TEST_UNSET_TAIL'".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rates_files_relative_to_config() {
+        let dir = config_dir(
+            "rates",
+            &[
+                (
+                    "paywall.yml",
+                    "include: sections/*.yml\ntax: { rates_file: vat.yml }\npaths: []\n",
+                ),
+                ("vat.yml", "DE: 19%\n"),
+                (
+                    "sections/research.yml",
+                    r#"- id: research
+  paywall_conditions: []
+  price_source: !Converted
+    source: !Hard $2.00
+    rates_file: rates/usd.yml
+"#,
+                ),
+                (
+                    "sections/rates/usd.yml",
+                    &format!(
+                        "base: USD\ntimestamp: {}\nrates:\n  EUR: 0.92\n",
+                        crate::utils::unix_now()
+                    ),
+                ),
+            ],
+        );
+        let loader = ConfigLoader::new(&dir.join("paywall.yml"));
+
+        assert_eq!(loader.validate(), vec![]);
+        let saved = loader.load().unwrap().to_yaml_string().unwrap();
+        assert!(saved.contains("rates_file: rates/usd.yml"), "{}", saved);
+        assert!(saved.contains("DE: '0.19'"), "{}", saved);
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = config_dir(
+            "cycle",
+            &[
+                ("paywall.yml", "include: [a.yml]\npaths: []\n"),
+                ("a.yml", "include: [paywall.yml]\n"),
+            ],
+        );
+
+        assert!(matches!(
+            ConfigLoader::new(&dir.join("paywall.yml")).load(),
+            Err(ConfigLoadError::Include { .. })
+        ));
+    }
+
+    #[test]
+    fn test_version_2_files() {
+        let dir = config_dir(
+            "version",
+            &[
+                (
+                    "paywall.yml",
+                    "version: 2\ninclude: [v1.yml]\npaths:\n  - id: premium\n    paywall_conditions: []\n    price_source: !Hard $1.25\n",
+                ),
+                (
+                    "v1.yml",
+                    "version: 1\npaths:\n  - id: research\n    paywall_conditions: []\n    price_source: !Hard $5.00\n",
+                ),
+                ("future.yml", "version: 3\npaths: []\n"),
+                ("settings.yml", "include: [tax.yml]\n"),
+                (
+                    "tax.yml",
+                    "version: 2\ntax: { default_country: DE }\npaths: []\n",
+                ),
+            ],
+        );
+
+        let config = ConfigLoader::new(&dir.join("paywall.yml")).load().unwrap();
+        assert_eq!(config.get_elements().len(), 2);
+
+        match ConfigLoader::new(&dir.join("future.yml")).load() {
+            Err(ConfigLoadError::Include { error, .. }) => assert!(error.contains("version 3")),
+            _ => panic!("Expected Include error"),
+        }
+        match ConfigLoader::new(&dir.join("settings.yml")).load() {
+            Err(ConfigLoadError::Include { error, .. }) => assert_eq!(
+                error,
+                "included files may only contain 'version', 'paths' and 'include'"
+            ),
+            _ => panic!("Expected Include error"),
+        }
+    }
+
+    #[test]
+    fn test_json_and_toml_files() {
+        let dir = config_dir(
+            "formats",
+            &[
+                (
+                    "paywall.json",
+                    r#"{
+  "include": ["sections/*.toml"],
+  "paths": [
+    {
+      "id": "premium",
+      "paywall_conditions": [{ "!HasRegexPath": "^/premium/.*$" }],
+      "price_source": { "!Hard": "${PREMIUM_PRICE:-$1.25}" }
+    }
+  ]
+}"#,
+                ),
+                (
+                    "sections/research.toml",
+                    r#"
+[[paths]]
+id = "research"
+paywall_conditions = [{ "!HasRegexPath" = "^/research/.*$" }]
+price_source = { "!Hard" = "$5.00" }
+"#,
+                ),
+                ("broken.json", "{ \"paths\": [ }"),
+            ],
+        );
+
+        let config = ConfigLoader::new(&dir.join("paywall.json"))
+            .with_env("PREMIUM_PRICE", "$2.00")
+            .load()
+            .unwrap();
+        assert_eq!(
+            price_at(&config, "/premium/a"),
+            "element 'premium': price $2.00"
+        );
+        assert_eq!(
+            price_at(&config, "/research/a"),
+            "element 'research': price $5.00"
+        );
+
+        match ConfigLoader::new(&dir.join("broken.json")).load() {
+            Err(ConfigLoadError::Parse {
+                error: ConfigFormatError::Json(_),
+                ..
+            }) => {}
+            _ => panic!("Expected Parse error"),
+        }
+    }
+}
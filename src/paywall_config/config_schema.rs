@@ -0,0 +1,738 @@
+use serde_json::{Map, Value, json};
+
+/// How a tagged variant is written in YAML, as used in the `yaml.customTags` setting of the
+/// VS Code YAML extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagKind {
+    Scalar,
+    Mapping,
+    Sequence,
+}
+
+impl TagKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TagKind::Scalar => "scalar",
+            TagKind::Mapping => "mapping",
+            TagKind::Sequence => "sequence",
+        }
+    }
+}
+
+/// Variant of an enum written with a YAML tag, e.g. `!Hard $1.25`
+struct TaggedVariant {
+    tag: &'static str,
+    description: &'static str,
+    kinds: &'static [TagKind],
+    schema: Value,
+}
+
+const SCALAR: &[TagKind] = &[TagKind::Scalar];
+const MAPPING: &[TagKind] = &[TagKind::Mapping];
+const SCALAR_OR_MAPPING: &[TagKind] = &[TagKind::Scalar, TagKind::Mapping];
+const SEQUENCE_OR_MAPPING: &[TagKind] = &[TagKind::Sequence, TagKind::Mapping];
+
+fn variant(
+    tag: &'static str,
+    description: &'static str,
+    kinds: &'static [TagKind],
+    schema: Value,
+) -> TaggedVariant {
+    TaggedVariant {
+        tag,
+        description,
+        kinds,
+        schema,
+    }
+}
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn reference(definition: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", definition) })
+}
+
+fn paywall_condition_variants() -> Vec<TaggedVariant> {
+    vec![
+        variant(
+            "HasRegexPath",
+            "The URL path matches a regular expression",
+            SCALAR,
+            json!({ "type": "string", "format": "regex" }),
+        ),
+        variant(
+            "MatchesCssSelector",
+            "An HTML document has a node matching a CSS selector",
+            SCALAR,
+            string("CSS selector, e.g. div.premium"),
+        ),
+        variant(
+            "HasFrontMatterKey",
+            "A Markdown document has a front matter key",
+            SCALAR,
+            string("Front matter key"),
+        ),
+        variant(
+            "MetaEquals",
+            "Document metadata equals a value",
+            MAPPING,
+            json!({
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string" },
+                    "value": { "type": ["string", "number", "boolean"] }
+                },
+                "required": ["key", "value"],
+                "additionalProperties": false
+            }),
+        ),
+        variant(
+            "JsonPathMatches",
+            "JSONPath predicate on a JSON document, e.g. $.article.premium == true",
+            SCALAR,
+            string("JSONPath predicate"),
+        ),
+        variant(
+            "HasContentType",
+            "MIME type of the document, with * as subtype wildcard",
+            SCALAR,
+            string("MIME type, e.g. audio/*"),
+        ),
+        variant(
+            "HasFileExtension",
+            "Case-insensitive extension of the URL path",
+            SCALAR,
+            string("File extension, e.g. pdf"),
+        ),
+        variant(
+            "MinSize",
+            "Binary documents of at least this many bytes",
+            SCALAR,
+            json!({ "type": "integer", "minimum": 0 }),
+        ),
+    ]
+}
+
+fn price_source_variants() -> Vec<TaggedVariant> {
+    vec![
+        variant(
+            "Hard",
+            "Fixed price, optionally per currency",
+            SCALAR_OR_MAPPING,
+            reference("CurrencyTable"),
+        ),
+        variant(
+            "FromHtmlAttribute",
+            "Price from an attribute of an HTML node, as selector:::attribute",
+            SCALAR,
+            json!({ "type": "string", "pattern": ":::" }),
+        ),
+        variant(
+            "Converted",
+            "Price of another source converted with exchange rates",
+            MAPPING,
+            json!({
+                "type": "object",
+                "properties": {
+                    "source": reference("PriceSource"),
+                    "rates_file": { "type": "string" },
+                    "rounding": reference("Rounding"),
+                    "max_age_hours": { "type": "integer", "minimum": 0 }
+                },
+                "required": ["source", "rates_file"],
+                "additionalProperties": false
+            }),
+        ),
+        variant(
+            "FromMeta",
+            "Price from document metadata",
+            MAPPING,
+            json!({
+                "type": "object",
+                "properties": { "key": { "type": "string" } },
+                "required": ["key"],
+                "additionalProperties": false
+            }),
+        ),
+        variant(
+            "FromJsonPath",
+            "Price from the first value at a JSONPath of a JSON document",
+            SCALAR,
+            string("JSONPath, e.g. $.offer.price"),
+        ),
+        variant(
+            "FromJsonLd",
+            "Price of the offers of the embedded JSON-LD object with this @type",
+            SCALAR,
+            string("JSON-LD @type, e.g. Product"),
+        ),
+        variant(
+            "FirstOf",
+            "First price any of several sources yields",
+            SEQUENCE_OR_MAPPING,
+            json!({
+                "anyOf": [
+                    { "type": "array", "items": reference("PriceSource") },
+                    {
+                        "type": "object",
+                        "properties": {
+                            "sources": { "type": "array", "items": reference("PriceSource") },
+                            "min": reference("CurrencyTable"),
+                            "max": reference("CurrencyTable")
+                        },
+                        "required": ["sources"],
+                        "additionalProperties": false
+                    }
+                ]
+            }),
+        ),
+        variant(
+            "Expression",
+            "Price computed by a type checked expression",
+            MAPPING,
+            json!({
+                "type": "object",
+                "properties": {
+                    "expression": { "type": "string" },
+                    "currency": reference("CurrencyCode"),
+                    "path_regex": { "type": "string", "format": "regex" },
+                    "variables": {
+                        "type": "object",
+                        "additionalProperties": reference("ExpressionVariable")
+                    }
+                },
+                "required": ["expression"],
+                "additionalProperties": false
+            }),
+        ),
+    ]
+}
+
+fn expression_variable_variants() -> Vec<TaggedVariant> {
+    let selector = || json!({ "type": "string", "pattern": ":::" });
+
+    vec![
+        variant(
+            "HtmlNumber",
+            "Number in an HTML attribute",
+            SCALAR,
+            selector(),
+        ),
+        variant("HtmlText", "Text of an HTML attribute", SCALAR, selector()),
+        variant("Meta", "Document metadata", SCALAR, string("Key")),
+        variant("Header", "Request header", SCALAR, string("Header name")),
+        variant("Cookie", "Request cookie", SCALAR, string("Cookie name")),
+        variant("Query", "Query parameter", SCALAR, string("Parameter name")),
+    ]
+}
+
+fn rounding_variants() -> Vec<TaggedVariant> {
+    vec![
+        variant(
+            "Ending",
+            "Round up to prices ending in these minor units, e.g. 99",
+            SCALAR,
+            json!({ "type": "integer", "minimum": 0, "maximum": 99 }),
+        ),
+        variant(
+            "Nearest",
+            "Round to the nearest multiple, e.g. 0.05",
+            SCALAR,
+            reference("Decimal"),
+        ),
+    ]
+}
+
+fn discount_variants() -> Vec<TaggedVariant> {
+    vec![
+        variant(
+            "Percent",
+            "Percentage off, e.g. 20% or 0.2",
+            SCALAR,
+            reference("Decimal"),
+        ),
+        variant("Fixed", "Amount off", SCALAR, reference("Money")),
+    ]
+}
+
+fn error_policy_variants() -> Vec<TaggedVariant> {
+    vec![variant(
+        "fixed_price",
+        "Charge a fallback price",
+        SCALAR_OR_MAPPING,
+        reference("CurrencyTable"),
+    )]
+}
+
+/// Built-in condition tags, which [custom conditions](super::CustomCondition) cannot use
+pub(crate) fn is_builtin_condition_tag(tag: &str) -> bool {
+    paywall_condition_variants().iter().any(|v| v.tag == tag)
+}
+
+/// Built-in price source tags, which [custom price sources](super::CustomPriceSource) cannot use
+pub(crate) fn is_builtin_price_source_tag(tag: &str) -> bool {
+    price_source_variants().iter().any(|v| v.tag == tag)
+}
+
+/// Schema of a tagged enum: each variant as its YAML value, which is what YAML editors
+/// validate once the tag is declared, or as a `{ "!Tag": value }` map of JSON and TOML
+fn tagged_enum(description: &str, variants: &[TaggedVariant], plain: &[Value]) -> Value {
+    let mut any_of: Vec<Value> = variants
+        .iter()
+        .map(|v| {
+            let key = format!("!{}", v.tag);
+            json!({
+                "title": key,
+                "description": v.description,
+                "anyOf": [
+                    {
+                        "type": "object",
+                        "properties": { key.clone(): v.schema },
+                        "required": [key],
+                        "additionalProperties": false
+                    },
+                    v.schema
+                ]
+            })
+        })
+        .collect();
+    any_of.extend_from_slice(plain);
+
+    json!({ "description": description, "anyOf": any_of })
+}
+
+fn definitions() -> Map<String, Value> {
+    let definitions = json!({
+        "PaywallCondition": tagged_enum(
+            "Condition of a paywall element",
+            &paywall_condition_variants(),
+            &[],
+        ),
+        "PriceSource": tagged_enum(
+            "Where the price of a paywall element comes from",
+            &price_source_variants(),
+            &[],
+        ),
+        "ExpressionVariable": tagged_enum(
+            "Variable of a price expression",
+            &expression_variable_variants(),
+            &[],
+        ),
+        "Rounding": tagged_enum(
+            "Rounding of converted prices",
+            &rounding_variants(),
+            &[json!({ "const": "Cent" })],
+        ),
+        "Discount": tagged_enum("Discount of a code or promotion", &discount_variants(), &[]),
+        "ErrorPolicy": tagged_enum(
+            "What to serve when the price cannot be determined",
+            &error_policy_variants(),
+            &[json!({ "enum": ["allow", "deny"] })],
+        ),
+        "Money": { "type": "string", "description": "Amount with currency, e.g. $1.25 or EUR 1.25" },
+        "CurrencyCode": { "type": "string", "pattern": "^[A-Z]{3}$" },
+        "CountryCode": { "type": "string", "pattern": "^[A-Za-z]{2}$" },
+        "Decimal": {
+            "type": ["number", "string"],
+            "description": "Decimal number like 0.19 or percentage like '19%'"
+        },
+        "Timestamp": {
+            "type": ["integer", "string"],
+            "description": "Unix seconds or a date like 2025-01-31T12:00:00Z"
+        },
+        "CurrencyTable": {
+            "anyOf": [
+                { "$ref": "#/$defs/Money" },
+                {
+                    "type": "object",
+                    "propertyNames": { "pattern": "^[A-Z]{3}$" },
+                    "additionalProperties": { "type": ["string", "number"] }
+                }
+            ]
+        },
+        "PaywallElement": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string", "pattern": "^[A-Za-z0-9._-]+$" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "teaser": {
+                    "type": "object",
+                    "properties": {
+                        "sections": { "type": "integer", "minimum": 0 },
+                        "max_level": { "type": "integer", "minimum": 1 }
+                    },
+                    "additionalProperties": false
+                },
+                "redact": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": { "type": "string" },
+                            "truncate": { "type": "integer", "minimum": 0 }
+                        },
+                        "required": ["path"],
+                        "additionalProperties": false
+                    }
+                },
+                "checkout_url": { "type": "string" },
+                "on_error": { "$ref": "#/$defs/ErrorPolicy" },
+                "paywall_conditions": {
+                    "type": "array",
+                    "items": { "$ref": "#/$defs/PaywallCondition" }
+                },
+                "price_source": { "$ref": "#/$defs/PriceSource" }
+            },
+            "required": ["paywall_conditions", "price_source"],
+            "additionalProperties": false
+        },
+        "Elements": {
+            "type": "array",
+            "items": { "$ref": "#/$defs/PaywallElement" }
+        }
+    });
+
+    match definitions {
+        Value::Object(definitions) => definitions,
+        _ => Map::new(),
+    }
+}
+
+/// JSON Schema of a paywall config, e.g. for editor validation and completion
+///
+/// JSON and TOML configs match it as written. For YAML the tags must be declared to the
+/// editor, see [yaml_custom_tags]. Unknown keys, e.g. misspelled ones, are rejected
+pub fn config_json_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "rustwall paywall config",
+        "type": "object",
+        "properties": {
+            "version": { "enum": [1, 2] },
+            "include": {
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } }
+                ]
+            },
+            "paths": { "$ref": "#/$defs/Elements" },
+            "currency_selection": {
+                "type": "object",
+                "properties": {
+                    "header": { "type": ["string", "null"], "description": "null to ignore headers" },
+                    "cookie": { "type": ["string", "null"], "description": "null to ignore cookies" },
+                    "accept_language": { "type": "boolean" },
+                    "fallback": { "anyOf": [{ "$ref": "#/$defs/CurrencyCode" }, { "type": "null" }] },
+                    "document_currency": {
+                        "anyOf": [{ "$ref": "#/$defs/CurrencyCode" }, { "type": "null" }]
+                    }
+                },
+                "additionalProperties": false
+            },
+            "discounts": {
+                "type": "object",
+                "properties": {
+                    "code_query_param": { "type": "string" },
+                    "code_cookie": { "type": "string" },
+                    "codes": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "code": { "type": "string" },
+                                "discount": { "$ref": "#/$defs/Discount" },
+                                "elements": { "type": "array", "items": { "type": "string" } },
+                                "max_uses": { "type": "integer", "minimum": 0 },
+                                "expires": { "$ref": "#/$defs/Timestamp" }
+                            },
+                            "required": ["code", "discount"],
+                            "additionalProperties": false
+                        }
+                    },
+                    "promotions": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "discount": { "$ref": "#/$defs/Discount" },
+                                "paths": { "type": "array", "items": { "type": "string" } },
+                                "elements": { "type": "array", "items": { "type": "string" } },
+                                "starts": { "$ref": "#/$defs/Timestamp" },
+                                "until": { "$ref": "#/$defs/Timestamp" }
+                            },
+                            "required": ["name", "discount"],
+                            "additionalProperties": false
+                        }
+                    }
+                },
+                "additionalProperties": false
+            },
+            "tax": {
+                "type": "object",
+                "properties": {
+                    "country_header": { "type": "string" },
+                    "country_cookie": { "type": "string" },
+                    "default_country": { "$ref": "#/$defs/CountryCode" },
+                    "overlay_display": { "enum": ["inclusive", "exclusive"] },
+                    "checkout_display": { "enum": ["inclusive", "exclusive"] },
+                    "rates": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/$defs/Decimal" }
+                    },
+                    "rates_file": { "type": "string" }
+                },
+                "additionalProperties": false
+            },
+            "explain_header": { "type": "boolean" }
+        },
+        "additionalProperties": false,
+        "$defs": definitions()
+    })
+}
+
+/// Tags of all variants in the format of the `yaml.customTags` setting of the VS Code YAML
+/// extension, e.g. `!Hard scalar`
+pub fn yaml_custom_tags() -> Vec<String> {
+    [
+        paywall_condition_variants(),
+        price_source_variants(),
+        expression_variable_variants(),
+        rounding_variants(),
+        discount_variants(),
+        error_policy_variants(),
+    ]
+    .iter()
+    .flatten()
+    .flat_map(|v| v.kinds.iter().map(|k| format!("!{} {}", v.tag, k.as_str())))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paywall_config::{ConfigFormat, PaywallConfig};
+    use std::fs;
+    use std::path::Path;
+
+    /// Raw string literals of the sources and `yaml` and `json` code blocks of the docs
+    fn fixtures() -> Vec<(String, String)> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut files = vec![root.join("README.md")];
+        let mut dirs = vec![root.join("src")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|e| e == "rs") {
+                    files.push(path);
+                }
+            }
+        }
+
+        let mut fixtures = Vec::new();
+        for file in files {
+            let name = file.strip_prefix(root).unwrap().display().to_string();
+            let text = fs::read_to_string(&file).unwrap();
+
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("r#\"") {
+                rest = &rest[start + 3..];
+                let Some(end) = rest.find("\"#") else { break };
+                fixtures.push((name.clone(), rest[..end].to_string()));
+                rest = &rest[end + 2..];
+            }
+
+            let mut block: Option<String> = None;
+            for line in text.lines() {
+                let line = line.trim_start();
+                let line = match line.strip_prefix("///").or(line.strip_prefix("//!")) {
+                    Some(doc) => doc.strip_prefix(' ').unwrap_or(doc),
+                    None => line,
+                };
+                match &mut block {
+                    Some(code) if line.starts_with("```") => {
+                        fixtures.push((name.clone(), std::mem::take(code)));
+                        block = None;
+                    }
+                    Some(code) => {
+                        code.push_str(line);
+                        code.push('\n');
+                    }
+                    None if line == "```yaml" || line == "```json" => block = Some(String::new()),
+                    None => {}
+                }
+            }
+        }
+        fixtures
+    }
+
+    /// A config as JSON and TOML configs are written; `None` if it uses custom tags, which the
+    /// schema cannot know
+    fn to_json(value: &serde_yml::Value, builtin_tags: &[String]) -> Option<Value> {
+        Some(match value {
+            serde_yml::Value::Null => Value::Null,
+            serde_yml::Value::Bool(b) => Value::Bool(*b),
+            serde_yml::Value::Number(n) => serde_json::to_value(n).ok()?,
+            serde_yml::Value::String(s) => Value::String(s.clone()),
+            serde_yml::Value::Sequence(values) => Value::Array(
+                values
+                    .iter()
+                    .map(|v| to_json(v, builtin_tags))
+                    .collect::<Option<_>>()?,
+            ),
+            serde_yml::Value::Mapping(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| {
+                        let key = match k {
+                            serde_yml::Value::String(key) => key.clone(),
+                            other => serde_yml::to_string(other).ok()?.trim().to_string(),
+                        };
+                        Some((key, to_json(v, builtin_tags)?))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            serde_yml::Value::Tagged(tagged) => {
+                let tag = tagged.tag.to_string();
+                if !builtin_tags.contains(&tag) {
+                    return None;
+                }
+                json!({ tag: to_json(&tagged.value, builtin_tags)? })
+            }
+        })
+    }
+
+    #[test]
+    fn test_fixtures_match_schema() {
+        let validator = jsonschema::validator_for(&config_json_schema()).unwrap();
+        let builtin_tags: Vec<String> = yaml_custom_tags()
+            .iter()
+            .filter_map(|t| t.split(' ').next().map(str::to_string))
+            .collect();
+
+        let mut checked = 0;
+        for (file, fixture) in fixtures() {
+            let Some(format) = [ConfigFormat::Yaml, ConfigFormat::Json, ConfigFormat::Toml]
+                .into_iter()
+                .find(|f| PaywallConfig::from_str_with_format(&fixture, *f).is_ok())
+            else {
+                continue;
+            };
+            let mut value = format.parse(&fixture).unwrap();
+            value.apply_merge().unwrap();
+            let Some(config) = to_json(&value, &builtin_tags) else {
+                continue;
+            };
+
+            let errors: Vec<String> = validator
+                .iter_errors(&config)
+                .map(|e| format!("{}: {}", e.instance_path(), e))
+                .collect();
+            assert!(
+                errors.is_empty(),
+                "fixture in {} does not match the schema: {:?}\n{}",
+                file,
+                errors,
+                fixture
+            );
+            checked += 1;
+        }
+
+        assert!(checked >= 20, "only {} fixtures checked", checked);
+    }
+
+    #[test]
+    fn test_schema_rejects_unknown_keys() {
+        let validator = jsonschema::validator_for(&config_json_schema()).unwrap();
+        let element = json!({
+            "paywall_conditions": [],
+            "price_source": { "!Hard": "$1.25" },
+        });
+        assert!(validator.is_valid(&json!({ "paths": [element.clone()] })));
+
+        let mut misspelled = element.clone();
+        misspelled["on_eror"] = json!("allow");
+        assert!(!validator.is_valid(&json!({ "paths": [misspelled] })));
+        assert!(!validator.is_valid(&json!({ "path": [element.clone()] })));
+        assert!(!validator.is_valid(&json!({
+            "paths": [element],
+            "tax": { "rate": 0.19 }
+        })));
+    }
+
+    #[test]
+    fn test_schema_covers_all_tags() {
+        let schema = config_json_schema();
+        let conditions = schema["$defs"]["PaywallCondition"]["anyOf"]
+            .as_array()
+            .unwrap();
+        let titles: Vec<&str> = conditions
+            .iter()
+            .filter_map(|c| c["title"].as_str())
+            .collect();
+
+        assert_eq!(
+            titles,
+            vec![
+                "!HasRegexPath",
+                "!MatchesCssSelector",
+                "!HasFrontMatterKey",
+                "!MetaEquals",
+                "!JsonPathMatches",
+                "!HasContentType",
+                "!HasFileExtension",
+                "!MinSize"
+            ]
+        );
+        assert_eq!(
+            schema["$defs"]["PriceSource"]["anyOf"]
+                .as_array()
+                .unwrap()
+                .len(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_schema_references_exist() {
+        fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(r)) = map.get("$ref") {
+                        refs.push(r.trim_start_matches("#/$defs/").to_string());
+                    }
+                    map.values().for_each(|v| collect_refs(v, refs));
+                }
+                Value::Array(values) => values.iter().for_each(|v| collect_refs(v, refs)),
+                _ => {}
+            }
+        }
+
+        let schema = config_json_schema();
+        let mut refs = Vec::new();
+        collect_refs(&schema, &mut refs);
+
+        assert!(!refs.is_empty());
+        for r in refs {
+            assert!(
+                schema["$defs"].get(&r).is_some(),
+                "missing definition {}",
+                r
+            );
+        }
+    }
+
+    #[test]
+    fn test_yaml_custom_tags() {
+        let tags = yaml_custom_tags();
+
+        assert!(tags.contains(&"!HasRegexPath scalar".to_string()));
+        assert!(tags.contains(&"!Hard scalar".to_string()));
+        assert!(tags.contains(&"!Hard mapping".to_string()));
+        assert!(tags.contains(&"!FirstOf sequence".to_string()));
+        assert!(tags.contains(&"!fixed_price scalar".to_string()));
+    }
+}
@@ -0,0 +1,511 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{DocumentAndPath, PaywallConfigV1, PaywallPriceOption, RequestContext};
+use crate::money::Money;
+
+/// Example page with the outcome a [config](PaywallConfigV1) must produce for it
+/// # Examples
+/// ```yaml
+/// - name: premium article
+///   path: /premium/a
+///   html_file: fixtures/a.html
+///   expect: { price: "$1.25", element: premium }
+/// - path: /free/b
+///   markdown_file: fixtures/b.md
+///   expect: { paywalled: false }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ConfigTestCase {
+    #[serde(default)]
+    name: Option<String>,
+    path: String,
+    #[serde(flatten)]
+    document: TestDocument,
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    expect: TestExpectation,
+}
+
+/// Fixture file of a [test case](ConfigTestCase), relative to the test case file
+#[derive(Debug, Deserialize)]
+enum TestDocument {
+    #[serde(rename = "html_file")]
+    Html(PathBuf),
+    #[serde(rename = "markdown_file")]
+    Markdown(PathBuf),
+    #[serde(rename = "json_file")]
+    Json(PathBuf),
+}
+
+/// Expected outcome; fields left out are not checked
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestExpectation {
+    #[serde(default)]
+    price: Option<Money>,
+    #[serde(default)]
+    element: Option<String>,
+    #[serde(default)]
+    paywalled: Option<bool>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigTestError {
+    #[error("Cannot parse test cases: {0}")]
+    Parse(#[from] serde_yml::Error),
+    #[error("Cannot read fixture '{path}': {error}")]
+    Fixture { path: String, error: String },
+}
+
+/// Expected and actual value of a checked field of a failing [test case](ConfigTestCase)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestMismatch {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug)]
+pub struct TestCaseResult {
+    pub name: String,
+    pub mismatches: Vec<TestMismatch>,
+    /// Set if the case could not be evaluated, e.g. for a missing fixture
+    pub error: Option<String>,
+}
+
+impl TestCaseResult {
+    pub fn is_success(&self) -> bool {
+        self.mismatches.is_empty() && self.error.is_none()
+    }
+}
+
+/// Results of [running](ConfigTestSuite::run) all test cases; displays as a diff-style report
+#[derive(Debug)]
+pub struct ConfigTestReport {
+    pub results: Vec<TestCaseResult>,
+}
+
+/// Test cases loaded from a YAML list, with fixture paths resolved against `base_dir`
+pub struct ConfigTestSuite {
+    cases: Vec<ConfigTestCase>,
+    base_dir: PathBuf,
+}
+
+impl ConfigTestCase {
+    pub fn get_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.path.clone())
+    }
+
+    /// The fixture with a request context built like the paywall does, with the
+    /// [currency selection](super::CurrencySelection) of `config` applied
+    fn load_document(
+        &self,
+        config: &PaywallConfigV1,
+        base_dir: &Path,
+    ) -> Result<DocumentAndPath, ConfigTestError> {
+        let file = match &self.document {
+            TestDocument::Html(f) | TestDocument::Markdown(f) | TestDocument::Json(f) => {
+                base_dir.join(f)
+            }
+        };
+        let fixture_error = |error: String| ConfigTestError::Fixture {
+            path: file.display().to_string(),
+            error,
+        };
+        let content = fs::read_to_string(&file).map_err(|e| fixture_error(e.to_string()))?;
+
+        let doc_and_path = match &self.document {
+            TestDocument::Html(_) => {
+                DocumentAndPath::new_from_html_and_path_str(&content, &self.path)
+            }
+            TestDocument::Markdown(_) => {
+                DocumentAndPath::new_from_markdown_and_path_str(&content, &self.path)
+            }
+            TestDocument::Json(_) => {
+                DocumentAndPath::new_from_json_and_path_str(&content, &self.path)
+            }
+        }
+        .map_err(|e| fixture_error(e.to_string()))?;
+
+        let mut ctx = RequestContext::new();
+        if let Some(query) = &self.query {
+            ctx = ctx.with_query_string(query);
+        }
+        for (name, value) in &self.headers {
+            ctx = ctx.with_header(name, value);
+        }
+
+        Ok(doc_and_path.with_request_context(config.get_currency_selection().apply(ctx)))
+    }
+
+    fn run(&self, config: &PaywallConfigV1, base_dir: &Path) -> TestCaseResult {
+        let mut result = TestCaseResult {
+            name: self.get_name(),
+            mismatches: Vec::new(),
+            error: None,
+        };
+
+        let doc_and_path = match self.load_document(config, base_dir) {
+            Ok(doc_and_path) => doc_and_path,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                return result;
+            }
+        };
+        let decision = config.get_price(&doc_and_path);
+
+        let actual_price = match &decision.price_option {
+            PaywallPriceOption::Price(p) => p.to_string(),
+            PaywallPriceOption::ConditionsNotMet => "<free>".to_string(),
+            PaywallPriceOption::PriceParsingError(e) => format!("<error: {}>", e.get_message()),
+        };
+        let is_paywalled = !matches!(decision.price_option, PaywallPriceOption::ConditionsNotMet);
+        let actual_element = decision
+            .element_id
+            .clone()
+            .unwrap_or_else(|| "<none>".to_string());
+
+        if let Some(price) = &self.expect.price
+            && decision.price_option.get_price() != Some(price)
+        {
+            result.mismatches.push(TestMismatch {
+                field: "price",
+                expected: price.to_string(),
+                actual: actual_price,
+            });
+        }
+        if let Some(element) = &self.expect.element
+            && decision.element_id.as_ref() != Some(element)
+        {
+            result.mismatches.push(TestMismatch {
+                field: "element",
+                expected: element.clone(),
+                actual: actual_element,
+            });
+        }
+        if let Some(paywalled) = self.expect.paywalled
+            && paywalled != is_paywalled
+        {
+            result.mismatches.push(TestMismatch {
+                field: "paywalled",
+                expected: paywalled.to_string(),
+                actual: is_paywalled.to_string(),
+            });
+        }
+
+        result
+    }
+}
+
+impl ConfigTestSuite {
+    pub fn from_yaml_str(
+        cases_yml: &str,
+        base_dir: &Path,
+    ) -> Result<ConfigTestSuite, ConfigTestError> {
+        Ok(ConfigTestSuite {
+            cases: serde_yml::from_str(cases_yml)?,
+            base_dir: base_dir.to_path_buf(),
+        })
+    }
+
+    /// Load test cases from a file, resolving fixtures relative to its directory
+    pub fn from_file(path: &Path) -> Result<ConfigTestSuite, ConfigTestError> {
+        let cases_yml = fs::read_to_string(path).map_err(|e| ConfigTestError::Fixture {
+            path: path.display().to_string(),
+            error: e.to_string(),
+        })?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        ConfigTestSuite::from_yaml_str(&cases_yml, base_dir)
+    }
+
+    pub fn get_cases(&self) -> &[ConfigTestCase] {
+        &self.cases
+    }
+
+    pub fn run(&self, config: &PaywallConfigV1) -> ConfigTestReport {
+        ConfigTestReport {
+            results: self
+                .cases
+                .iter()
+                .map(|c| c.run(config, &self.base_dir))
+                .collect(),
+        }
+    }
+}
+
+impl ConfigTestReport {
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(TestCaseResult::is_success)
+    }
+
+    pub fn get_failure_count(&self) -> usize {
+        self.results.iter().filter(|r| !r.is_success()).count()
+    }
+
+    /// JUnit XML report with one `testcase` per test case, for CI systems
+    pub fn to_junit_xml(&self, suite_name: &str) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+            escape_xml(suite_name),
+            self.results.len(),
+            self.results
+                .iter()
+                .filter(|r| r.error.is_none() && !r.mismatches.is_empty())
+                .count(),
+            self.results.iter().filter(|r| r.error.is_some()).count(),
+        ));
+
+        for result in &self.results {
+            let name = escape_xml(&result.name);
+
+            if let Some(error) = &result.error {
+                xml.push_str(&format!(
+                    "  <testcase name=\"{}\">\n    <error message=\"{}\"/>\n  </testcase>\n",
+                    name,
+                    escape_xml(error)
+                ));
+            } else if result.mismatches.is_empty() {
+                xml.push_str(&format!("  <testcase name=\"{}\"/>\n", name));
+            } else {
+                let details: Vec<String> = result
+                    .mismatches
+                    .iter()
+                    .map(|m| format!("{}: expected {}, got {}", m.field, m.expected, m.actual))
+                    .collect();
+                xml.push_str(&format!(
+                    "  <testcase name=\"{}\">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                    name,
+                    escape_xml(&details[0]),
+                    escape_xml(&details.join("\n"))
+                ));
+            }
+        }
+
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+impl fmt::Display for ConfigTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in &self.results {
+            if result.is_success() {
+                writeln!(f, "PASS {}", result.name)?;
+                continue;
+            }
+
+            writeln!(f, "FAIL {}", result.name)?;
+            if let Some(error) = &result.error {
+                writeln!(f, "  error: {}", error)?;
+            }
+            for mismatch in &result.mismatches {
+                writeln!(f, "  {}:", mismatch.field)?;
+                writeln!(f, "  - {}", mismatch.expected)?;
+                writeln!(f, "  + {}", mismatch.actual)?;
+            }
+        }
+
+        write!(
+            f,
+            "{} cases, {} passed, {} failed",
+            self.results.len(),
+            self.results.len() - self.get_failure_count(),
+            self.get_failure_count()
+        )
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rustwall-config-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a.html"),
+            "<html><body><div id=\"price\" data-price=\"$2.00\"></div></body></html>",
+        )
+        .unwrap();
+        fs::write(dir.join("b.md"), "# Free\n\nText").unwrap();
+        dir
+    }
+
+    fn config() -> PaywallConfigV1 {
+        let config_yml = r#"
+paths:
+  - id: premium
+    paywall_conditions:
+      - !HasRegexPath "^/premium/.*$"
+    price_source: !FromHtmlAttribute div#price:::data-price
+"#;
+        PaywallConfigV1::from_yaml_str(config_yml).unwrap()
+    }
+
+    #[test]
+    fn test_passing_cases() {
+        let dir = fixtures_dir("pass");
+        let cases_yml = r#"
+- name: premium article
+  path: /premium/a
+  html_file: a.html
+  expect: { price: "$2.00", element: premium, paywalled: true }
+- path: /free/b
+  markdown_file: b.md
+  expect: { paywalled: false }
+"#;
+
+        let suite = ConfigTestSuite::from_yaml_str(cases_yml, &dir).unwrap();
+        let report = suite.run(&config());
+
+        assert!(report.is_success(), "{}", report);
+        assert_eq!(report.results[1].name, "/free/b");
+        assert!(report.to_string().ends_with("2 cases, 2 passed, 0 failed"));
+    }
+
+    #[test]
+    fn test_failing_case_report() {
+        let dir = fixtures_dir("fail");
+        let cases_yml = r#"
+- name: premium article
+  path: /premium/a
+  html_file: a.html
+  expect: { price: "$1.25", element: basic }
+- name: missing fixture
+  path: /premium/c
+  html_file: c.html
+  expect: { paywalled: true }
+"#;
+
+        let suite = ConfigTestSuite::from_yaml_str(cases_yml, &dir).unwrap();
+        let report = suite.run(&config());
+
+        assert_eq!(report.get_failure_count(), 2);
+        assert_eq!(
+            report.results[0].mismatches,
+            vec![
+                TestMismatch {
+                    field: "price",
+                    expected: "$1.25".to_string(),
+                    actual: "$2.00".to_string()
+                },
+                TestMismatch {
+                    field: "element",
+                    expected: "basic".to_string(),
+                    actual: "premium".to_string()
+                }
+            ]
+        );
+        assert!(
+            report
+                .to_string()
+                .contains("FAIL premium article\n  price:\n  - $1.25\n  + $2.00\n")
+        );
+        assert!(report.results[1].error.is_some());
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let dir = fixtures_dir("junit");
+        let cases_yml = r#"
+- name: "a <premium> article"
+  path: /premium/a
+  html_file: a.html
+  expect: { price: "$1.25" }
+- path: /free/b
+  markdown_file: b.md
+  expect: { paywalled: false }
+"#;
+
+        let suite = ConfigTestSuite::from_yaml_str(cases_yml, &dir).unwrap();
+        let xml = suite.run(&config()).to_junit_xml("paywall.yml");
+
+        assert!(
+            xml.contains(
+                "<testsuite name=\"paywall.yml\" tests=\"2\" failures=\"1\" errors=\"0\">"
+            )
+        );
+        assert!(xml.contains("<testcase name=\"a &lt;premium&gt; article\">"));
+        assert!(xml.contains("<failure message=\"price: expected $1.25, got $2.00\">"));
+        assert!(xml.contains("<testcase name=\"/free/b\"/>"));
+    }
+
+    #[test]
+    fn test_currency_selection_of_config() {
+        let dir = fixtures_dir("currency");
+        fs::write(
+            dir.join("bare.html"),
+            "<html><body><div id=\"price\" data-price=\"2.00\"></div></body></html>",
+        )
+        .unwrap();
+        let config = PaywallConfigV1::from_yaml_str(
+            r#"
+currency_selection:
+  fallback: EUR
+  document_currency: USD
+paths:
+  - id: premium
+    paywall_conditions:
+      - !HasRegexPath "^/premium/.*$"
+    price_source: !FromHtmlAttribute div#price:::data-price
+  - id: table
+    paywall_conditions: []
+    price_source: !Hard { USD: "$1.00", EUR: "€0.95" }
+"#,
+        )
+        .unwrap();
+        let cases_yml = r#"
+- path: /premium/a
+  html_file: bare.html
+  expect: { price: "$2.00", element: premium }
+- path: /table/a
+  html_file: bare.html
+  expect: { price: "€0.95", element: table }
+- path: /table/b
+  html_file: bare.html
+  headers: { X-Rustwall-Currency: USD }
+  expect: { price: "$1.00" }
+"#;
+
+        let suite = ConfigTestSuite::from_yaml_str(cases_yml, &dir).unwrap();
+        let report = suite.run(&config);
+
+        assert!(report.is_success(), "{}", report);
+    }
+
+    #[test]
+    fn test_invalid_expectation() {
+        let cases_yml = r#"
+- path: /premium/a
+  html_file: a.html
+  expect: { cost: "$1.25" }
+"#;
+
+        assert!(matches!(
+            ConfigTestSuite::from_yaml_str(cases_yml, Path::new(".")),
+            Err(ConfigTestError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,341 @@
+use serde_yml::{Mapping, Value};
+
+use super::{ConfigFormat, PaywallConfigError, PaywallConfigV1};
+
+/// Schema version written by [migrate_yaml]
+pub const LATEST_CONFIG_VERSION: u64 = 2;
+
+/// Config of any supported schema version, selected by its `version:` field; configs without
+/// one are version 1
+pub enum PaywallConfig {
+    V1(PaywallConfigV1),
+    V2(PaywallConfigV2),
+}
+
+/// Version 2 of the config schema: the settings and `paths` of version 1 under an explicit
+/// `version: 2`, so that configs state the schema they were written for
+/// # Examples
+/// ```yaml
+/// version: 2
+/// paths:
+///   - id: premium
+///     paywall_conditions: [ !HasRegexPath "^/premium/.*$" ]
+///     price_source: !Hard $1.25
+/// ```
+pub struct PaywallConfigV2 {
+    config: PaywallConfigV1,
+}
+
+impl PaywallConfigV2 {
+    pub fn from_yaml_str(config_yml: &str) -> Result<PaywallConfigV2, PaywallConfigError> {
+        match PaywallConfig::from_yaml_str(config_yml)? {
+            PaywallConfig::V2(config) => Ok(config),
+            PaywallConfig::V1(_) => Err(PaywallConfigError::UnexpectedVersion {
+                expected: 2,
+                found: 1,
+            }),
+        }
+    }
+
+    pub fn get_config(&self) -> &PaywallConfigV1 {
+        &self.config
+    }
+
+    pub fn into_config(self) -> PaywallConfigV1 {
+        self.config
+    }
+}
+
+impl PaywallConfig {
+    /// Parse a YAML config of any supported version
+    pub fn from_yaml_str(config_yml: &str) -> Result<PaywallConfig, PaywallConfigError> {
+        let value: Value = serde_yml::from_str(config_yml)?;
+        let version = get_version(&value)?;
+
+        // parsed from the text again to keep error locations
+        let config = PaywallConfigV1::from_yaml_str(config_yml)?;
+        Ok(PaywallConfig::with_version(version, config))
+    }
+
+    /// Parse a config of any supported version and [format](ConfigFormat)
+    pub fn from_str_with_format(
+        config: &str,
+        format: ConfigFormat,
+    ) -> Result<PaywallConfig, PaywallConfigError> {
+        match format {
+            ConfigFormat::Yaml => PaywallConfig::from_yaml_str(config),
+            _ => PaywallConfig::from_value(format.parse(config)?),
+        }
+    }
+
+    pub fn from_value(value: Value) -> Result<PaywallConfig, PaywallConfigError> {
+        let version = get_version(&value)?;
+        let config: PaywallConfigV1 = serde_yml::from_value(value)?;
+        config.validate_ids()?;
+        Ok(PaywallConfig::with_version(version, config))
+    }
+
+    fn with_version(version: u64, config: PaywallConfigV1) -> PaywallConfig {
+        match version {
+            1 => PaywallConfig::V1(config),
+            _ => PaywallConfig::V2(PaywallConfigV2 { config }),
+        }
+    }
+
+    pub fn get_version(&self) -> u64 {
+        match self {
+            PaywallConfig::V1(_) => 1,
+            PaywallConfig::V2(_) => 2,
+        }
+    }
+
+    /// The config in the latest schema version
+    pub fn into_latest(self) -> PaywallConfigV2 {
+        match self {
+            PaywallConfig::V1(config) => PaywallConfigV2 { config },
+            PaywallConfig::V2(config) => config,
+        }
+    }
+
+    /// The config to evaluate requests with
+    pub fn into_config(self) -> PaywallConfigV1 {
+        self.into_latest().into_config()
+    }
+}
+
+/// Value of the `version:` field, 1 if missing
+pub fn get_version(value: &Value) -> Result<u64, PaywallConfigError> {
+    match value.get("version") {
+        None | Some(Value::Null) => Ok(1),
+        Some(Value::Number(n)) => match n.as_u64() {
+            Some(version) if (1..=LATEST_CONFIG_VERSION).contains(&version) => Ok(version),
+            _ => Err(PaywallConfigError::UnsupportedVersion(n.to_string())),
+        },
+        Some(other) => Err(PaywallConfigError::UnsupportedVersion(
+            serde_yml::to_string(other)
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        )),
+    }
+}
+
+/// A config rewritten to the [latest version](LATEST_CONFIG_VERSION)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub from_version: u64,
+    pub config_yml: String,
+    /// False if the config had to be re-serialized, which drops comments and formatting
+    pub comments_preserved: bool,
+}
+
+/// Rewrite a config to the [latest version](LATEST_CONFIG_VERSION)
+///
+/// Block style configs are edited line by line so that comments are kept; other configs are
+/// re-serialized. The result is parsed again to make sure it is valid
+pub fn migrate_yaml(config_yml: &str) -> Result<Migration, PaywallConfigError> {
+    let value: Value = serde_yml::from_str(config_yml)?;
+    let from_version = get_version(&value)?;
+    PaywallConfig::from_yaml_str(config_yml)?;
+
+    if from_version == LATEST_CONFIG_VERSION {
+        return Ok(Migration {
+            from_version,
+            config_yml: config_yml.to_string(),
+            comments_preserved: true,
+        });
+    }
+
+    let migration = match migrate_lines(config_yml) {
+        Some(migrated) => Migration {
+            from_version,
+            config_yml: migrated,
+            comments_preserved: true,
+        },
+        None => Migration {
+            from_version,
+            config_yml: migrate_value(value)?,
+            comments_preserved: false,
+        },
+    };
+
+    PaywallConfigV2::from_yaml_str(&migration.config_yml)?;
+    Ok(migration)
+}
+
+/// V1 to V2 on the text: set `version: 2`, keeping a comment after an existing version; `None`
+/// if the config is not a block style map
+fn migrate_lines(config_yml: &str) -> Option<String> {
+    let lines: Vec<&str> = config_yml.lines().collect();
+    let is_content = |line: &&str| {
+        let trimmed = line.trim();
+        !trimmed.is_empty() && !trimmed.starts_with('#') && trimmed != "---"
+    };
+    let first = lines.iter().position(is_content)?;
+    if lines[first].trim_start().starts_with(['{', '[', '-', '?']) {
+        return None;
+    }
+    let indent = lines[first].len() - lines[first].trim_start().len();
+    let is_top_level_key = |line: &str, key: &str| {
+        line.len() - line.trim_start().len() == indent
+            && line.trim_start().strip_prefix(key).is_some_and(|rest| {
+                rest.starts_with(':')
+                    && rest[1..]
+                        .chars()
+                        .next()
+                        .is_none_or(|c| c == ' ' || c == '\t')
+            })
+    };
+
+    let version_line = lines.iter().position(|l| is_top_level_key(l, "version"));
+    let prefix = " ".repeat(indent);
+
+    let mut migrated: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    match version_line {
+        Some(i) => {
+            let comment = lines[i].find(" #").map_or("", |c| &lines[i][c..]);
+            migrated[i] = format!("{}version: {}{}", prefix, LATEST_CONFIG_VERSION, comment);
+        }
+        None => migrated.insert(
+            first,
+            format!("{}version: {}", prefix, LATEST_CONFIG_VERSION),
+        ),
+    }
+
+    let mut result = migrated.join("\n");
+    if config_yml.ends_with('\n') {
+        result.push('\n');
+    }
+    Some(result)
+}
+
+fn migrate_value(value: Value) -> Result<String, PaywallConfigError> {
+    let Value::Mapping(map) = value else {
+        return Err(PaywallConfigError::Layout(
+            "config must be a map".to_string(),
+        ));
+    };
+
+    let mut migrated = Mapping::new();
+    migrated.insert(Value::from("version"), Value::from(LATEST_CONFIG_VERSION));
+    for (key, value) in map {
+        if key.as_str() != Some("version") {
+            migrated.insert(key, value);
+        }
+    }
+
+    Ok(serde_yml::to_string(&Value::Mapping(migrated))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paywall_config::DocumentAndPath;
+
+    const V1_CONFIG: &str = r#"
+# Paywall of the magazine
+currency_selection:
+  fallback: USD
+paths: # checked in order
+  # research is the most expensive
+  - id: research
+    paywall_conditions:
+      - !HasRegexPath "^/research/.*$"
+    price_source: !Hard $5.00
+  - id: premium
+    paywall_conditions: [ !HasRegexPath "^/premium/.*$" ]
+    price_source: !Hard { USD: "$1.25", EUR: "€1.10" }
+"#;
+
+    fn price_at(config: &PaywallConfigV1, path: &str) -> String {
+        let doc_and_path =
+            DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", path)
+                .unwrap();
+        config.get_price(&doc_and_path).to_string()
+    }
+
+    #[test]
+    fn test_dispatch_on_version() {
+        let v1 = PaywallConfig::from_yaml_str(V1_CONFIG).unwrap();
+        assert_eq!(v1.get_version(), 1);
+
+        let v2 = PaywallConfig::from_yaml_str(
+            "version: 2\npaths:\n  - paywall_conditions: []\n    price_source: !Hard $1.00\n",
+        )
+        .unwrap();
+        assert_eq!(v2.get_version(), 2);
+        assert_eq!(v2.into_config().get_elements().len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        assert!(matches!(
+            PaywallConfig::from_yaml_str("version: 3\npaths: []\n"),
+            Err(PaywallConfigError::UnsupportedVersion(v)) if v == "3"
+        ));
+        assert!(matches!(
+            PaywallConfig::from_yaml_str("version: two\npaths: []\n"),
+            Err(PaywallConfigError::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            PaywallConfigV2::from_yaml_str(V1_CONFIG),
+            Err(PaywallConfigError::UnexpectedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_migrate_v1_preserves_comments() {
+        let migration = migrate_yaml(V1_CONFIG).unwrap();
+
+        assert_eq!(migration.from_version, 1);
+        assert!(migration.comments_preserved);
+        assert!(
+            migration
+                .config_yml
+                .starts_with("\n# Paywall of the magazine\nversion: 2\ncurrency_selection:\n")
+        );
+        assert_eq!(
+            migration.config_yml,
+            V1_CONFIG.replacen("currency_selection:", "version: 2\ncurrency_selection:", 1)
+        );
+
+        let v1 = PaywallConfig::from_yaml_str(V1_CONFIG)
+            .unwrap()
+            .into_config();
+        let v2 = PaywallConfigV2::from_yaml_str(&migration.config_yml)
+            .unwrap()
+            .into_config();
+        for path in ["/research/a", "/premium/a", "/free/a"] {
+            assert_eq!(price_at(&v1, path), price_at(&v2, path));
+        }
+    }
+
+    #[test]
+    fn test_migrate_replaces_version_and_keeps_latest() {
+        let config_yml = "version: 1 # schema\npaths: []\n";
+
+        let migration = migrate_yaml(config_yml).unwrap();
+        assert_eq!(migration.config_yml, "version: 2 # schema\npaths: []\n");
+
+        let again = migrate_yaml(&migration.config_yml).unwrap();
+        assert_eq!(again.from_version, 2);
+        assert_eq!(again.config_yml, migration.config_yml);
+    }
+
+    #[test]
+    fn test_migrate_flow_style_is_reserialized() {
+        let config_yml = "{ paths: [ { paywall_conditions: [], price_source: !Hard $1.00 } ] }";
+
+        let migration = migrate_yaml(config_yml).unwrap();
+
+        assert!(!migration.comments_preserved);
+        assert_eq!(
+            PaywallConfigV2::from_yaml_str(&migration.config_yml)
+                .unwrap()
+                .get_config()
+                .get_elements()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use super::PriceSource;
+use super::exchange_rates::{ExchangeRateError, ExchangeRateTable};
+use crate::money::{CurrencyCode, Decimal, Money};
+use crate::utils::{resolve_config_path, unix_now};
+use serde::ser::{self, SerializeMap};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Rounding applied to converted prices, in minor units of the target currency
+/// # Examples
+/// ```yaml
+/// rounding: !Ending 99    # 1.23 -> 0.99, 1.60 -> 1.99
+/// rounding: !Nearest 0.05 # 1.23 -> 1.25
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub enum Rounding {
+    #[default]
+    Cent,
+    Ending(u32),
+    Nearest(Decimal),
+}
+
+impl Rounding {
+    /// Round an exact amount of minor units, halves away from zero
+    pub fn apply(&self, minor_units: &Decimal, minor_unit_digits: u32) -> i64 {
+        let rounded = match self {
+            Rounding::Cent => minor_units.round(),
+            Rounding::Ending(ending) => {
+                let ending = i128::from(*ending % 100);
+                let below = minor_units.floor().div_euclid(100) * 100 + ending;
+                let distance = |candidate: &i128| {
+                    Decimal::new(*candidate, 0)
+                        .checked_sub(minor_units)
+                        .map(|d| d.abs())
+                        .unwrap_or(Decimal::ZERO)
+                };
+
+                [below - 100, below, below + 100]
+                    .into_iter()
+                    .filter(|c| *c >= ending)
+                    .min_by(|a, b| distance(a).cmp(&distance(b)).then(b.cmp(a)))
+                    .unwrap_or(ending)
+            }
+            Rounding::Nearest(step) => {
+                let step_minor = step
+                    .shift(minor_unit_digits as i32)
+                    .map(|s| s.round())
+                    .unwrap_or(1)
+                    .max(1);
+                let steps = minor_units
+                    .checked_div(&Decimal::new(step_minor, 0))
+                    .map(|s| s.round())
+                    .unwrap_or(0);
+                steps * step_minor
+            }
+        };
+
+        i64::try_from(rounded).unwrap_or(i64::MAX)
+    }
+}
+
+/// Decorates a [price source](PriceSource) to offer its price in the reader's
+/// [preferred currencies](super::RequestContext::get_preferred_currencies) via an offline
+/// [exchange rate table](ExchangeRateTable); prices fail once the rates are older than
+/// `max_age_hours`
+/// # Examples
+/// ```yaml
+/// price_source: !Converted
+///   source: !Hard $1.00
+///   rates_file: rates.yml
+///   rounding: !Ending 99
+///   max_age_hours: 48
+/// ```
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(try_from = "CurrencyConversionConfig")]
+pub struct CurrencyConversion {
+    source: Box<PriceSource>,
+    rates: ExchangeRateTable,
+    rounding: Rounding,
+    /// File the rates were read from, needed to serialize the conversion
+    rates_file: Option<(String, u64)>,
+}
+
+#[derive(Deserialize)]
+struct CurrencyConversionConfig {
+    source: Box<PriceSource>,
+    rates_file: String,
+    #[serde(default)]
+    rounding: Rounding,
+    #[serde(default = "default_max_age_hours")]
+    max_age_hours: u64,
+}
+
+fn default_max_age_hours() -> u64 {
+    7 * 24
+}
+
+impl TryFrom<CurrencyConversionConfig> for CurrencyConversion {
+    type Error = ExchangeRateError;
+
+    fn try_from(config: CurrencyConversionConfig) -> Result<Self, Self::Error> {
+        CurrencyConversion::from_rates_file(
+            config.source,
+            &config.rates_file,
+            config.rounding,
+            config.max_age_hours,
+        )
+    }
+}
+
+impl CurrencyConversion {
+    pub fn new(
+        source: Box<PriceSource>,
+        rates: ExchangeRateTable,
+        rounding: Rounding,
+    ) -> CurrencyConversion {
+        CurrencyConversion {
+            source,
+            rates,
+            rounding,
+            rates_file: None,
+        }
+    }
+
+    /// Conversion with the rates of a file, relative to the config it is in, which must not be
+    /// older than `max_age_hours`, see [check_age](Self::check_age)
+    pub fn from_rates_file(
+        source: Box<PriceSource>,
+        rates_file: &str,
+        rounding: Rounding,
+        max_age_hours: u64,
+    ) -> Result<CurrencyConversion, ExchangeRateError> {
+        if max_age_hours.checked_mul(3600).is_none() {
+            return Err(ExchangeRateError::InvalidMaxAge(max_age_hours));
+        }
+        let rates = ExchangeRateTable::from_file(&resolve_config_path(rates_file))?;
+        let conversion = CurrencyConversion {
+            rates_file: Some((rates_file.to_string(), max_age_hours)),
+            ..CurrencyConversion::new(source, rates, rounding)
+        };
+
+        conversion.check_age(unix_now())?;
+        Ok(conversion)
+    }
+
+    /// Rates of a file must still be within their `max_age_hours` at `now`, also long after
+    /// loading; rates given [directly](Self::new) do not expire
+    pub fn check_age(&self, now: u64) -> Result<(), ExchangeRateError> {
+        match &self.rates_file {
+            Some((_, max_age_hours)) => self
+                .rates
+                .check_age(now, max_age_hours.saturating_mul(3600)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn get_source(&self) -> &PriceSource {
+        &self.source
+    }
+
+    /// Convert into the first preferred currency that is either the original one or has a rate;
+    /// without such a currency the price stays unchanged
+    pub fn convert(&self, price: Money, preferred: &[CurrencyCode]) -> Money {
+        for target in preferred {
+            if target == price.currency_code() {
+                break;
+            }
+
+            let Some(rate) = self.rates.get_rate(price.currency_code(), target) else {
+                continue;
+            };
+
+            let digits = target.minor_unit_digits();
+            let converted_minor_units = price
+                .to_decimal()
+                .checked_mul(&rate)
+                .and_then(|amount| amount.shift(digits as i32));
+
+            if let Ok(minor_units) = converted_minor_units {
+                let rounded = self.rounding.apply(&minor_units, digits);
+                return Money::from_minor_units(rounded, target.clone());
+            }
+        }
+
+        price
+    }
+}
+
+/// Only conversions [read from a rates file](CurrencyConversion::from_rates_file) can be
+/// serialized
+impl Serialize for CurrencyConversion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let Some((rates_file, max_age_hours)) = &self.rates_file else {
+            return Err(ser::Error::custom(
+                "currency conversion without a rates file cannot be serialized",
+            ));
+        };
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("source", &self.source)?;
+        map.serialize_entry("rates_file", rates_file)?;
+        if self.rounding != Rounding::default() {
+            map.serialize_entry("rounding", &self.rounding)?;
+        }
+        if *max_age_hours != default_max_age_hours() {
+            map.serialize_entry("max_age_hours", max_age_hours)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(code: &str) -> CurrencyCode {
+        CurrencyCode::new(code).unwrap()
+    }
+
+    fn dec(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    fn money(s: &str) -> Money {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rounding_ending() {
+        assert_eq!(Rounding::Ending(99).apply(&dec("123"), 2), 99);
+        assert_eq!(Rounding::Ending(99).apply(&dec("160"), 2), 199);
+        assert_eq!(Rounding::Ending(99).apply(&dec("20"), 2), 99);
+    }
+
+    #[test]
+    fn test_rounding_nearest() {
+        assert_eq!(Rounding::Nearest(dec("0.05")).apply(&dec("123"), 2), 125);
+        assert_eq!(Rounding::Nearest(dec("0.05")).apply(&dec("122"), 2), 120);
+        assert_eq!(Rounding::Nearest(dec("10")).apply(&dec("1234"), 0), 1230);
+        assert_eq!(Rounding::Cent.apply(&dec("122.5"), 2), 123);
+    }
+
+    #[test]
+    fn test_convert_to_preferred_currency() {
+        let rates = ExchangeRateTable::from_csv_str("USD,EUR,0.92,1700000000\n").unwrap();
+        let price_source: PriceSource = serde_yml::from_str("!Hard $2.00").unwrap();
+        let conversion = CurrencyConversion::new(
+            Box::new(price_source),
+            rates,
+            Rounding::Nearest(dec("0.05")),
+        );
+
+        let converted = conversion.convert(money("$2.00"), &[code("GBP"), code("EUR")]);
+
+        assert_eq!(converted, money("€1.85"));
+    }
+
+    #[test]
+    fn test_convert_between_minor_unit_digits() {
+        let rates = ExchangeRateTable::from_csv_str("USD,JPY,151.37,1700000000\n").unwrap();
+        let price_source: PriceSource = serde_yml::from_str("!Hard $2.00").unwrap();
+        let conversion = CurrencyConversion::new(Box::new(price_source), rates, Rounding::Cent);
+
+        let converted = conversion.convert(money("$2.00"), &[code("JPY")]);
+
+        assert_eq!(converted, money("¥303"));
+    }
+
+    #[test]
+    fn test_convert_keeps_original_if_preferred() {
+        let rates = ExchangeRateTable::from_csv_str("USD,EUR,0.92,1700000000\n").unwrap();
+        let price_source: PriceSource = serde_yml::from_str("!Hard $2.00").unwrap();
+        let conversion = CurrencyConversion::new(Box::new(price_source), rates, Rounding::Cent);
+
+        let converted = conversion.convert(money("$2.00"), &[code("USD"), code("EUR")]);
+
+        assert_eq!(converted, money("$2.00"));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_old_rates_file() {
+        let rates_path = std::env::temp_dir().join("rustwall_test_old_rates.csv");
+        std::fs::write(&rates_path, "USD,EUR,0.92,2000-01-01T00:00:00Z\n").unwrap();
+
+        let config_yml = format!(
+            "source: !Hard $1.00\nrates_file: {}\nmax_age_hours: 24\n",
+            rates_path.display()
+        );
+
+        let conversion: Result<CurrencyConversion, _> = serde_yml::from_str(&config_yml);
+
+        assert!(conversion.is_err());
+    }
+
+    #[test]
+    fn test_rates_expire_after_loading() {
+        let rates_path = std::env::temp_dir().join("rustwall_test_expiring_rates.csv");
+        std::fs::write(&rates_path, format!("USD,EUR,0.92,{}\n", unix_now())).unwrap();
+        let price_source: PriceSource = serde_yml::from_str("!Hard $2.00").unwrap();
+
+        let conversion = CurrencyConversion::from_rates_file(
+            Box::new(price_source),
+            &rates_path.display().to_string(),
+            Rounding::Cent,
+            24,
+        )
+        .unwrap();
+
+        assert!(conversion.check_age(unix_now() + 3600).is_ok());
+        assert!(matches!(
+            conversion.check_age(unix_now() + 25 * 3600),
+            Err(ExchangeRateError::TooOld { .. })
+        ));
+        assert!(matches!(
+            CurrencyConversion::from_rates_file(
+                Box::new(serde_yml::from_str("!Hard $2.00").unwrap()),
+                &rates_path.display().to_string(),
+                Rounding::Cent,
+                u64::MAX / 1000,
+            ),
+            Err(ExchangeRateError::InvalidMaxAge(_))
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+
+use super::RequestContext;
+use crate::money::CurrencyCode;
+
+/// Configures how the reader's currency is picked from a [request](RequestContext)
+///
+/// Sources are tried in order: header, cookie, `Accept-Language`, fallback. Prices in documents
+/// written without symbol or code, like `data-price="1.25"`, are read in the `document_currency`
+/// and rejected without it, so the reader cannot pick their currency
+/// # Examples
+/// ```yaml
+/// currency_selection:
+///   header: X-Rustwall-Currency
+///   cookie: rustwall_currency
+///   accept_language: true
+///   fallback: USD
+///   document_currency: USD
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct CurrencySelection {
+    header: Option<String>,
+    cookie: Option<String>,
+    accept_language: bool,
+    fallback: Option<CurrencyCode>,
+    document_currency: Option<CurrencyCode>,
+}
+
+impl Default for CurrencySelection {
+    fn default() -> Self {
+        CurrencySelection {
+            header: Some("X-Rustwall-Currency".to_string()),
+            cookie: Some("rustwall_currency".to_string()),
+            accept_language: true,
+            fallback: None,
+            document_currency: None,
+        }
+    }
+}
+
+impl CurrencySelection {
+    /// All currencies the reader may want, most preferred first and without duplicates
+    pub fn preferred_currencies(&self, ctx: &RequestContext) -> Vec<CurrencyCode> {
+        let mut currencies = Vec::new();
+
+        let from_header = self.header.as_deref().and_then(|h| ctx.get_header(h));
+        let from_cookie = self.cookie.as_deref().and_then(|c| ctx.get_cookie(c));
+
+        for value in [from_header, from_cookie].into_iter().flatten() {
+            if let Ok(code) = CurrencyCode::new(value.trim()) {
+                currencies.push(code);
+            }
+        }
+
+        if let (true, Some(accept_language)) =
+            (self.accept_language, ctx.get_header("Accept-Language"))
+        {
+            currencies.extend(currencies_from_accept_language(accept_language));
+        }
+
+        currencies.extend(self.fallback.clone());
+
+        let mut deduplicated: Vec<CurrencyCode> = Vec::new();
+        for code in currencies {
+            if !deduplicated.contains(&code) {
+                deduplicated.push(code);
+            }
+        }
+
+        deduplicated
+    }
+
+    pub fn get_document_currency(&self) -> Option<&CurrencyCode> {
+        self.document_currency.as_ref()
+    }
+
+    /// Store the [preferred currencies](Self::preferred_currencies) and the document currency in
+    /// the request context; currencies already in the context come first and a document
+    /// currency already set is kept, so applying twice changes nothing
+    pub fn apply(&self, ctx: RequestContext) -> RequestContext {
+        let mut currencies = ctx.get_preferred_currencies().to_vec();
+        for code in self.preferred_currencies(&ctx) {
+            if !currencies.contains(&code) {
+                currencies.push(code);
+            }
+        }
+        let document_currency = ctx
+            .get_document_currency()
+            .or(self.document_currency.as_ref())
+            .cloned();
+
+        ctx.with_preferred_currencies(currencies)
+            .with_document_currency(document_currency)
+    }
+}
+
+fn currencies_from_accept_language(accept_language: &str) -> Vec<CurrencyCode> {
+    let mut weighted: Vec<(f32, &str)> = accept_language
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.trim().split(';');
+            let tag = pieces.next()?.trim();
+            let quality = pieces
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            Some((quality, tag))
+        })
+        .filter(|(quality, tag)| *quality > 0.0 && !tag.is_empty())
+        .collect();
+
+    // stable sort keeps header order for equal weights
+    weighted.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    weighted
+        .into_iter()
+        .filter_map(|(_, tag)| currency_for_language_tag(tag))
+        .collect()
+}
+
+fn currency_for_language_tag(tag: &str) -> Option<CurrencyCode> {
+    let mut subtags = tag.split(['-', '_']);
+    let language = subtags.next()?.to_ascii_lowercase();
+    let region = subtags
+        .find(|s| s.len() == 2 && s.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(|s| s.to_ascii_uppercase());
+
+    let code = match region.as_deref() {
+        Some("US") => "USD",
+        Some("GB") => "GBP",
+        Some("CH") => "CHF",
+        Some("CA") => "CAD",
+        Some("AU") => "AUD",
+        Some("JP") => "JPY",
+        Some(
+            "AT" | "BE" | "CY" | "DE" | "EE" | "ES" | "FI" | "FR" | "GR" | "HR" | "IE" | "IT"
+            | "LT" | "LU" | "LV" | "MT" | "NL" | "PT" | "SI" | "SK",
+        ) => "EUR",
+        Some(_) => return None,
+        None => match language.as_str() {
+            "de" | "fr" | "it" | "es" | "nl" | "fi" | "el" | "sk" | "sl" | "et" | "lv" | "lt" => {
+                "EUR"
+            }
+            "ja" => "JPY",
+            _ => return None,
+        },
+    };
+
+    CurrencyCode::new(code).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(codes: &[&str]) -> Vec<CurrencyCode> {
+        codes
+            .iter()
+            .map(|c| CurrencyCode::new(c).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_header_before_cookie_before_accept_language() {
+        let selection = CurrencySelection::default();
+        let ctx = RequestContext::new()
+            .with_header("X-Rustwall-Currency", "GBP")
+            .with_cookie("rustwall_currency", "EUR")
+            .with_header("Accept-Language", "en-US");
+
+        assert_eq!(
+            selection.preferred_currencies(&ctx),
+            codes(&["GBP", "EUR", "USD"])
+        );
+    }
+
+    #[test]
+    fn test_accept_language_quality_order() {
+        let selection = CurrencySelection::default();
+        let ctx =
+            RequestContext::new().with_header("Accept-Language", "en;q=0.5, de-DE, en-GB;q=0.8");
+
+        assert_eq!(selection.preferred_currencies(&ctx), codes(&["EUR", "GBP"]));
+    }
+
+    #[test]
+    fn test_fallback_from_config() {
+        let config_yml = r#"
+        header: X-Currency
+        accept_language: false
+        fallback: USD
+        "#;
+
+        let selection: CurrencySelection = serde_yml::from_str(config_yml).unwrap();
+        let ctx = RequestContext::new()
+            .with_header("Accept-Language", "de-DE")
+            .with_header("X-Currency", "not a currency");
+
+        assert_eq!(selection.preferred_currencies(&ctx), codes(&["USD"]));
+    }
+
+    #[test]
+    fn test_apply_keeps_context_currencies() {
+        let config_yml = r#"
+        fallback: EUR
+        document_currency: USD
+        "#;
+
+        let selection: CurrencySelection = serde_yml::from_str(config_yml).unwrap();
+        let ctx = selection.apply(
+            RequestContext::new()
+                .with_preferred_currencies(codes(&["GBP"]))
+                .with_document_currency(Some(CurrencyCode::new("CHF").unwrap())),
+        );
+
+        assert_eq!(ctx.get_preferred_currencies(), codes(&["GBP", "EUR"]));
+        assert_eq!(ctx.get_document_currency().unwrap().as_str(), "CHF");
+
+        let again = selection.apply(ctx.clone());
+        assert_eq!(
+            again.get_preferred_currencies(),
+            ctx.get_preferred_currencies()
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+use crate::money::{CurrencyCode, Money};
+
+/// Prices of the same item in several currencies, the first entry being the default
+///
+/// Deserializes either from a single price or from a map of currency codes to prices
+/// # Examples
+/// ```yaml
+/// !Hard $1.00
+/// !Hard { USD: "$1.00", EUR: "€0.95", GBP: "£0.85" }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrencyTable {
+    entries: Vec<Money>,
+}
+
+impl CurrencyTable {
+    /// Table of prices in distinct currencies, the first being the default
+    pub fn new(prices: Vec<Money>) -> Result<CurrencyTable, String> {
+        if prices.is_empty() {
+            return Err("A currency table needs at least one price".to_string());
+        }
+        for (i, price) in prices.iter().enumerate() {
+            if prices[..i]
+                .iter()
+                .any(|p| p.currency_code() == price.currency_code())
+            {
+                return Err(format!(
+                    "Duplicate currency code '{}'",
+                    price.currency_code()
+                ));
+            }
+        }
+
+        Ok(CurrencyTable { entries: prices })
+    }
+
+    pub fn get(&self, code: &CurrencyCode) -> Option<&Money> {
+        self.entries.iter().find(|p| p.currency_code() == code)
+    }
+
+    pub fn get_currency_codes(&self) -> Vec<&CurrencyCode> {
+        self.entries.iter().map(Money::currency_code).collect()
+    }
+
+    /// Pick the first preferred currency available in the table, otherwise the default entry
+    pub fn select(&self, preferred: &[CurrencyCode]) -> &Money {
+        preferred
+            .iter()
+            .find_map(|code| self.get(code))
+            .unwrap_or(&self.entries[0])
+    }
+}
+
+impl From<Money> for CurrencyTable {
+    fn from(price: Money) -> Self {
+        CurrencyTable {
+            entries: vec![price],
+        }
+    }
+}
+
+impl fmt::Display for CurrencyTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prices: Vec<String> = self.entries.iter().map(Money::to_string).collect();
+
+        match prices.as_slice() {
+            [price] => write!(f, "{}", price),
+            _ => write!(f, "{{ {} }}", prices.join(", ")),
+        }
+    }
+}
+
+impl Serialize for CurrencyTable {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let [price] = self.entries.as_slice() {
+            return serializer.collect_str(price);
+        }
+
+        let mut map = serializer.serialize_map(Some(self.entries.len()))?;
+        for price in &self.entries {
+            map.serialize_entry(price.currency_code(), &price.to_string())?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for CurrencyTable {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CurrencyTableVisitor;
+
+        impl<'de> Visitor<'de> for CurrencyTableVisitor {
+            type Value = CurrencyTable;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a price like '$1.00' or a map of currency codes to prices")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let price = value.parse::<Money>().map_err(de::Error::custom)?;
+
+                Ok(CurrencyTable {
+                    entries: vec![price],
+                })
+            }
+
+            fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                let mut entries: Vec<Money> = Vec::new();
+
+                while let Some((code, price)) = map.next_entry::<CurrencyCode, String>()? {
+                    let price = Money::parse_with_default(&price, Some(&code))
+                        .map_err(de::Error::custom)?;
+
+                    if *price.currency_code() != code {
+                        return Err(de::Error::custom(format!(
+                            "Price '{}' is not in currency '{}'",
+                            price, code
+                        )));
+                    }
+                    if entries.iter().any(|p| *p.currency_code() == code) {
+                        return Err(de::Error::custom(format!(
+                            "Duplicate currency code '{}'",
+                            code
+                        )));
+                    }
+                    entries.push(price);
+                }
+
+                if entries.is_empty() {
+                    return Err(de::Error::invalid_length(0, &self));
+                }
+
+                Ok(CurrencyTable { entries })
+            }
+        }
+
+        deserializer.deserialize_any(CurrencyTableVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(code: &str) -> CurrencyCode {
+        CurrencyCode::new(code).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize_single_price() {
+        let table: CurrencyTable = serde_yml::from_str("$1.00").unwrap();
+
+        assert_eq!(table.get_currency_codes(), vec![&code("USD")]);
+    }
+
+    #[test]
+    fn test_deserialize_single_price_unknown_symbol() {
+        let table: Result<CurrencyTable, _> = serde_yml::from_str("\"1.00\"");
+
+        assert!(table.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_mismatching_currency() {
+        let table: Result<CurrencyTable, _> = serde_yml::from_str("{ EUR: \"$1.00\" }");
+
+        assert!(table.is_err());
+    }
+
+    #[test]
+    fn test_select_preferred_currency() {
+        let config_yml = r#"
+        { USD: "$1.00", EUR: "€0.95", GBP: "0.85" }
+        "#;
+
+        let table: CurrencyTable = serde_yml::from_str(config_yml).unwrap();
+        let selected = table.select(&[code("CHF"), code("GBP")]);
+
+        assert_eq!(*selected, "£0.85".parse::<Money>().unwrap());
+    }
+
+    #[test]
+    fn test_select_defaults_to_first_entry() {
+        let config_yml = r#"
+        EUR: "€0.95"
+        USD: "$1.00"
+        "#;
+
+        let table: CurrencyTable = serde_yml::from_str(config_yml).unwrap();
+        let selected = table.select(&[code("CHF")]);
+
+        assert_eq!(*selected.currency_code(), code("EUR"));
+    }
+}
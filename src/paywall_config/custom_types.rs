@@ -0,0 +1,657 @@
+use async_trait::async_trait;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, VariantAccess, Visitor,
+};
+use serde::{Deserializer, Serialize, Serializer, forward_to_deserialize_any};
+use serde_yml::Value;
+use serde_yml::value::{Tag, TaggedValue};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+use super::config_schema::{is_builtin_condition_tag, is_builtin_price_source_tag};
+use super::{DocumentAndPath, PaywallCondition, PriceSource, PriceSourceExtractError};
+use crate::money::Money;
+
+/// Condition type of a library user, written in configs under the tag it is
+/// [registered](register_condition) with, e.g. `!MyCrmSegment gold`
+///
+/// The value under the tag is deserialized into the implementing type. Synchronous
+/// implementations simply do not await. Asynchronous ones are awaited by the `_async`
+/// evaluation methods, e.g. [get_price_async](super::PaywallConfigV1::get_price_async), which
+/// async servers should call; the other methods run them with
+/// [block_on](crate::utils::block_on), parking the calling thread
+/// # Examples
+/// ```
+/// use async_trait::async_trait;
+/// use rustwall::paywall_config::{
+///     CustomCondition, DocumentAndPath, PaywallConfigV1, RequestContext, register_condition,
+/// };
+/// use serde::Deserialize;
+///
+/// /// Segment of a reader, like a request to a CRM service
+/// async fn crm_segment(user: String) -> String {
+///     let segment = tokio::spawn(async move { if user == "alice" { "gold" } else { "basic" } });
+///     segment.await.unwrap().to_string()
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct MyCrmSegment(String);
+///
+/// #[async_trait]
+/// impl CustomCondition for MyCrmSegment {
+///     async fn is_paywalled(&self, doc_and_path: &DocumentAndPath) -> bool {
+///         let ctx = doc_and_path.get_request_context();
+///         let user = ctx.get_cookie("user").unwrap_or_default().to_string();
+///         crm_segment(user).await == self.0
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// register_condition::<MyCrmSegment>("MyCrmSegment")?;
+/// let config = PaywallConfigV1::from_yaml_str(
+///     "paths: [ { paywall_conditions: [ !MyCrmSegment gold ], price_source: !Hard $1.25 } ]",
+/// )?;
+///
+/// // in a request handler on a single threaded runtime
+/// let doc_and_path = DocumentAndPath::new_from_html_and_path_str("<html></html>", "/a")?
+///     .with_request_context(RequestContext::new().with_cookie("user", "alice"));
+/// let decision = config.get_price_async(&doc_and_path).await;
+/// assert_eq!(decision.to_string(), "element #0: price $1.25");
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait CustomCondition: Send + Sync {
+    async fn is_paywalled(&self, doc_and_path: &DocumentAndPath) -> bool;
+}
+
+/// Price source type of a library user, written in configs under the tag it is
+/// [registered](register_price_source) with; see [CustomCondition] for sync and async
+/// implementations
+#[async_trait]
+pub trait CustomPriceSource: Send + Sync {
+    async fn get_price(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> Result<Money, PriceSourceExtractError>;
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CustomTypeError {
+    #[error("'!{0}' is a built-in tag")]
+    BuiltinTag(String),
+    #[error("'!{0}' is already registered")]
+    AlreadyRegistered(String),
+    #[error("Invalid tag '!{0}': use letters, digits and '_' only")]
+    InvalidTag(String),
+}
+
+/// Instance of a registered custom type, keeping its config value to be serialized again
+pub struct CustomType<T: ?Sized> {
+    tag: &'static str,
+    value: Value,
+    inner: Arc<T>,
+}
+
+impl<T: ?Sized> CustomType<T> {
+    pub fn get_tag(&self) -> &'static str {
+        self.tag
+    }
+
+    /// Value under the tag in the config
+    pub fn get_value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn get_inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// Instances are equal if they were read from the same tag and value
+impl<T: ?Sized> PartialEq for CustomType<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag && self.value == other.value
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for CustomType<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomType")
+            .field("tag", &self.tag)
+            .field("value", &self.value)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Tag and value as compact JSON, e.g. `MyCrmSegment "gold"`
+impl<T: ?Sized> fmt::Display for CustomType<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = serde_json::to_string(&self.value).unwrap_or_default();
+        write!(f, "{} {}", self.tag, value)
+    }
+}
+
+impl<T: ?Sized> Serialize for CustomType<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new(self.tag),
+            value: self.value.clone(),
+        }))
+        .serialize(serializer)
+    }
+}
+
+type Factory<T> = fn(Value) -> Result<Arc<T>, serde_yml::Error>;
+
+pub(crate) struct CustomTypeRegistry {
+    conditions: BTreeMap<&'static str, Factory<dyn CustomCondition>>,
+    price_sources: BTreeMap<&'static str, Factory<dyn CustomPriceSource>>,
+}
+
+/// Process-wide, as deserialization cannot be handed a registry
+static REGISTRY: RwLock<CustomTypeRegistry> = RwLock::new(CustomTypeRegistry {
+    conditions: BTreeMap::new(),
+    price_sources: BTreeMap::new(),
+});
+
+fn check_tag(tag: &str, is_builtin: fn(&str) -> bool) -> Result<(), CustomTypeError> {
+    if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(CustomTypeError::InvalidTag(tag.to_string()));
+    }
+    if is_builtin(tag) {
+        return Err(CustomTypeError::BuiltinTag(tag.to_string()));
+    }
+    Ok(())
+}
+
+/// Read `!tag value` conditions as `T`; register before loading configs that use the tag
+pub fn register_condition<T>(tag: &'static str) -> Result<(), CustomTypeError>
+where
+    T: CustomCondition + DeserializeOwned + 'static,
+{
+    check_tag(tag, is_builtin_condition_tag)?;
+
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if registry.conditions.contains_key(tag) {
+        return Err(CustomTypeError::AlreadyRegistered(tag.to_string()));
+    }
+    registry.conditions.insert(tag, |value| {
+        Ok(Arc::new(serde_yml::from_value::<T>(value)?))
+    });
+    Ok(())
+}
+
+/// Read `!tag value` price sources as `T`; register before loading configs that use the tag
+pub fn register_price_source<T>(tag: &'static str) -> Result<(), CustomTypeError>
+where
+    T: CustomPriceSource + DeserializeOwned + 'static,
+{
+    check_tag(tag, is_builtin_price_source_tag)?;
+
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if registry.price_sources.contains_key(tag) {
+        return Err(CustomTypeError::AlreadyRegistered(tag.to_string()));
+    }
+    registry.price_sources.insert(tag, |value| {
+        Ok(Arc::new(serde_yml::from_value::<T>(value)?))
+    });
+    Ok(())
+}
+
+/// Enum with built-in variants and a variant for registered custom types
+pub(crate) trait CustomTagged: Sized {
+    type Inner: ?Sized;
+
+    const NAME: &'static str;
+
+    /// Registered tag and factory of a custom type
+    fn lookup(
+        registry: &CustomTypeRegistry,
+        tag: &str,
+    ) -> Option<(&'static str, Factory<Self::Inner>)>;
+
+    fn from_custom(custom: CustomType<Self::Inner>) -> Self;
+
+    /// The derived deserialization of the built-in variants
+    fn deserialize_builtin<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>;
+}
+
+impl CustomTagged for PaywallCondition {
+    type Inner = dyn CustomCondition;
+
+    const NAME: &'static str = "paywall condition";
+
+    fn lookup(
+        registry: &CustomTypeRegistry,
+        tag: &str,
+    ) -> Option<(&'static str, Factory<Self::Inner>)> {
+        registry
+            .conditions
+            .get_key_value(tag)
+            .map(|(tag, factory)| (*tag, *factory))
+    }
+
+    fn from_custom(custom: CustomType<Self::Inner>) -> Self {
+        PaywallCondition::Custom(custom)
+    }
+
+    fn deserialize_builtin<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        PaywallCondition::deserialize(deserializer)
+    }
+}
+
+impl CustomTagged for PriceSource {
+    type Inner = dyn CustomPriceSource;
+
+    const NAME: &'static str = "price source";
+
+    fn lookup(
+        registry: &CustomTypeRegistry,
+        tag: &str,
+    ) -> Option<(&'static str, Factory<Self::Inner>)> {
+        registry
+            .price_sources
+            .get_key_value(tag)
+            .map(|(tag, factory)| (*tag, *factory))
+    }
+
+    fn from_custom(custom: CustomType<Self::Inner>) -> Self {
+        PriceSource::Custom(custom)
+    }
+
+    fn deserialize_builtin<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        PriceSource::deserialize(deserializer)
+    }
+}
+
+/// Read the tag, then either the value of a registered tag or a built-in variant
+///
+/// The input is streamed instead of buffered, so errors keep their line and column
+pub(crate) fn deserialize_tagged<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: CustomTagged,
+{
+    deserializer.deserialize_enum(T::NAME, &[], TaggedVisitor(PhantomData))
+}
+
+struct TaggedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: CustomTagged> Visitor<'de> for TaggedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a tagged {}", T::NAME)
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<T, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let (tag, variant): (String, A::Variant) = data.variant()?;
+        let custom = T::lookup(&REGISTRY.read().unwrap_or_else(|e| e.into_inner()), &tag);
+
+        let Some((tag, factory)) = custom else {
+            return T::deserialize_builtin(ReplayTag { tag, variant });
+        };
+        let value: Value = variant.newtype_variant()?;
+        let inner = factory(value.clone()).map_err(de::Error::custom)?;
+        Ok(T::from_custom(CustomType { tag, value, inner }))
+    }
+}
+
+/// Hands an already read tag and the rest of its variant to a derived deserializer
+struct ReplayTag<A> {
+    tag: String,
+    variant: A,
+}
+
+impl<'de, A> Deserializer<'de> for ReplayTag<A>
+where
+    A: VariantAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn deserialize_any<W>(self, visitor: W) -> Result<W::Value, A::Error>
+    where
+        W: Visitor<'de>,
+    {
+        visitor.visit_enum(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+impl<'de, A> EnumAccess<'de> for ReplayTag<A>
+where
+    A: VariantAccess<'de>,
+{
+    type Error = A::Error;
+    type Variant = A;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, A), A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        let tag = seed.deserialize(self.tag.into_deserializer())?;
+        Ok((tag, self.variant))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paywall_config::{
+        BinaryResponse, InMemoryPaywallEventSink, PaywallConfigV1, PaywallElement, RequestContext,
+    };
+    use serde::Deserialize;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::{Mutex, Once};
+    use std::task::{Context, Poll};
+
+    /// Segment from a cookie, like a CRM lookup
+    #[derive(Deserialize)]
+    struct CrmSegment(String);
+
+    #[async_trait]
+    impl CustomCondition for CrmSegment {
+        async fn is_paywalled(&self, doc_and_path: &DocumentAndPath) -> bool {
+            doc_and_path.get_request_context().get_cookie("segment") == Some(self.0.as_str())
+        }
+    }
+
+    /// Future that is pending once, like a request to a pricing service
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct LoyaltyPrice {
+        base: Money,
+        #[serde(default)]
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl CustomPriceSource for LoyaltyPrice {
+        async fn get_price(
+            &self,
+            _doc_and_path: &DocumentAndPath,
+        ) -> Result<Money, PriceSourceExtractError> {
+            YieldOnce(false).await;
+            if self.fail {
+                return Err(PriceSourceExtractError::CustomSourceError(
+                    "pricing service unavailable".to_string(),
+                ));
+            }
+            Ok(self.base.with_minor_units(self.base.minor_units() / 2))
+        }
+    }
+
+    /// Failing source counting its calls per key, as tests run in parallel
+    #[derive(Deserialize)]
+    struct CountedFailure(String);
+
+    static CALLS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+    #[async_trait]
+    impl CustomPriceSource for CountedFailure {
+        async fn get_price(
+            &self,
+            _doc_and_path: &DocumentAndPath,
+        ) -> Result<Money, PriceSourceExtractError> {
+            *CALLS.lock().unwrap().entry(self.0.clone()).or_default() += 1;
+            Err(PriceSourceExtractError::CustomSourceError(
+                "unavailable".to_string(),
+            ))
+        }
+    }
+
+    /// Price computed by a task on the test runtime, which only progresses while it is awaited
+    #[derive(Deserialize)]
+    struct SpawnedPrice(Money);
+
+    #[async_trait]
+    impl CustomPriceSource for SpawnedPrice {
+        async fn get_price(
+            &self,
+            _doc_and_path: &DocumentAndPath,
+        ) -> Result<Money, PriceSourceExtractError> {
+            let price = self.0.clone();
+            Ok(tokio::spawn(async move { price }).await.unwrap())
+        }
+    }
+
+    /// Segment looked up by a task on the test runtime
+    #[derive(Deserialize)]
+    struct SpawnedSegment(String);
+
+    #[async_trait]
+    impl CustomCondition for SpawnedSegment {
+        async fn is_paywalled(&self, doc_and_path: &DocumentAndPath) -> bool {
+            let segment = doc_and_path
+                .get_request_context()
+                .get_cookie("segment")
+                .map(str::to_string);
+            let expected = self.0.clone();
+            tokio::spawn(async move { segment.as_deref() == Some(expected.as_str()) })
+                .await
+                .unwrap()
+        }
+    }
+
+    fn register() {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            register_condition::<CrmSegment>("TestCrmSegment").unwrap();
+            register_price_source::<LoyaltyPrice>("TestLoyaltyPrice").unwrap();
+            register_price_source::<CountedFailure>("TestCountedFailure").unwrap();
+            register_price_source::<SpawnedPrice>("TestSpawnedPrice").unwrap();
+            register_condition::<SpawnedSegment>("TestSpawnedSegment").unwrap();
+        });
+    }
+
+    const CONFIG: &str = r#"
+paths:
+  - id: gold
+    paywall_conditions:
+      - !HasRegexPath "^/premium/.*$"
+      - !TestCrmSegment gold
+    price_source: !FirstOf
+      - !TestLoyaltyPrice { base: $3.00, fail: true }
+      - !TestLoyaltyPrice { base: $2.00 }
+  - id: premium
+    paywall_conditions: [ !HasRegexPath "^/premium/.*$" ]
+    price_source: !Hard $2.00
+"#;
+
+    fn price_for_segment(config: &PaywallConfigV1, segment: &str) -> String {
+        let doc_and_path =
+            DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", "/premium/a")
+                .unwrap()
+                .with_request_context(RequestContext::new().with_cookie("segment", segment));
+        config.get_price(&doc_and_path).to_string()
+    }
+
+    #[test]
+    fn test_custom_types_deserialize_with_builtins() {
+        register();
+        let config = PaywallConfigV1::from_yaml_str(CONFIG).unwrap();
+
+        assert_eq!(
+            price_for_segment(&config, "gold"),
+            "element 'gold': price $1.00"
+        );
+        assert_eq!(
+            price_for_segment(&config, "silver"),
+            "element 'premium': price $2.00"
+        );
+    }
+
+    #[test]
+    fn test_explain_calls_custom_source_once() {
+        register();
+        let mut config = PaywallConfigV1::from_yaml_str(
+            r#"
+explain_header: true
+paths:
+  - paywall_conditions: []
+    price_source: !FirstOf [ !TestCountedFailure explain, !Hard $1.00 ]
+"#,
+        )
+        .unwrap();
+        let sink = Arc::new(InMemoryPaywallEventSink::default());
+        config.set_event_sink(sink.clone());
+        let doc_and_path =
+            DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", "/a")
+                .unwrap();
+
+        let (decision, header) = config.get_price_with_explain_header(&doc_and_path);
+
+        assert_eq!(decision.to_string(), "element #0: price $1.00");
+        assert!(header.unwrap().1.contains("FirstOf #2: Hard $1.00"));
+        assert_eq!(CALLS.lock().unwrap()["explain"], 1);
+        assert!(sink.get_events().is_empty());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_get_price_async_on_single_threaded_runtime() {
+        register();
+        let config = PaywallConfigV1::from_yaml_str(
+            r#"
+paths:
+  - id: spawned
+    paywall_conditions: [ !TestCrmSegment gold ]
+    price_source: !FirstOf [ !TestSpawnedPrice $4.00, !Hard $1.00 ]
+"#,
+        )
+        .unwrap();
+        let doc_and_path =
+            DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", "/a")
+                .unwrap()
+                .with_request_context(RequestContext::new().with_cookie("segment", "gold"));
+
+        assert_eq!(
+            config.get_price_async(&doc_and_path).await.to_string(),
+            "element 'spawned': price $4.00"
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_element_async_on_single_threaded_runtime() {
+        register();
+        let element: PaywallElement = serde_yml::from_str(
+            r#"
+id: report
+checkout_url: /buy{path}
+paywall_conditions: [ !TestSpawnedSegment gold ]
+price_source: !TestSpawnedPrice $4.00
+"#,
+        )
+        .unwrap();
+        let doc_and_path =
+            DocumentAndPath::new_from_binary_and_path_str("application/pdf", 100, "/r.pdf")
+                .unwrap()
+                .with_request_context(RequestContext::new().with_cookie("segment", "gold"));
+
+        assert_eq!(
+            element
+                .get_binary_response_async(&doc_and_path, false)
+                .await,
+            Some(BinaryResponse::RedirectToCheckout("/buy/r.pdf".to_string()))
+        );
+        let trace = element.explain_async(0, &doc_and_path).await;
+        assert!(trace.matched);
+        assert_eq!(
+            trace.price.unwrap().result,
+            Ok("$4.00".parse::<Money>().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_custom_types_round_trip() {
+        register();
+        let config = PaywallConfigV1::from_yaml_str(CONFIG).unwrap();
+        let saved = config.to_yaml_string().unwrap();
+
+        assert!(saved.contains("- !TestCrmSegment gold\n"));
+        assert!(saved.contains("- !TestLoyaltyPrice\n"));
+        assert_eq!(PaywallConfigV1::from_yaml_str(&saved).unwrap(), config);
+    }
+
+    #[test]
+    fn test_invalid_custom_value_is_reported() {
+        register();
+        let error = PaywallConfigV1::from_yaml_str(
+            r#"
+paths:
+  - paywall_conditions: [ !TestCrmSegment [gold] ]
+    price_source: !TestLoyaltyPrice { base: 2.00 }
+"#,
+        )
+        .err()
+        .unwrap();
+
+        assert!(matches!(
+            error,
+            crate::paywall_config::PaywallConfigError::Parse(_)
+        ));
+        assert!(
+            PaywallConfigV1::from_yaml_str(&CONFIG.replace("TestCrmSegment", "Unregistered"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_register_rejects_tags() {
+        register();
+
+        assert_eq!(
+            register_condition::<CrmSegment>("HasRegexPath"),
+            Err(CustomTypeError::BuiltinTag("HasRegexPath".to_string()))
+        );
+        assert_eq!(
+            register_condition::<CrmSegment>("TestCrmSegment"),
+            Err(CustomTypeError::AlreadyRegistered(
+                "TestCrmSegment".to_string()
+            ))
+        );
+        assert_eq!(
+            register_price_source::<LoyaltyPrice>("!Loyalty"),
+            Err(CustomTypeError::InvalidTag("!Loyalty".to_string()))
+        );
+        assert!(register_price_source::<LoyaltyPrice>("TestCrmSegment").is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use super::CurrencyTable;
+
+/// What to serve when the price of a matching [element](super::PaywallElement) cannot be
+/// determined or its document cannot be parsed; discounts and tax apply to a fixed price too,
+/// and content whose discount or tax fails stays locked
+/// # Examples
+/// ```yaml
+/// on_error: allow                 # serve for free, e.g. marketing pages
+/// on_error: deny                  # keep locked without a price (default)
+/// on_error: !fixed_price $1.00    # charge a fallback price
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    Allow,
+    #[default]
+    Deny,
+    FixedPrice(CurrencyTable),
+}
+
+impl ErrorPolicy {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ErrorPolicy::Allow => "allow",
+            ErrorPolicy::Deny => "deny",
+            ErrorPolicy::FixedPrice(_) => "fixed_price",
+        }
+    }
+}
+
+/// Emitted each time an [error policy](ErrorPolicy) decides instead of the configured price
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PaywallErrorEvent {
+    pub element_id: Option<String>,
+    pub url_path: String,
+    pub policy: &'static str,
+    pub error: String,
+}
+
+pub trait PaywallEventSink: Send + Sync {
+    fn emit(&self, event: &PaywallErrorEvent);
+}
+
+/// Writes every event as one line of JSON to stderr
+#[derive(Debug, Default)]
+pub struct StderrPaywallEventSink;
+
+impl PaywallEventSink for StderrPaywallEventSink {
+    fn emit(&self, event: &PaywallErrorEvent) {
+        if let Ok(line) = serde_json::to_string(event) {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryPaywallEventSink {
+    events: Mutex<Vec<PaywallErrorEvent>>,
+}
+
+impl InMemoryPaywallEventSink {
+    pub fn get_events(&self) -> Vec<PaywallErrorEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl PaywallEventSink for InMemoryPaywallEventSink {
+    fn emit(&self, event: &PaywallErrorEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_policies() {
+        let policies: Vec<ErrorPolicy> =
+            serde_yml::from_str("[allow, deny, !fixed_price $1.00]").unwrap();
+
+        assert_eq!(policies[0], ErrorPolicy::Allow);
+        assert_eq!(policies[1], ErrorPolicy::Deny);
+        assert_eq!(policies[2].get_name(), "fixed_price");
+    }
+
+    #[test]
+    fn test_event_serializes_to_json() {
+        let event = PaywallErrorEvent {
+            element_id: Some("research".to_string()),
+            url_path: "/research/a".to_string(),
+            policy: "deny",
+            error: "No price".to_string(),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"element_id":"research","url_path":"/research/a","policy":"deny","error":"No price"}"#
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::money::{CurrencyCode, Decimal};
+use crate::utils::parse_timestamp;
+
+/// Exchange rates supplied as a local file, either YAML
+/// ```yaml
+/// base: USD
+/// timestamp: 2025-06-01T00:00:00Z
+/// rates:
+///   EUR: 0.92
+///   GBP: 0.79
+/// ```
+/// or CSV with one `base,quote,rate,timestamp` row per rate
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeRateTable {
+    rates: Vec<(CurrencyCode, CurrencyCode, Decimal)>,
+    timestamp: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum ExchangeRateError {
+    #[error("Cannot read rates file '{path}': {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Cannot parse YAML rates: {0}")]
+    Yaml(#[from] serde_yml::Error),
+    #[error("Cannot parse CSV rates, line {line}: {message}")]
+    Csv { line: usize, message: String },
+    #[error("Invalid timestamp '{0}'")]
+    InvalidTimestamp(String),
+    #[error("Invalid rate {rate} for {from}->{to}: rates must be positive")]
+    InvalidRate {
+        from: CurrencyCode,
+        to: CurrencyCode,
+        rate: Decimal,
+    },
+    #[error("Invalid max_age_hours {0}: too large")]
+    InvalidMaxAge(u64),
+    #[error("Rates from {timestamp} are older than the allowed {max_age_secs}s")]
+    TooOld { timestamp: u64, max_age_secs: u64 },
+}
+
+#[derive(Deserialize)]
+struct YamlRates {
+    base: CurrencyCode,
+    timestamp: String,
+    rates: HashMap<CurrencyCode, Decimal>,
+}
+
+impl ExchangeRateTable {
+    /// Load a rates file, picking the format by its extension (`.csv`, otherwise YAML)
+    pub fn from_file(path: &Path) -> Result<ExchangeRateTable, ExchangeRateError> {
+        let content = fs::read_to_string(path).map_err(|source| ExchangeRateError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+
+        let is_csv = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+        if is_csv {
+            ExchangeRateTable::from_csv_str(&content)
+        } else {
+            ExchangeRateTable::from_yaml_str(&content)
+        }
+    }
+
+    pub fn from_yaml_str(rates_yml: &str) -> Result<ExchangeRateTable, ExchangeRateError> {
+        let parsed: YamlRates = serde_yml::from_str(rates_yml)?;
+        let timestamp = parse_timestamp(&parsed.timestamp)
+            .ok_or(ExchangeRateError::InvalidTimestamp(parsed.timestamp))?;
+
+        let mut rates: Vec<_> = parsed
+            .rates
+            .into_iter()
+            .map(|(to, rate)| (parsed.base.clone(), to, rate))
+            .collect();
+        rates.sort_by(|a, b| a.1.cmp(&b.1));
+
+        ExchangeRateTable::new(rates, timestamp)
+    }
+
+    /// The table is as old as its oldest row
+    pub fn from_csv_str(rates_csv: &str) -> Result<ExchangeRateTable, ExchangeRateError> {
+        let mut rates = Vec::new();
+        let mut timestamp: Option<u64> = None;
+
+        for (index, line) in rates_csv.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.first() == Some(&"base") {
+                continue;
+            }
+
+            let csv_error = |message: String| ExchangeRateError::Csv {
+                line: line_number,
+                message,
+            };
+
+            if fields.len() != 4 {
+                return Err(csv_error(format!(
+                    "expected 4 fields, found {}",
+                    fields.len()
+                )));
+            }
+
+            let from = CurrencyCode::new(fields[0]).map_err(|e| csv_error(e.to_string()))?;
+            let to = CurrencyCode::new(fields[1]).map_err(|e| csv_error(e.to_string()))?;
+            let rate = fields[2]
+                .parse::<Decimal>()
+                .map_err(|e| csv_error(e.to_string()))?;
+            let row_timestamp = parse_timestamp(fields[3])
+                .ok_or(ExchangeRateError::InvalidTimestamp(fields[3].to_string()))?;
+
+            timestamp = Some(timestamp.map_or(row_timestamp, |t| t.min(row_timestamp)));
+            rates.push((from, to, rate));
+        }
+
+        let timestamp = timestamp.ok_or(ExchangeRateError::Csv {
+            line: 0,
+            message: "no rates found".to_string(),
+        })?;
+
+        ExchangeRateTable::new(rates, timestamp)
+    }
+
+    fn new(
+        rates: Vec<(CurrencyCode, CurrencyCode, Decimal)>,
+        timestamp: u64,
+    ) -> Result<ExchangeRateTable, ExchangeRateError> {
+        for (from, to, rate) in &rates {
+            if *rate <= Decimal::ZERO {
+                return Err(ExchangeRateError::InvalidRate {
+                    from: from.clone(),
+                    to: to.clone(),
+                    rate: *rate,
+                });
+            }
+        }
+
+        Ok(ExchangeRateTable { rates, timestamp })
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn check_age(&self, now: u64, max_age_secs: u64) -> Result<(), ExchangeRateError> {
+        if now.saturating_sub(self.timestamp) > max_age_secs {
+            return Err(ExchangeRateError::TooOld {
+                timestamp: self.timestamp,
+                max_age_secs,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Rate to multiply an amount in `from` with to get `to`, using direct, inverse or
+    /// cross rates over a shared base
+    pub fn get_rate(&self, from: &CurrencyCode, to: &CurrencyCode) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+
+        let direct = |from: &CurrencyCode, to: &CurrencyCode| {
+            self.rates.iter().find_map(|(f, t, rate)| {
+                if f == from && t == to {
+                    Some(*rate)
+                } else if f == to && t == from {
+                    Decimal::ONE.checked_div(rate).ok()
+                } else {
+                    None
+                }
+            })
+        };
+
+        direct(from, to).or_else(|| {
+            self.rates
+                .iter()
+                .find_map(|(base, _, _)| direct(from, base)?.checked_mul(&direct(base, to)?).ok())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(code: &str) -> CurrencyCode {
+        CurrencyCode::new(code).unwrap()
+    }
+
+    fn dec(s: &str) -> Option<Decimal> {
+        s.parse().ok()
+    }
+
+    #[test]
+    fn test_yaml_rates() {
+        let rates_yml = r#"
+        base: USD
+        timestamp: 2025-06-01T00:00:00Z
+        rates:
+          EUR: 0.8
+          GBP: 0.5
+        "#;
+
+        let table = ExchangeRateTable::from_yaml_str(rates_yml).unwrap();
+
+        assert_eq!(table.get_timestamp(), 1748736000);
+        assert_eq!(table.get_rate(&code("USD"), &code("EUR")), dec("0.8"));
+        assert_eq!(table.get_rate(&code("EUR"), &code("USD")), dec("1.25"));
+        assert_eq!(table.get_rate(&code("EUR"), &code("GBP")), dec("0.625"));
+        assert_eq!(table.get_rate(&code("EUR"), &code("CHF")), None);
+    }
+
+    #[test]
+    fn test_csv_rates_oldest_timestamp() {
+        let rates_csv = "base,quote,rate,timestamp\n\
+                         USD,EUR,0.92,2025-06-02T00:00:00Z\n\
+                         USD,GBP,0.79,2025-06-01T00:00:00Z\n";
+
+        let table = ExchangeRateTable::from_csv_str(rates_csv).unwrap();
+
+        assert_eq!(table.get_timestamp(), 1748736000);
+        assert_eq!(table.get_rate(&code("USD"), &code("GBP")), dec("0.79"));
+    }
+
+    #[test]
+    fn test_csv_rates_invalid_line() {
+        let rates_csv = "USD,EUR,0.92\n";
+
+        match ExchangeRateTable::from_csv_str(rates_csv) {
+            Err(ExchangeRateError::Csv { line: 1, .. }) => {}
+            _ => panic!("Expected Csv error"),
+        }
+    }
+
+    #[test]
+    fn test_negative_rate_rejected() {
+        let rates_csv = "USD,EUR,-0.92,1700000000\n";
+
+        assert!(ExchangeRateTable::from_csv_str(rates_csv).is_err());
+    }
+
+    #[test]
+    fn test_check_age() {
+        let table = ExchangeRateTable::from_csv_str("USD,EUR,0.92,1000\n").unwrap();
+
+        assert!(table.check_age(1500, 600).is_ok());
+        match table.check_age(2000, 600) {
+            Err(ExchangeRateError::TooOld { .. }) => {}
+            _ => panic!("Expected TooOld error"),
+        }
+    }
+}
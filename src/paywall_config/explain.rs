@@ -0,0 +1,135 @@
+use std::fmt;
+
+use crate::money::Money;
+
+/// Response header carrying the [compact trace](DecisionTrace::to_header_value) if
+/// `explain_header` is enabled in the [config](super::PaywallConfigV1)
+pub const EXPLAIN_HEADER: &str = "X-Rustwall-Explain";
+
+/// Why a [config](super::PaywallConfigV1) decided as it did for a request: the elements
+/// evaluated up to the deciding one, their conditions and where the price came from
+#[derive(Debug, Clone)]
+pub struct DecisionTrace {
+    pub url_path: String,
+    pub elements: Vec<ElementTrace>,
+    pub decision: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElementTrace {
+    /// Position of the element in `paths`
+    pub index: usize,
+    pub element_id: Option<String>,
+    pub matched: bool,
+    pub conditions: Vec<ConditionTrace>,
+    /// Set for the matching element
+    pub price: Option<PriceTrace>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConditionTrace {
+    /// The condition as configured, e.g. `HasRegexPath "^/premium/.*$"`
+    pub condition: String,
+    pub matched: bool,
+    /// What was found in the document, e.g. the matching node `<div class="premium">`
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PriceTrace {
+    /// The source that yielded the price, e.g. `FirstOf #2: FromMeta price`
+    pub source: String,
+    pub result: Result<Money, String>,
+}
+
+impl ElementTrace {
+    pub fn get_label(&self) -> String {
+        match &self.element_id {
+            Some(id) => format!("'{}'", id),
+            None => format!("#{}", self.index),
+        }
+    }
+}
+
+impl DecisionTrace {
+    /// Single line summary fit for a response header: non-ASCII characters are escaped
+    pub fn to_header_value(&self) -> String {
+        let mut parts: Vec<String> = self
+            .elements
+            .iter()
+            .map(|e| {
+                let failed: Vec<&str> = e
+                    .conditions
+                    .iter()
+                    .filter(|c| !c.matched)
+                    .map(|c| c.condition.split(' ').next().unwrap_or_default())
+                    .collect();
+
+                if e.matched {
+                    format!("{}=match", e.get_label())
+                } else {
+                    format!("{}=no({})", e.get_label(), failed.join(","))
+                }
+            })
+            .collect();
+
+        if let Some(price) = self.elements.iter().find_map(|e| e.price.as_ref()) {
+            parts.push(format!("price={}", price));
+        }
+        parts.push(format!("decision={}", self.decision));
+
+        parts
+            .join("; ")
+            .chars()
+            .map(|c| match c {
+                ' '..='~' => c.to_string(),
+                _ => c.escape_unicode().to_string(),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PriceTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.result {
+            Ok(price) => write!(f, "{} -> {}", self.source, price),
+            Err(e) => write!(f, "{} -> error: {}", self.source, e),
+        }
+    }
+}
+
+impl fmt::Display for DecisionTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "path: {}", self.url_path)?;
+
+        for element in &self.elements {
+            writeln!(
+                f,
+                "element {}: {}",
+                element.get_label(),
+                if element.matched {
+                    "matched"
+                } else {
+                    "not matched"
+                }
+            )?;
+            for condition in &element.conditions {
+                write!(
+                    f,
+                    "  [{}] {}",
+                    if condition.matched { "x" } else { " " },
+                    condition.condition
+                )?;
+                match &condition.detail {
+                    Some(detail) => writeln!(f, " ({})", detail)?,
+                    None => writeln!(f)?,
+                }
+            }
+            if let Some(price) = &element.price {
+                writeln!(f, "  price: {}", price)?;
+            }
+        }
+
+        write!(f, "decision: {}", self.decision)
+    }
+}
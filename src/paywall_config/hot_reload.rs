@@ -0,0 +1,372 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use super::{ConfigLoadError, ConfigLoader, PaywallConfigV1};
+use crate::utils::unix_now;
+
+type ConfigSetup = dyn Fn(&mut PaywallConfigV1) + Send + Sync;
+
+/// Modification time and size of a file, `None` if it cannot be read
+type Fingerprint = Option<(SystemTime, u64)>;
+
+/// [Config](PaywallConfigV1) that is reloaded when its files change, on request or on `SIGHUP`
+///
+/// A new config is swapped in atomically and only if it loads and validates; requests keep
+/// the [snapshot](HotReloadConfig::snapshot) they started with. The discount usage store and
+/// the event sink of the previous config are carried over, so usage counts survive reloads;
+/// they replace what the [setup](HotReloadConfig::with_setup) set on the reloaded config
+pub struct HotReloadConfig {
+    loader: ConfigLoader,
+    setup: Box<ConfigSetup>,
+    current: RwLock<Arc<PaywallConfigV1>>,
+    fingerprints: Mutex<Vec<(PathBuf, Fingerprint)>>,
+    status: Mutex<ReloadStatus>,
+}
+
+/// Outcome of the last reload, e.g. as body of a reload endpoint
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReloadStatus {
+    /// Number of configs loaded successfully, the initial one included
+    pub generation: u64,
+    /// Unix seconds of the last attempt
+    pub reloaded_at: u64,
+    /// Error of the last attempt; the previous config stays active
+    pub error: Option<String>,
+    /// Files of the active config
+    pub files: Vec<PathBuf>,
+    pub element_count: usize,
+}
+
+impl ReloadStatus {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl HotReloadConfig {
+    /// Load the initial config, which must be valid
+    pub fn new(loader: ConfigLoader) -> Result<HotReloadConfig, ConfigLoadError> {
+        HotReloadConfig::with_setup(loader, |_| {})
+    }
+
+    /// Like [new](HotReloadConfig::new), running `setup` on every loaded config, e.g. to
+    /// [set the event sink](PaywallConfigV1::set_event_sink); on reloads it runs before the
+    /// event sink and discount usage of the previous config are carried over
+    pub fn with_setup<F>(loader: ConfigLoader, setup: F) -> Result<HotReloadConfig, ConfigLoadError>
+    where
+        F: Fn(&mut PaywallConfigV1) + Send + Sync + 'static,
+    {
+        let resolved = loader.resolve()?;
+        let files = resolved.get_files().to_vec();
+        let mut config = resolved.into_config()?;
+        setup(&mut config);
+
+        Ok(HotReloadConfig {
+            status: Mutex::new(ReloadStatus {
+                generation: 1,
+                reloaded_at: unix_now(),
+                error: None,
+                files: files.clone(),
+                element_count: config.get_elements().len(),
+            }),
+            fingerprints: Mutex::new(fingerprint_all(files)),
+            current: RwLock::new(Arc::new(config)),
+            setup: Box::new(setup),
+            loader,
+        })
+    }
+
+    /// Config to evaluate a request with; stays valid across reloads
+    pub fn snapshot(&self) -> Arc<PaywallConfigV1> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn get_status(&self) -> ReloadStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Load the config again and swap it in if it is valid
+    pub fn reload(&self) -> ReloadStatus {
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        status.reloaded_at = unix_now();
+
+        let loaded = self.loader.resolve().and_then(|resolved| {
+            let files = resolved.get_files().to_vec();
+            resolved.into_config().map(|config| (config, files))
+        });
+
+        match loaded {
+            Ok((mut config, files)) => {
+                (self.setup)(&mut config);
+                let previous = self.snapshot();
+                config.set_event_sink(previous.get_event_sink().clone());
+                if let Some(discounts) = previous.get_discounts() {
+                    config.set_discount_usage_store(discounts.get_usage_store().clone());
+                }
+                status.generation += 1;
+                status.error = None;
+                status.element_count = config.get_elements().len();
+                status.files = files.clone();
+
+                *self.fingerprints.lock().unwrap_or_else(|e| e.into_inner()) =
+                    fingerprint_all(files);
+                *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+            }
+            Err(e) => {
+                status.error = Some(e.to_string());
+                // remember the broken state so it is not retried until the files change again
+                let mut fingerprints = self.fingerprints.lock().unwrap_or_else(|e| e.into_inner());
+                for (path, fingerprint) in fingerprints.iter_mut() {
+                    *fingerprint = fingerprint_of(path);
+                }
+            }
+        }
+
+        status.clone()
+    }
+
+    /// [Reload](HotReloadConfig::reload) if any file of the config was modified since the last
+    /// attempt
+    pub fn reload_if_changed(&self) -> Option<ReloadStatus> {
+        let changed = self
+            .fingerprints
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|(path, fingerprint)| fingerprint_of(path) != *fingerprint);
+
+        changed.then(|| self.reload())
+    }
+
+    /// Poll the config files every `interval` in a background thread, which ends when the
+    /// config is dropped; failed reloads are reported on stderr
+    pub fn spawn_watcher(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let weak: Weak<HotReloadConfig> = Arc::downgrade(self);
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                let Some(config) = weak.upgrade() else {
+                    return;
+                };
+                if let Some(ReloadStatus {
+                    error: Some(error), ..
+                }) = config.reload_if_changed()
+                {
+                    eprintln!("rustwall: config reload failed: {}", error);
+                }
+            }
+        })
+    }
+
+    /// Reload on every `SIGHUP` in a background thread
+    #[cfg(unix)]
+    pub fn reload_on_sighup(self: &Arc<Self>) -> std::io::Result<JoinHandle<()>> {
+        let mut signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGHUP])?;
+        let weak: Weak<HotReloadConfig> = Arc::downgrade(self);
+
+        Ok(thread::spawn(move || {
+            for _ in signals.forever() {
+                let Some(config) = weak.upgrade() else {
+                    return;
+                };
+                if let Some(error) = config.reload().error {
+                    eprintln!("rustwall: config reload failed: {}", error);
+                }
+            }
+        }))
+    }
+}
+
+fn fingerprint_of(path: &Path) -> Fingerprint {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn fingerprint_all(files: Vec<PathBuf>) -> Vec<(PathBuf, Fingerprint)> {
+    files
+        .into_iter()
+        .map(|path| {
+            let fingerprint = fingerprint_of(&path);
+            (path, fingerprint)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paywall_config::{DocumentAndPath, InMemoryPaywallEventSink, RequestContext};
+    use crate::pricing::DiscountError;
+
+    const CONFIG: &str = r#"
+include: [section.yml]
+paths:
+  - id: premium
+    paywall_conditions: [ !HasRegexPath "^/premium/.*$" ]
+    price_source: !Hard $1.25
+"#;
+
+    const SECTION: &str = r#"
+- id: research
+  paywall_conditions: [ !HasRegexPath "^/research/.*$" ]
+  price_source: !Hard $5.00
+"#;
+
+    fn config_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rustwall-hot-reload-{}-{}",
+            std::process::id(),
+            name
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("paywall.yml"), CONFIG).unwrap();
+        fs::write(dir.join("section.yml"), SECTION).unwrap();
+        dir
+    }
+
+    fn price_at(config: &PaywallConfigV1, path: &str) -> String {
+        let doc_and_path =
+            DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", path)
+                .unwrap();
+        config.get_price(&doc_and_path).to_string()
+    }
+
+    fn reloader(dir: &Path) -> HotReloadConfig {
+        HotReloadConfig::with_setup(ConfigLoader::new(&dir.join("paywall.yml")), |config| {
+            config.set_event_sink(Arc::new(InMemoryPaywallEventSink::default()))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reload_swaps_valid_config_and_keeps_snapshots() {
+        let dir = config_dir("swap");
+        let reloader = reloader(&dir);
+        let before = reloader.snapshot();
+
+        assert_eq!(reloader.reload_if_changed(), None);
+
+        fs::write(dir.join("section.yml"), SECTION.replace("$5.00", "$6.00")).unwrap();
+        let status = reloader.reload_if_changed().unwrap();
+
+        assert!(status.is_success());
+        assert_eq!(status.generation, 2);
+        assert_eq!(status.files.len(), 2);
+        assert_eq!(
+            price_at(&reloader.snapshot(), "/research/a"),
+            "element 'research': price $6.00"
+        );
+        assert_eq!(
+            price_at(&before, "/research/a"),
+            "element 'research': price $5.00"
+        );
+    }
+
+    #[test]
+    fn test_invalid_config_is_not_swapped_in() {
+        let dir = config_dir("invalid");
+        let reloader = reloader(&dir);
+
+        fs::write(
+            dir.join("paywall.yml"),
+            CONFIG.replace("!Hard $1.25", "!Hard 1.25"),
+        )
+        .unwrap();
+        let status = reloader.reload_if_changed().unwrap();
+
+        assert!(!status.is_success());
+        assert_eq!(status.generation, 1);
+        assert!(status.error.unwrap().contains("paywall.yml"));
+        assert_eq!(
+            price_at(&reloader.snapshot(), "/premium/a"),
+            "element 'premium': price $1.25"
+        );
+        assert_eq!(reloader.reload_if_changed(), None);
+        assert!(
+            serde_json::to_string(&reloader.get_status())
+                .unwrap()
+                .contains("\"generation\":1")
+        );
+    }
+
+    #[test]
+    fn test_reload_keeps_discount_usage() {
+        let dir = config_dir("discounts");
+        let with_discounts = format!(
+            "discounts:\n  codes:\n    - code: ONCE\n      discount: !Percent 50%\n      max_uses: 1\n{}",
+            CONFIG
+        );
+        fs::write(dir.join("paywall.yml"), &with_discounts).unwrap();
+        let reloader = HotReloadConfig::new(ConfigLoader::new(&dir.join("paywall.yml"))).unwrap();
+        let discounted = || {
+            let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+                "<html><body></body></html>",
+                "/premium/a",
+            )
+            .unwrap()
+            .with_request_context(RequestContext::new().with_query_param("coupon", "ONCE"));
+            reloader
+                .snapshot()
+                .get_price(&doc_and_path)
+                .discount
+                .unwrap()
+        };
+        // two readers got the discount offered before the reload
+        let (first, second) = (discounted(), discounted());
+
+        let before = reloader.snapshot();
+        before.get_discounts().unwrap().redeem(&first).unwrap();
+        assert!(reloader.reload().is_success());
+
+        let after = reloader.snapshot();
+        assert!(matches!(
+            after.get_discounts().unwrap().redeem(&second),
+            Err(DiscountError::UsageLimitReached(_))
+        ));
+        assert_eq!(discounted().source, None);
+        assert!(Arc::ptr_eq(after.get_event_sink(), before.get_event_sink()));
+    }
+
+    #[test]
+    fn test_reload_keeps_sink_set_by_setup() {
+        let dir = config_dir("sink");
+        let reloader = reloader(&dir);
+        let before = reloader.snapshot();
+
+        assert!(reloader.reload().is_success());
+
+        let after = reloader.snapshot();
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert!(Arc::ptr_eq(after.get_event_sink(), before.get_event_sink()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reload_on_sighup() {
+        let dir = config_dir("sighup");
+        let reloader = Arc::new(reloader(&dir));
+        let _handle = reloader.reload_on_sighup().unwrap();
+
+        signal_hook::low_level::raise(signal_hook::consts::SIGHUP).unwrap();
+        for _ in 0..100 {
+            if reloader.get_status().generation > 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(reloader.get_status().generation, 2);
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utils::JsonPath;
+
+/// Field of a JSON document hidden behind the paywall: blanked, or truncated to the first
+/// `truncate` characters of a string or items of an array
+/// # Examples
+/// ```yaml
+/// redact:
+///   - path: $.article.body
+///   - path: $.article.summary
+///     truncate: 200
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonRedaction {
+    path: JsonPath,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    truncate: Option<usize>,
+}
+
+impl JsonRedaction {
+    pub fn apply(&self, document: &mut Value) {
+        self.path
+            .for_each_mut(document, |value| match self.truncate {
+                Some(n) => truncate(value, n),
+                None => blank(value),
+            });
+    }
+}
+
+fn blank(value: &mut Value) {
+    *value = match value {
+        Value::String(_) => Value::String(String::new()),
+        Value::Array(_) => Value::Array(Vec::new()),
+        Value::Object(_) => Value::Object(serde_json::Map::new()),
+        _ => Value::Null,
+    };
+}
+
+fn truncate(value: &mut Value, n: usize) {
+    match value {
+        Value::String(s) => {
+            if let Some((i, _)) = s.char_indices().nth(n) {
+                s.truncate(i);
+            }
+        }
+        Value::Array(items) => items.truncate(n),
+        _ => blank(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_blank_and_truncate() {
+        let config_yml = r#"
+        - path: $.article.body
+        - path: $.article.summary
+          truncate: 3
+        - path: $.article.paragraphs
+          truncate: 1
+        - path: $.article.premium
+        "#;
+
+        let redactions: Vec<JsonRedaction> = serde_yml::from_str(config_yml).unwrap();
+        let mut document = json!({
+            "article": {
+                "body": "secret",
+                "summary": "Zürich news",
+                "paragraphs": ["a", "b"],
+                "premium": true
+            }
+        });
+
+        redactions.iter().for_each(|r| r.apply(&mut document));
+
+        assert_eq!(
+            document,
+            json!({
+                "article": {
+                    "body": "",
+                    "summary": "Zür",
+                    "paragraphs": ["a"],
+                    "premium": null
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use serde::{Deserialize, Serialize};
+use serde_yml::{Mapping, Value};
+use thiserror::Error;
+
+/// Markdown document split into YAML front matter and body
+/// # Examples
+/// ```markdown
+/// ---
+/// title: Research notes
+/// paywall: true
+/// ---
+/// Intro available to everyone
+///
+/// ## Details
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MarkdownDoc {
+    front_matter: Mapping,
+    body: String,
+}
+
+#[derive(Debug, Error)]
+pub enum MarkdownDocError {
+    #[error("Front matter is not closed by a '---' line")]
+    UnclosedFrontMatter,
+    #[error("Cannot parse front matter: {0}")]
+    InvalidFrontMatter(#[from] serde_yml::Error),
+    #[error("Front matter must be a mapping of keys to values")]
+    FrontMatterNotAMapping,
+}
+
+impl MarkdownDoc {
+    pub fn parse(markdown: &str) -> Result<MarkdownDoc, MarkdownDocError> {
+        let markdown = markdown.strip_prefix('\u{feff}').unwrap_or(markdown);
+
+        let mut lines = markdown.split_inclusive('\n');
+        let starts_with_front_matter = lines.next().is_some_and(|first| first.trim_end() == "---");
+
+        if !starts_with_front_matter {
+            return Ok(MarkdownDoc {
+                front_matter: Mapping::new(),
+                body: markdown.to_string(),
+            });
+        }
+
+        let mut offset = markdown.find('\n').map_or(markdown.len(), |i| i + 1);
+        let front_matter_start = offset;
+
+        for line in lines {
+            let trimmed = line.trim_end();
+
+            if trimmed == "---" || trimmed == "..." {
+                let front_matter_yml = &markdown[front_matter_start..offset];
+                let body = &markdown[offset + line.len()..];
+
+                let front_matter = match serde_yml::from_str::<Value>(front_matter_yml)? {
+                    Value::Mapping(mapping) => mapping,
+                    Value::Null => Mapping::new(),
+                    _ => return Err(MarkdownDocError::FrontMatterNotAMapping),
+                };
+
+                return Ok(MarkdownDoc {
+                    front_matter,
+                    body: body.to_string(),
+                });
+            }
+
+            offset += line.len();
+        }
+
+        Err(MarkdownDocError::UnclosedFrontMatter)
+    }
+
+    pub fn get_front_matter(&self) -> &Mapping {
+        &self.front_matter
+    }
+
+    pub fn get_front_matter_value(&self, key: &str) -> Option<&Value> {
+        self.front_matter.get(key)
+    }
+
+    /// Front matter value as text if it is a string, number or boolean
+    pub fn get_front_matter_str(&self, key: &str) -> Option<String> {
+        match self.front_matter.get(key)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn get_body(&self) -> &str {
+        &self.body
+    }
+
+    /// Byte offsets and levels of the ATX headings (`# ...` to `###### ...`) in the body,
+    /// ignoring fenced code blocks
+    pub fn get_headings(&self) -> Vec<(usize, usize)> {
+        let mut headings = Vec::new();
+        let mut fence: Option<&str> = None;
+        let mut offset = 0;
+
+        for line in self.body.split_inclusive('\n') {
+            let trimmed = line.trim_start();
+
+            if let Some(open) = fence {
+                if trimmed.starts_with(open) {
+                    fence = None;
+                }
+            } else if trimmed.starts_with("```") {
+                fence = Some("```");
+            } else if trimmed.starts_with("~~~") {
+                fence = Some("~~~");
+            } else {
+                let level = trimmed.chars().take_while(|c| *c == '#').count();
+                let rest = &trimmed[level..];
+
+                if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t'])) {
+                    headings.push((offset, level));
+                }
+            }
+
+            offset += line.len();
+        }
+
+        headings
+    }
+
+    /// Body up to the heading after the first `sections` headings of at most `max_level`,
+    /// i.e. the intro plus `sections` sections; the whole body if it has no more sections
+    pub fn teaser(&self, sections: usize, max_level: usize) -> &str {
+        let cut = self
+            .get_headings()
+            .into_iter()
+            .filter(|(_, level)| *level <= max_level)
+            .nth(sections)
+            .map_or(self.body.len(), |(offset, _)| offset);
+
+        &self.body[..cut]
+    }
+}
+
+/// Free part of a Markdown document shown before the paywall
+/// # Examples
+/// ```yaml
+/// teaser: { sections: 1, max_level: 2 }
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkdownTeaser {
+    #[serde(default)]
+    sections: usize,
+    #[serde(default = "default_max_level")]
+    max_level: usize,
+}
+
+fn default_max_level() -> usize {
+    2
+}
+
+impl MarkdownTeaser {
+    pub fn cut<'a>(&self, doc: &'a MarkdownDoc) -> &'a str {
+        doc.teaser(self.sections, self.max_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = "---\ntitle: Notes\npaywall: true\n---\nIntro\n\n## First\nA\n```\n# not a heading\n```\n### Sub\n## Second\nB\n";
+
+    #[test]
+    fn test_parse_front_matter_and_body() {
+        let doc = MarkdownDoc::parse(ARTICLE).unwrap();
+
+        assert_eq!(
+            doc.get_front_matter_value("paywall"),
+            Some(&Value::Bool(true))
+        );
+        assert!(doc.get_body().starts_with("Intro"));
+    }
+
+    #[test]
+    fn test_front_matter_str() {
+        let doc = MarkdownDoc::parse(
+            "---\nprice: \"$2.00\"\npaywall: true\nwords: 1200\ntags: [a]\n---\n",
+        )
+        .unwrap();
+
+        assert_eq!(doc.get_front_matter_str("price").as_deref(), Some("$2.00"));
+        assert_eq!(doc.get_front_matter_str("paywall").as_deref(), Some("true"));
+        assert_eq!(doc.get_front_matter_str("words").as_deref(), Some("1200"));
+        assert_eq!(doc.get_front_matter_str("tags"), None);
+        assert_eq!(doc.get_front_matter_str("missing"), None);
+    }
+
+    #[test]
+    fn test_parse_without_front_matter() {
+        let doc = MarkdownDoc::parse("# Title\nText").unwrap();
+
+        assert!(doc.get_front_matter().is_empty());
+        assert_eq!(doc.get_body(), "# Title\nText");
+    }
+
+    #[test]
+    fn test_parse_unclosed_front_matter() {
+        assert!(matches!(
+            MarkdownDoc::parse("---\ntitle: Notes\n"),
+            Err(MarkdownDocError::UnclosedFrontMatter)
+        ));
+    }
+
+    #[test]
+    fn test_parse_front_matter_not_a_mapping() {
+        assert!(matches!(
+            MarkdownDoc::parse("---\n- a\n---\n"),
+            Err(MarkdownDocError::FrontMatterNotAMapping)
+        ));
+    }
+
+    #[test]
+    fn test_headings_skip_code_blocks() {
+        let doc = MarkdownDoc::parse(ARTICLE).unwrap();
+        let levels: Vec<usize> = doc.get_headings().into_iter().map(|(_, l)| l).collect();
+
+        assert_eq!(levels, vec![2, 3, 2]);
+    }
+
+    #[test]
+    fn test_teaser() {
+        let doc = MarkdownDoc::parse(ARTICLE).unwrap();
+
+        assert_eq!(doc.teaser(0, 2), "Intro\n\n");
+        assert!(doc.teaser(1, 2).ends_with("### Sub\n"));
+        assert_eq!(doc.teaser(5, 2), doc.get_body());
+    }
+}
@@ -1,33 +1,654 @@
-pub mod currency_wrapper;
+pub mod binary_response;
+pub mod builder;
+pub mod config_format;
+pub mod config_loader;
+pub mod config_schema;
+pub mod config_test;
+pub mod config_version;
+pub mod currency_conversion;
+pub mod currency_selection;
+pub mod currency_table;
+pub mod custom_types;
+pub mod error_policy;
+pub mod exchange_rates;
+pub mod explain;
+pub mod hot_reload;
+pub mod json_redaction;
+pub mod markdown_doc;
 pub mod paywall_condition;
+pub mod price_expression;
+pub mod price_fallback;
+pub mod request_context;
 pub mod requestable_doc;
 pub mod url_path;
+pub mod validation;
 
-pub use currency_wrapper::CurrencyWrapper;
+pub use binary_response::{BinaryResponse, ByteRange, ByteRangeError};
+pub use builder::{PaywallConfigBuilder, PaywallElementBuilder};
+pub use config_format::{ConfigFormat, ConfigFormatError};
+pub use config_loader::{ConfigLoadError, ConfigLoader, ResolvedConfig};
+pub use config_schema::{config_json_schema, yaml_custom_tags};
+pub use config_test::{
+    ConfigTestCase, ConfigTestError, ConfigTestReport, ConfigTestSuite, TestCaseResult,
+    TestExpectation, TestMismatch,
+};
+pub use config_version::{
+    LATEST_CONFIG_VERSION, Migration, PaywallConfig, PaywallConfigV2, migrate_yaml,
+};
+pub use currency_conversion::{CurrencyConversion, Rounding};
+pub use currency_selection::CurrencySelection;
+pub use currency_table::CurrencyTable;
+pub use custom_types::{
+    CustomCondition, CustomPriceSource, CustomType, CustomTypeError, register_condition,
+    register_price_source,
+};
+pub use error_policy::{
+    ErrorPolicy, InMemoryPaywallEventSink, PaywallErrorEvent, PaywallEventSink,
+    StderrPaywallEventSink,
+};
+pub use exchange_rates::{ExchangeRateError, ExchangeRateTable};
+pub use explain::{ConditionTrace, DecisionTrace, EXPLAIN_HEADER, ElementTrace, PriceTrace};
+pub use hot_reload::{HotReloadConfig, ReloadStatus};
+pub use json_redaction::JsonRedaction;
+pub use markdown_doc::{MarkdownDoc, MarkdownDocError, MarkdownTeaser};
 pub use paywall_condition::PaywallCondition;
-pub use requestable_doc::{DocumentAndPath, RequestableDoc};
+pub use price_expression::{ExpressionVariable, PriceExpression};
+pub use price_fallback::PriceFallback;
+pub use request_context::RequestContext;
+pub use requestable_doc::{DocumentAndPath, DocumentAndPathError, RequestableDoc};
 pub use url_path::{UrlPath, UrlPathError};
+pub use validation::{ConfigIssue, validate, validate_with_format};
 
-use crate::utils::{HtmlAttributeSelector, HtmlAttributeSelectorError};
+use crate::utils::{
+    CssSelectorError, HtmlAttributeSelector, HtmlAttributeSelectorError, JsonLdError, JsonPath,
+    JsonPathError, block_on, find_json_ld_offer, unix_now,
+};
 
+use crate::money::{CurrencyCode, Money, MoneyError};
+use crate::pricing::{
+    DiscountConfig, DiscountError, DiscountUsageStore, DiscountedPrice, ExpressionError,
+    TaxBreakdown, TaxConfig, TaxError,
+};
+use serde::de::IgnoredAny;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
 use std::fmt;
-use currency::Currency;
-use html_editor::operation::Selector;
-use serde::Deserialize;
+use std::sync::Arc;
+use thiserror::Error;
 
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PaywallConfigV1 {
     paths: Vec<PaywallElement>,
+    #[serde(default)]
+    currency_selection: CurrencySelection,
+    #[serde(default)]
+    discounts: Option<DiscountConfig>,
+    #[serde(default)]
+    tax: Option<TaxConfig>,
+    /// Add the [explain header](EXPLAIN_HEADER) to responses, for debugging only
+    #[serde(default)]
+    explain_header: bool,
+    #[serde(skip, default = "default_event_sink")]
+    event_sink: Arc<dyn PaywallEventSink>,
+    /// Read by [PaywallConfig] and the [ConfigLoader], accepted here so that they are not
+    /// unknown keys
+    #[serde(default, rename = "version")]
+    _version: IgnoredAny,
+    #[serde(default, rename = "include")]
+    _include: IgnoredAny,
 }
 
-#[derive(Deserialize)]
+fn default_event_sink() -> Arc<dyn PaywallEventSink> {
+    Arc::new(StderrPaywallEventSink)
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Compares the settings only, not the event sink
+impl PartialEq for PaywallConfigV1 {
+    fn eq(&self, other: &Self) -> bool {
+        self.paths == other.paths
+            && self.currency_selection == other.currency_selection
+            && self.discounts == other.discounts
+            && self.tax == other.tax
+            && self.explain_header == other.explain_header
+    }
+}
+
+impl fmt::Debug for PaywallConfigV1 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PaywallConfigV1")
+            .field("paths", &self.paths)
+            .field("currency_selection", &self.currency_selection)
+            .field("discounts", &self.discounts)
+            .field("tax", &self.tax)
+            .field("explain_header", &self.explain_header)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Serializes in the [latest schema version](LATEST_CONFIG_VERSION), leaving out unset
+/// settings
+impl Serialize for PaywallConfigV1 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("version", &LATEST_CONFIG_VERSION)?;
+        if !is_default(&self.currency_selection) {
+            map.serialize_entry("currency_selection", &self.currency_selection)?;
+        }
+        if let Some(discounts) = &self.discounts {
+            map.serialize_entry("discounts", discounts)?;
+        }
+        if let Some(tax) = &self.tax {
+            map.serialize_entry("tax", tax)?;
+        }
+        if self.explain_header {
+            map.serialize_entry("explain_header", &true)?;
+        }
+        map.serialize_entry("paths", &self.paths)?;
+        map.end()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PaywallConfigError {
+    #[error("Cannot parse paywall config: {0}")]
+    Parse(#[from] serde_yml::Error),
+    #[error("Cannot parse paywall config: {0}")]
+    Format(#[from] ConfigFormatError),
+    #[error("Invalid paywall element id '{0}': use ASCII letters, digits, '-', '_' or '.'")]
+    InvalidElementId(String),
+    #[error("Duplicate paywall element id '{0}'")]
+    DuplicateElementId(String),
+    #[error("Paywall element without a price source")]
+    MissingPriceSource,
+    #[error("Unsupported paywall config version {0}, the latest is {LATEST_CONFIG_VERSION}")]
+    UnsupportedVersion(String),
+    #[error("Expected a version {expected} paywall config, found version {found}")]
+    UnexpectedVersion { expected: u64, found: u64 },
+    #[error("Invalid paywall config layout: {0}")]
+    Layout(String),
+}
+
+impl PaywallConfigV1 {
+    /// Parse a YAML config and validate the ids of all [paywall elements](PaywallElement)
+    pub fn from_yaml_str(config_yml: &str) -> Result<PaywallConfigV1, PaywallConfigError> {
+        let config: PaywallConfigV1 = serde_yml::from_str(config_yml)?;
+        config.validate_ids()?;
+        Ok(config)
+    }
+
+    /// The config as YAML in the [latest schema version](LATEST_CONFIG_VERSION); comments and
+    /// the layout of the original file are not kept
+    pub fn to_yaml_string(&self) -> Result<String, PaywallConfigError> {
+        Ok(serde_yml::to_string(self)?)
+    }
+
+    fn validate_ids(&self) -> Result<(), PaywallConfigError> {
+        let mut seen = HashSet::new();
+
+        for id in self.paths.iter().filter_map(|e| e.get_id()) {
+            if !Self::is_valid_id(id) {
+                return Err(PaywallConfigError::InvalidElementId(id.to_string()));
+            }
+            if !seen.insert(id) {
+                return Err(PaywallConfigError::DuplicateElementId(id.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn is_valid_id(id: &str) -> bool {
+        !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    }
+
+    pub fn get_currency_selection(&self) -> &CurrencySelection {
+        &self.currency_selection
+    }
+
+    /// `doc_and_path` with the [currency selection](CurrencySelection) of the config applied to
+    /// its request context, as prices are read when deciding
+    pub fn with_currency_selection(&self, doc_and_path: &DocumentAndPath) -> DocumentAndPath {
+        let ctx = self
+            .currency_selection
+            .apply(doc_and_path.get_request_context().clone());
+        doc_and_path.clone().with_request_context(ctx)
+    }
+
+    pub fn get_discounts(&self) -> Option<&DiscountConfig> {
+        self.discounts.as_ref()
+    }
+
+    /// Share discount code usage counts, e.g. between server instances
+    pub fn set_discount_usage_store(&mut self, usage_store: Arc<dyn DiscountUsageStore>) {
+        if let Some(discounts) = &mut self.discounts {
+            discounts.set_usage_store(usage_store);
+        }
+    }
+
+    /// Receive the events emitted when an [error policy](ErrorPolicy) kicks in; they are
+    /// written to stderr by default
+    pub fn set_event_sink(&mut self, event_sink: Arc<dyn PaywallEventSink>) {
+        self.event_sink = event_sink;
+    }
+
+    pub fn get_event_sink(&self) -> &Arc<dyn PaywallEventSink> {
+        &self.event_sink
+    }
+
+    pub fn get_tax(&self) -> Option<&TaxConfig> {
+        self.tax.as_ref()
+    }
+
+    pub fn get_elements(&self) -> &[PaywallElement] {
+        &self.paths
+    }
+
+    pub fn get_element_by_id(&self, id: &str) -> Option<&PaywallElement> {
+        self.paths.iter().find(|e| e.get_id() == Some(id))
+    }
+
+    /// Evaluate all [paywall elements](PaywallElement) in order; the first one whose conditions
+    /// are met decides the net price, which is then reduced by [discounts](DiscountConfig) and
+    /// taxed if a [tax config](TaxConfig) is set. Prices are read with the
+    /// [currency selection](CurrencySelection) of the config applied to the request
+    ///
+    /// If the price cannot be determined, the [error policy](ErrorPolicy) of the element
+    /// decides; a fixed fallback price is still discounted and taxed unless discount or tax
+    /// computation failed themselves
+    ///
+    /// [Custom types](CustomCondition) are run with [block_on]; use
+    /// [get_price_async](Self::get_price_async) from async code
+    pub fn get_price(&self, doc_and_path: &DocumentAndPath) -> PaywallDecision {
+        block_on(self.get_price_async(doc_and_path))
+    }
+
+    /// [get_price](Self::get_price) awaiting [custom types](CustomCondition) on the caller's
+    /// executor
+    pub async fn get_price_async(&self, doc_and_path: &DocumentAndPath) -> PaywallDecision {
+        self.decide(doc_and_path, None).await
+    }
+
+    /// [get_price](Self::get_price) recording every element evaluated up to the deciding one
+    /// in `trace`; conditions and price sources are evaluated once either way
+    async fn decide(
+        &self,
+        doc_and_path: &DocumentAndPath,
+        mut trace: Option<&mut Vec<ElementTrace>>,
+    ) -> PaywallDecision {
+        let doc_and_path = &self.with_currency_selection(doc_and_path);
+        let mut matching = None;
+        for (index, element) in self.paths.iter().enumerate() {
+            let matched = match trace.as_deref_mut() {
+                Some(elements) => {
+                    let element_trace = element.trace_conditions(index, doc_and_path).await;
+                    let matched = element_trace.matched;
+                    elements.push(element_trace);
+                    matched
+                }
+                None => element.is_paywalled_async(doc_and_path).await,
+            };
+            if matched {
+                matching = Some((index, element));
+                break;
+            }
+        }
+        let Some((index, element)) = matching else {
+            return PaywallDecision {
+                element_id: None,
+                element_index: None,
+                price_option: PaywallPriceOption::ConditionsNotMet,
+                discount: None,
+                tax: None,
+                error_event: None,
+            };
+        };
+
+        let price = match trace.and_then(|elements| elements.last_mut()) {
+            Some(element_trace) => {
+                let (price, source) = element
+                    .price_source
+                    .get_price_with_trace_async(doc_and_path)
+                    .await;
+                element_trace.price = Some(PriceTrace {
+                    source,
+                    result: price.as_ref().map(Money::clone).map_err(|e| e.to_string()),
+                });
+                price
+            }
+            None => element.price_source.get_price_async(doc_and_path).await,
+        };
+
+        let mut decision = PaywallDecision {
+            element_id: element.get_id().map(str::to_string),
+            element_index: Some(index),
+            price_option: match price {
+                Ok(p) => PaywallPriceOption::Price(p),
+                Err(e) => PaywallPriceOption::PriceParsingError(PaywallPriceError::Extract(e)),
+            },
+            discount: None,
+            tax: None,
+            error_event: None,
+        };
+        self.settle(
+            element,
+            &mut decision,
+            doc_and_path.get_url_path_as_str(),
+            doc_and_path.get_request_context(),
+        );
+
+        decision
+    }
+
+    /// Decide for a request whose document could not be parsed: the first element whose
+    /// path conditions match, ignoring conditions on the document, applies its
+    /// [error policy](ErrorPolicy)
+    pub fn get_price_for_document_error(
+        &self,
+        url_path: &UrlPath,
+        request_context: &RequestContext,
+        error: &DocumentAndPathError,
+    ) -> PaywallDecision {
+        let Some((index, element)) = self
+            .paths
+            .iter()
+            .enumerate()
+            .find(|(_, e)| e.matches_path(url_path.get_path()))
+        else {
+            return PaywallDecision {
+                element_id: None,
+                element_index: None,
+                price_option: PaywallPriceOption::ConditionsNotMet,
+                discount: None,
+                tax: None,
+                error_event: None,
+            };
+        };
+
+        let mut decision = PaywallDecision {
+            element_id: element.get_id().map(str::to_string),
+            element_index: Some(index),
+            price_option: PaywallPriceOption::PriceParsingError(PaywallPriceError::Document(
+                error.to_string(),
+            )),
+            discount: None,
+            tax: None,
+            error_event: None,
+        };
+        let request_context = self.currency_selection.apply(request_context.clone());
+        self.settle(
+            element,
+            &mut decision,
+            url_path.get_path(),
+            &request_context,
+        );
+
+        decision
+    }
+
+    /// Trace of the evaluation: every element up to the deciding one with the result of each
+    /// condition, and where the price came from
+    pub fn explain(&self, doc_and_path: &DocumentAndPath) -> DecisionTrace {
+        self.get_price_with_trace(doc_and_path).1
+    }
+
+    /// [Decide](Self::get_price) and [explain](Self::explain) the decision in a single
+    /// evaluation
+    pub fn get_price_with_trace(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> (PaywallDecision, DecisionTrace) {
+        block_on(self.get_price_with_trace_async(doc_and_path))
+    }
+
+    /// [get_price_with_trace](Self::get_price_with_trace) awaiting
+    /// [custom types](CustomCondition)
+    pub async fn get_price_with_trace_async(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> (PaywallDecision, DecisionTrace) {
+        let mut elements = Vec::new();
+        let decision = self.decide(doc_and_path, Some(&mut elements)).await;
+        let trace = DecisionTrace {
+            url_path: doc_and_path.get_url_path_as_str().to_string(),
+            elements,
+            decision: decision.to_string(),
+        };
+
+        (decision, trace)
+    }
+
+    /// [Decide](Self::get_price), with the [explain header](EXPLAIN_HEADER) name and value if
+    /// enabled by `explain_header`
+    pub fn get_price_with_explain_header(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> (PaywallDecision, Option<(&'static str, String)>) {
+        block_on(self.get_price_with_explain_header_async(doc_and_path))
+    }
+
+    /// [get_price_with_explain_header](Self::get_price_with_explain_header) awaiting
+    /// [custom types](CustomCondition)
+    pub async fn get_price_with_explain_header_async(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> (PaywallDecision, Option<(&'static str, String)>) {
+        if !self.explain_header {
+            return (self.get_price_async(doc_and_path).await, None);
+        }
+
+        let (decision, trace) = self.get_price_with_trace_async(doc_and_path).await;
+        (decision, Some((EXPLAIN_HEADER, trace.to_header_value())))
+    }
+
+    /// Let the error policy replace a failed price, then apply discounts and tax to the price,
+    /// also to a fixed fallback price; a discount or tax that fails keeps the content locked
+    /// whatever the policy. Each failure emits exactly one event
+    fn settle(
+        &self,
+        element: &PaywallElement,
+        decision: &mut PaywallDecision,
+        url_path: &str,
+        ctx: &RequestContext,
+    ) {
+        if let Some(error) = decision.price_option.get_error().map(|e| e.get_message()) {
+            let policy = element.get_error_policy();
+            self.emit_error_event(element, decision, url_path, policy, error);
+
+            match policy {
+                ErrorPolicy::Allow => decision.price_option = PaywallPriceOption::ConditionsNotMet,
+                ErrorPolicy::Deny => {}
+                ErrorPolicy::FixedPrice(table) => {
+                    decision.price_option = PaywallPriceOption::Price(
+                        table.select(ctx.get_preferred_currencies()).clone(),
+                    )
+                }
+            }
+        }
+
+        if let Err(e) = self.apply_discount_and_tax(decision, url_path, ctx) {
+            self.emit_error_event(
+                element,
+                decision,
+                url_path,
+                &ErrorPolicy::Deny,
+                e.to_string(),
+            );
+            decision.price_option = PaywallPriceOption::PriceParsingError(e);
+            decision.discount = None;
+            decision.tax = None;
+        }
+    }
+
+    fn emit_error_event(
+        &self,
+        element: &PaywallElement,
+        decision: &mut PaywallDecision,
+        url_path: &str,
+        policy: &ErrorPolicy,
+        error: String,
+    ) {
+        let event = PaywallErrorEvent {
+            element_id: element.get_id().map(str::to_string),
+            url_path: url_path.to_string(),
+            policy: policy.get_name(),
+            error,
+        };
+        self.event_sink.emit(&event);
+        decision.error_event = Some(event);
+    }
+
+    fn apply_discount_and_tax(
+        &self,
+        decision: &mut PaywallDecision,
+        url_path: &str,
+        ctx: &RequestContext,
+    ) -> Result<(), PaywallPriceError> {
+        let PaywallPriceOption::Price(price) = &decision.price_option else {
+            return Ok(());
+        };
+        let mut net = price.clone();
+
+        if let Some(discounts) = &self.discounts {
+            let discounted =
+                discounts.apply(&net, decision.element_id.as_deref(), url_path, ctx)?;
+            net = discounted.final_price.clone();
+            decision.discount = Some(discounted);
+        }
+
+        if let Some(tax) = &self.tax {
+            decision.tax = Some(tax.compute(&net, ctx)?);
+        }
+
+        decision.price_option = PaywallPriceOption::Price(net);
+        Ok(())
+    }
+}
+
+/// Outcome of evaluating a [config](PaywallConfigV1), referring to the deciding
+/// [element](PaywallElement) by its id and position; the price is net of discounts and before
+/// tax
+pub struct PaywallDecision {
+    pub element_id: Option<String>,
+    pub element_index: Option<usize>,
+    pub price_option: PaywallPriceOption,
+    pub discount: Option<DiscountedPrice>,
+    pub tax: Option<TaxBreakdown>,
+    /// Set if the [error policy](ErrorPolicy) of the element decided the outcome, or a discount
+    /// or tax failure kept the content locked
+    pub error_event: Option<PaywallErrorEvent>,
+}
+
+impl PaywallDecision {
+    /// The deciding element like [ElementTrace::get_label]: its quoted id, or `#` and its index
+    pub fn get_label(&self) -> String {
+        match &self.element_id {
+            Some(id) => format!("'{}'", id),
+            None => format!("#{}", self.element_index.unwrap_or_default()),
+        }
+    }
+}
+
+impl fmt::Display for PaywallDecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = self.get_label();
+
+        match &self.price_option {
+            PaywallPriceOption::Price(p) => write!(f, "element {}: price {}", label, p),
+            PaywallPriceOption::ConditionsNotMet if self.error_event.is_some() => {
+                write!(f, "element {}: free after error", label)
+            }
+            PaywallPriceOption::ConditionsNotMet => write!(f, "no paywall element matched"),
+            PaywallPriceOption::PriceParsingError(e) => write!(
+                f,
+                "element {}: price parsing error: {}",
+                label,
+                e.get_message()
+            ),
+        }
+    }
+}
+
+/// Where the price of a [paywall element](PaywallElement) comes from
+///
+/// Besides the built-in variants, price sources of library users are read under the tags they
+/// are [registered](register_price_source) with
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(remote = "Self")]
 pub enum PriceSource {
-    Hard(CurrencyWrapper),
+    Hard(CurrencyTable),
     FromHtmlAttribute(HtmlAttributeSelector),
+    Converted(CurrencyConversion),
+    /// Price from [document metadata](RequestableDoc::get_meta), e.g. `!FromMeta { key: price }`
+    FromMeta {
+        key: String,
+    },
+    /// Price from the first value at a [JSONPath](JsonPath) of a JSON document; plain numbers
+    /// are read in the [document currency](CurrencySelection)
+    FromJsonPath(JsonPath),
+    /// Price of the `offers` of the embedded JSON-LD object with this `@type`, e.g.
+    /// `!FromJsonLd Product`
+    FromJsonLd(String),
+    /// First price any of several sources yields, see [PriceFallback]
+    FirstOf(PriceFallback),
+    /// Price computed by a type checked expression, see [PriceExpression]
+    Expression(PriceExpression),
+    /// [Registered](register_price_source) price source of a library user
+    #[serde(skip)]
+    Custom(CustomType<dyn CustomPriceSource>),
+}
+
+/// Registered tags are looked up before the built-in variants are deserialized
+impl<'de> Deserialize<'de> for PriceSource {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        custom_types::deserialize_tagged(deserializer)
+    }
+}
+
+impl Serialize for PriceSource {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            PriceSource::Custom(custom) => custom.serialize(serializer),
+            _ => PriceSource::serialize(self, serializer),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum PriceSourceExtractError {
     HtmlAttributeSelectorError(HtmlAttributeSelectorError),
+    UnknownCurrency(String),
+    UnsupportedDocument(&'static str),
+    MetaNotFound(String),
+    /// Raw price and why it cannot be parsed
+    InvalidPrice(String, MoneyError),
+    JsonPathNotFound(String),
+    JsonLdError(JsonLdError),
+    AllSourcesFailed(Vec<PriceSourceExtractError>),
+    ExpressionError(ExpressionError),
+    /// Price of a [FirstOf](PriceFallback) source in a currency its bounds do not list
+    UnboundedCurrency(Money),
+    /// Rates of a [conversion](CurrencyConversion) expired since the config was loaded
+    ExchangeRateError(ExchangeRateError),
+    /// Failure of a [custom price source](CustomPriceSource)
+    CustomSourceError(String),
 }
 
 impl fmt::Display for PriceSourceExtractError {
@@ -36,96 +657,570 @@ impl fmt::Display for PriceSourceExtractError {
             PriceSourceExtractError::HtmlAttributeSelectorError(err) => {
                 write!(f, "HtmlAttributeSelectorError: {}", err)
             }
+            PriceSourceExtractError::UnknownCurrency(price) => {
+                write!(f, "Cannot infer currency code of price '{}'", price)
+            }
+            PriceSourceExtractError::UnsupportedDocument(doc_type) => {
+                write!(f, "Price source cannot read {} documents", doc_type)
+            }
+            PriceSourceExtractError::MetaNotFound(key) => {
+                write!(f, "Document has no '{}' metadata", key)
+            }
+            PriceSourceExtractError::InvalidPrice(price, err) => {
+                write!(f, "Cannot parse price '{}': {}", price, err)
+            }
+            PriceSourceExtractError::JsonPathNotFound(path) => {
+                write!(f, "No price value at JSONPath '{}'", path)
+            }
+            PriceSourceExtractError::JsonLdError(err) => write!(f, "JsonLdError: {}", err),
+            PriceSourceExtractError::ExpressionError(err) => write!(f, "ExpressionError: {}", err),
+            PriceSourceExtractError::ExchangeRateError(err) => {
+                write!(f, "ExchangeRateError: {}", err)
+            }
+            PriceSourceExtractError::UnboundedCurrency(price) => {
+                write!(f, "No min or max bound for the currency of price {}", price)
+            }
+            PriceSourceExtractError::CustomSourceError(message) => {
+                write!(f, "Custom price source failed: {}", message)
+            }
+            PriceSourceExtractError::AllSourcesFailed(errors) => {
+                write!(f, "All price sources failed")?;
+                for (i, err) in errors.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PriceSourceExtractError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PriceSourceExtractError::HtmlAttributeSelectorError(err) => Some(err),
+            PriceSourceExtractError::JsonLdError(err) => Some(err),
+            PriceSourceExtractError::ExpressionError(err) => Some(err),
+            PriceSourceExtractError::ExchangeRateError(err) => Some(err),
+            PriceSourceExtractError::InvalidPrice(_, err) => Some(err),
+            PriceSourceExtractError::AllSourcesFailed(errors) => errors
+                .last()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
+            PriceSourceExtractError::UnknownCurrency(_)
+            | PriceSourceExtractError::UnboundedCurrency(_)
+            | PriceSourceExtractError::UnsupportedDocument(_)
+            | PriceSourceExtractError::MetaNotFound(_)
+            | PriceSourceExtractError::JsonPathNotFound(_)
+            | PriceSourceExtractError::CustomSourceError(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriceSource::Hard(table) => write!(f, "Hard {}", table),
+            PriceSource::FromHtmlAttribute(selector) => write!(f, "FromHtmlAttribute {}", selector),
+            PriceSource::Converted(conversion) => {
+                write!(f, "Converted from {}", conversion.get_source())
+            }
+            PriceSource::FromMeta { key } => write!(f, "FromMeta {}", key),
+            PriceSource::FromJsonPath(path) => write!(f, "FromJsonPath {}", path),
+            PriceSource::FromJsonLd(ld_type) => write!(f, "FromJsonLd {}", ld_type),
+            PriceSource::FirstOf(fallback) => {
+                let sources: Vec<String> = fallback
+                    .get_sources()
+                    .iter()
+                    .map(PriceSource::to_string)
+                    .collect();
+                write!(f, "FirstOf [{}]", sources.join(", "))
+            }
+            PriceSource::Expression(expression) => {
+                write!(f, "Expression {:?}", expression.get_expression().as_str())
+            }
+            PriceSource::Custom(custom) => write!(f, "{}", custom),
         }
     }
 }
 
 impl PriceSource {
+    /// Fixed price like `$1.25`
+    pub fn hard(price: &str) -> Result<PriceSource, MoneyError> {
+        Ok(PriceSource::Hard(CurrencyTable::from(
+            price.parse::<Money>()?,
+        )))
+    }
+
+    pub fn from_html_attribute(
+        html_selector: &str,
+        attribute_name: &str,
+    ) -> Result<PriceSource, CssSelectorError> {
+        Ok(PriceSource::FromHtmlAttribute(HtmlAttributeSelector::new(
+            html_selector,
+            attribute_name,
+        )?))
+    }
+
+    pub fn from_meta(key: &str) -> PriceSource {
+        PriceSource::FromMeta {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn from_json_path(path: &str) -> Result<PriceSource, JsonPathError> {
+        Ok(PriceSource::FromJsonPath(JsonPath::new(path)?))
+    }
+
+    pub fn from_json_ld(json_ld_type: &str) -> PriceSource {
+        PriceSource::FromJsonLd(json_ld_type.to_string())
+    }
+
+    /// Extract the price and record which source yielded it; for [FirstOf](PriceSource::FirstOf)
+    /// this is the first source that succeeded
+    pub fn explain(&self, doc_and_path: &DocumentAndPath) -> PriceTrace {
+        block_on(self.explain_async(doc_and_path))
+    }
+
+    /// [explain](Self::explain) awaiting [custom price sources](CustomPriceSource)
+    pub async fn explain_async(&self, doc_and_path: &DocumentAndPath) -> PriceTrace {
+        let (price, source) = self.get_price_with_trace_async(doc_and_path).await;
+
+        PriceTrace {
+            source,
+            result: price.map_err(|e| e.to_string()),
+        }
+    }
+
+    /// [get_price](Self::get_price) along with a description of the source that yielded the
+    /// price, e.g. `FirstOf #2: FromMeta price`; every source is evaluated once
+    pub fn get_price_with_trace(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> (Result<Money, PriceSourceExtractError>, String) {
+        block_on(self.get_price_with_trace_async(doc_and_path))
+    }
+
+    /// [get_price_with_trace](Self::get_price_with_trace) awaiting
+    /// [custom price sources](CustomPriceSource)
+    pub async fn get_price_with_trace_async(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> (Result<Money, PriceSourceExtractError>, String) {
+        match self {
+            PriceSource::FirstOf(fallback) => {
+                let mut errors = Vec::new();
+
+                for (i, source) in fallback.get_sources().iter().enumerate() {
+                    let (price, source) =
+                        Box::pin(source.get_price_with_trace_async(doc_and_path)).await;
+                    match price.and_then(|price| fallback.clamp(price)) {
+                        Ok(price) => {
+                            return (Ok(price), format!("FirstOf #{}: {}", i + 1, source));
+                        }
+                        Err(e) => errors.push(e),
+                    }
+                }
+
+                (
+                    Err(PriceSourceExtractError::AllSourcesFailed(errors)),
+                    self.to_string(),
+                )
+            }
+            PriceSource::Converted(conversion) => {
+                let (price, source) = Box::pin(
+                    conversion
+                        .get_source()
+                        .get_price_with_trace_async(doc_and_path),
+                )
+                .await;
+                let preferred = doc_and_path
+                    .get_request_context()
+                    .get_preferred_currencies();
+                let price = conversion
+                    .check_age(unix_now())
+                    .map_err(PriceSourceExtractError::ExchangeRateError)
+                    .and(price);
+
+                (
+                    price.map(|p| conversion.convert(p, preferred)),
+                    format!("Converted from {}", source),
+                )
+            }
+            PriceSource::Hard(_)
+            | PriceSource::FromHtmlAttribute(_)
+            | PriceSource::FromMeta { .. }
+            | PriceSource::FromJsonPath(_)
+            | PriceSource::FromJsonLd(_)
+            | PriceSource::Expression(_)
+            | PriceSource::Custom(_) => {
+                (self.get_price_async(doc_and_path).await, self.to_string())
+            }
+        }
+    }
+
+    /// Extract the price; prices in several currencies are selected by the
+    /// [preferred currencies](RequestContext::get_preferred_currencies) of the request
     pub fn get_price(
         &self,
         doc_and_path: &DocumentAndPath,
-    ) -> Result<Currency, PriceSourceExtractError> {
+    ) -> Result<Money, PriceSourceExtractError> {
+        block_on(self.get_price_async(doc_and_path))
+    }
+
+    /// [get_price](Self::get_price) awaiting [custom price sources](CustomPriceSource)
+    pub async fn get_price_async(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> Result<Money, PriceSourceExtractError> {
         let doc = doc_and_path.get_document();
+        let ctx = doc_and_path.get_request_context();
+        let preferred = ctx.get_preferred_currencies();
+        let document_currency = ctx.get_document_currency();
 
         match (self, doc) {
-            (PriceSource::Hard(CurrencyWrapper { currency }), _) => Ok(currency.clone()),
+            (PriceSource::Hard(table), _) => Ok(table.select(preferred).clone()),
             (PriceSource::FromHtmlAttribute(selector), RequestableDoc::HtmlNode(node)) => {
-                let extracted = selector.get_attribute::<Currency>(&node);
-                match extracted {
-                    Ok(currency) => Ok(currency),
-                    Err(error) => Err(PriceSourceExtractError::HtmlAttributeSelectorError(error)),
-                }
+                let raw = selector
+                    .get_attribute::<String>(node)
+                    .map_err(PriceSourceExtractError::HtmlAttributeSelectorError)?;
+
+                Money::parse_with_default(&raw, document_currency).map_err(|e| match e {
+                    MoneyError::MissingCurrency(_) => PriceSourceExtractError::UnknownCurrency(raw),
+                    e => PriceSourceExtractError::InvalidPrice(raw, e),
+                })
+            }
+            (PriceSource::FromJsonPath(path), RequestableDoc::Json(document)) => {
+                let raw = match path.select_first(document) {
+                    Some(serde_json::Value::String(s)) => s.clone(),
+                    Some(serde_json::Value::Number(n)) => n.to_string(),
+                    _ => return Err(PriceSourceExtractError::JsonPathNotFound(path.to_string())),
+                };
+
+                Money::parse_with_default(&raw, document_currency).map_err(|e| match e {
+                    MoneyError::MissingCurrency(_) => PriceSourceExtractError::UnknownCurrency(raw),
+                    e => PriceSourceExtractError::InvalidPrice(raw, e),
+                })
+            }
+            (PriceSource::FromJsonLd(ld_type), RequestableDoc::HtmlNode(node)) => {
+                let offer = find_json_ld_offer(node, ld_type)
+                    .map_err(PriceSourceExtractError::JsonLdError)?;
+                let currency = match &offer.currency {
+                    Some(code) => Some(CurrencyCode::new(code).map_err(|_| {
+                        PriceSourceExtractError::JsonLdError(JsonLdError::InvalidCurrency(
+                            code.clone(),
+                        ))
+                    })?),
+                    None => document_currency.cloned(),
+                };
+
+                Money::parse_with_default(&offer.price, currency.as_ref()).map_err(|e| match e {
+                    MoneyError::MissingCurrency(_) => {
+                        PriceSourceExtractError::UnknownCurrency(offer.price)
+                    }
+                    e => PriceSourceExtractError::InvalidPrice(offer.price, e),
+                })
+            }
+            (
+                PriceSource::FromHtmlAttribute(_)
+                | PriceSource::FromJsonPath(_)
+                | PriceSource::FromJsonLd(_),
+                _,
+            ) => Err(PriceSourceExtractError::UnsupportedDocument(
+                doc.get_type_name(),
+            )),
+            (PriceSource::FromMeta { key }, _) => {
+                let raw = doc
+                    .get_meta(key)
+                    .ok_or_else(|| PriceSourceExtractError::MetaNotFound(key.clone()))?;
+
+                Money::parse_with_default(&raw, document_currency).map_err(|e| match e {
+                    MoneyError::MissingCurrency(_) => PriceSourceExtractError::UnknownCurrency(raw),
+                    e => PriceSourceExtractError::InvalidPrice(raw, e),
+                })
+            }
+            (PriceSource::FirstOf(fallback), _) => fallback.get_price_async(doc_and_path).await,
+            (PriceSource::Expression(expression), _) => expression.get_price(doc_and_path),
+            (PriceSource::Custom(custom), _) => custom.get_inner().get_price(doc_and_path).await,
+            (PriceSource::Converted(conversion), _) => {
+                conversion
+                    .check_age(unix_now())
+                    .map_err(PriceSourceExtractError::ExchangeRateError)?;
+                let price = Box::pin(conversion.get_source().get_price_async(doc_and_path)).await?;
+                Ok(conversion.convert(price, preferred))
             }
         }
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct PaywallElement {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    teaser: Option<MarkdownTeaser>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    redact: Vec<JsonRedaction>,
+    /// Where gated binary downloads are redirected, `{path}` is replaced by the percent-encoded
+    /// URL path; without it they are answered with `402 Payment Required`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checkout_url: Option<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    on_error: ErrorPolicy,
     paywall_conditions: Vec<PaywallCondition>,
     price_source: PriceSource,
 }
 
+#[derive(Debug)]
 pub enum PaywallPriceOption {
-    Price(Currency),
+    Price(Money),
     ConditionsNotMet,
-    PriceParsingError(String)
+    PriceParsingError(PaywallPriceError),
 }
 
-impl PaywallPriceOption {
-    pub fn unwrap(&self) -> Currency {
-        match self {
-            PaywallPriceOption::Price(p) => p.clone(),
-            PaywallPriceOption::ConditionsNotMet => panic!("PaywallPriceOption .unwrap() to Currency but variant is ConditionsNotMet"),
-            PaywallPriceOption::PriceParsingError(_) => panic!("PaywallPriceOption .unwrap() to Currency but variant is PriceParsingError"),
-        } 
+/// Why no price could be determined; the structured cause is kept as
+/// [source](std::error::Error::source)
+#[derive(Debug, Error)]
+pub enum PaywallPriceError {
+    #[error("Paywall conditions not met")]
+    ConditionsNotMet,
+    #[error("Cannot extract price")]
+    Extract(#[from] PriceSourceExtractError),
+    #[error("Cannot apply discount")]
+    Discount(#[from] DiscountError),
+    #[error("Cannot compute tax")]
+    Tax(#[from] TaxError),
+    #[error("Cannot parse document: {0}")]
+    Document(String),
+}
+
+impl PaywallPriceError {
+    /// Message followed by the message of its cause, for logs and events
+    pub fn get_message(&self) -> String {
+        match std::error::Error::source(self) {
+            Some(source) => format!("{}: {}", self, source),
+            None => self.to_string(),
+        }
     }
 }
 
-impl PaywallElement {
-    pub fn get_price(&self, doc_and_path: &DocumentAndPath) -> PaywallPriceOption {
-        let is_paywalled = self
-            .paywall_conditions
-            .iter()
-            .map(|x| x.is_paywalled(doc_and_path))
-            .all(|r| r);
+impl PaywallPriceOption {
+    pub fn get_price(&self) -> Option<&Money> {
+        match self {
+            PaywallPriceOption::Price(p) => Some(p),
+            PaywallPriceOption::ConditionsNotMet | PaywallPriceOption::PriceParsingError(_) => None,
+        }
+    }
 
-        if !is_paywalled {
-            return PaywallPriceOption::ConditionsNotMet;
+    pub fn get_error(&self) -> Option<&PaywallPriceError> {
+        match self {
+            PaywallPriceOption::PriceParsingError(e) => Some(e),
+            PaywallPriceOption::Price(_) | PaywallPriceOption::ConditionsNotMet => None,
         }
+    }
 
-        let price = self.price_source.get_price(doc_and_path);
+    pub fn into_result(self) -> Result<Money, PaywallPriceError> {
+        match self {
+            PaywallPriceOption::Price(p) => Ok(p),
+            PaywallPriceOption::ConditionsNotMet => Err(PaywallPriceError::ConditionsNotMet),
+            PaywallPriceOption::PriceParsingError(e) => Err(e),
+        }
+    }
 
-        match price {
-            Ok(p) => PaywallPriceOption::Price(p),
-            Err(e) => PaywallPriceOption::PriceParsingError(e.to_string()),
+    /// Currency the price was selected in, to be handed to templates and payment providers
+    pub fn currency_code(&self) -> Option<&CurrencyCode> {
+        match self {
+            PaywallPriceOption::Price(p) => Some(p.currency_code()),
+            _ => None,
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use html_editor::parse;
+impl PaywallElement {
+    pub fn to_yaml_string(&self) -> Result<String, PaywallConfigError> {
+        Ok(serde_yml::to_string(self)?)
+    }
 
-    #[test]
-    fn test_paywall_element_simple() {
-        let config_yml = r#"
-        paywall_conditions:
-          - !HasRegexPath "^/premium/.*$"
-        price_source: !Hard $1.25
-        "#;
+    pub fn get_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
 
-        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
 
-        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
-            "<html><head></head><body><div id=test data-price=\"$1.25\"/></body></html>",
-            "/premium/test",
-        )
-        .unwrap();
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
 
-        let currency_target = element.get_price(&doc_and_path).unwrap();
-        let currency_expected = Currency::from_str("$1.25").unwrap();
+    pub fn get_error_policy(&self) -> &ErrorPolicy {
+        &self.on_error
+    }
+
+    /// Whether all conditions that only depend on the path are met
+    pub fn matches_path(&self, url_path: &str) -> bool {
+        self.paywall_conditions
+            .iter()
+            .all(|c| c.matches_path(url_path).unwrap_or(true))
+    }
+
+    /// Free part of a Markdown document; `None` for other documents or without a teaser config
+    pub fn get_teaser<'a>(&self, doc_and_path: &'a DocumentAndPath) -> Option<&'a str> {
+        match (&self.teaser, doc_and_path.get_document()) {
+            (Some(teaser), RequestableDoc::Markdown(markdown)) => Some(teaser.cut(markdown)),
+            (
+                Some(_),
+                RequestableDoc::HtmlNode(_)
+                | RequestableDoc::Json(_)
+                | RequestableDoc::Binary { .. },
+            )
+            | (None, _) => None,
+        }
+    }
+
+    /// JSON document with the configured [redactions](JsonRedaction) applied; `None` for other
+    /// documents
+    pub fn get_redacted_json(&self, doc_and_path: &DocumentAndPath) -> Option<serde_json::Value> {
+        match doc_and_path.get_document() {
+            RequestableDoc::Json(document) => {
+                let mut document = document.clone();
+                self.redact.iter().for_each(|r| r.apply(&mut document));
+                Some(document)
+            }
+            RequestableDoc::HtmlNode(_)
+            | RequestableDoc::Markdown(_)
+            | RequestableDoc::Binary { .. } => None,
+        }
+    }
+
+    /// Response for a binary document: gated if the conditions are met and the reader is not
+    /// `entitled`, otherwise the full file or the `Range` of the request; `None` for other
+    /// documents. `{path}` in the checkout URL is replaced by the percent-encoded URL path
+    pub fn get_binary_response(
+        &self,
+        doc_and_path: &DocumentAndPath,
+        entitled: bool,
+    ) -> Option<BinaryResponse> {
+        block_on(self.get_binary_response_async(doc_and_path, entitled))
+    }
+
+    /// [get_binary_response](Self::get_binary_response) awaiting
+    /// [custom conditions](CustomCondition)
+    pub async fn get_binary_response_async(
+        &self,
+        doc_and_path: &DocumentAndPath,
+        entitled: bool,
+    ) -> Option<BinaryResponse> {
+        let RequestableDoc::Binary { size, .. } = doc_and_path.get_document() else {
+            return None;
+        };
+        let checkout_url = self
+            .checkout_url
+            .as_ref()
+            .map(|url| url.replace("{path}", &doc_and_path.get_url_path().percent_encode()));
+
+        Some(BinaryResponse::new(
+            self.is_paywalled_async(doc_and_path).await && !entitled,
+            checkout_url,
+            doc_and_path.get_request_context().get_header("Range"),
+            *size,
+        ))
+    }
+
+    pub fn is_paywalled(&self, doc_and_path: &DocumentAndPath) -> bool {
+        block_on(self.is_paywalled_async(doc_and_path))
+    }
+
+    /// [is_paywalled](Self::is_paywalled) awaiting [custom conditions](CustomCondition)
+    pub async fn is_paywalled_async(&self, doc_and_path: &DocumentAndPath) -> bool {
+        for condition in &self.paywall_conditions {
+            if !condition.is_paywalled_async(doc_and_path).await {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Trace of all conditions, with the price source if all of them match
+    pub fn explain(&self, index: usize, doc_and_path: &DocumentAndPath) -> ElementTrace {
+        block_on(self.explain_async(index, doc_and_path))
+    }
+
+    /// [explain](Self::explain) awaiting [custom types](CustomCondition)
+    pub async fn explain_async(
+        &self,
+        index: usize,
+        doc_and_path: &DocumentAndPath,
+    ) -> ElementTrace {
+        let mut trace = self.trace_conditions(index, doc_and_path).await;
+        if trace.matched {
+            trace.price = Some(self.price_source.explain_async(doc_and_path).await);
+        }
+
+        trace
+    }
+
+    async fn trace_conditions(&self, index: usize, doc_and_path: &DocumentAndPath) -> ElementTrace {
+        let mut conditions: Vec<ConditionTrace> = Vec::new();
+        for condition in &self.paywall_conditions {
+            let matched = condition.is_paywalled_async(doc_and_path).await;
+            conditions.push(condition.trace(doc_and_path, matched));
+        }
+
+        ElementTrace {
+            index,
+            element_id: self.id.clone(),
+            matched: conditions.iter().all(|c| c.matched),
+            conditions,
+            price: None,
+        }
+    }
+
+    pub fn get_price(&self, doc_and_path: &DocumentAndPath) -> PaywallPriceOption {
+        block_on(self.get_price_async(doc_and_path))
+    }
+
+    /// [get_price](Self::get_price) awaiting [custom types](CustomCondition)
+    pub async fn get_price_async(&self, doc_and_path: &DocumentAndPath) -> PaywallPriceOption {
+        if !self.is_paywalled_async(doc_and_path).await {
+            return PaywallPriceOption::ConditionsNotMet;
+        }
+
+        let price = self.price_source.get_price_async(doc_and_path).await;
+
+        match price {
+            Ok(p) => PaywallPriceOption::Price(p),
+            Err(e) => PaywallPriceOption::PriceParsingError(PaywallPriceError::Extract(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use html_editor::parse;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_paywall_element_simple() {
+        let config_yml = r#"
+        paywall_conditions:
+          - !HasRegexPath "^/premium/.*$"
+        price_source: !Hard $1.25
+        "#;
+
+        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body><div id=test data-price=\"$1.25\"/></body></html>",
+            "/premium/test",
+        )
+        .unwrap();
+
+        let currency_target = element.get_price(&doc_and_path).into_result().unwrap();
+        let currency_expected = Money::from_str("$1.25").unwrap();
 
         assert_eq!(currency_target, currency_expected);
     }
@@ -145,12 +1240,13 @@ mod tests {
         .unwrap();
 
         let currency_target = price_source.get_price(&doc_and_path).unwrap();
-        let currency_expected = Currency::from_str("$1.25").unwrap();
+        let currency_expected = Money::from_str("$1.25").unwrap();
 
         assert_eq!(currency_target, currency_expected);
     }
 
     #[test]
+    #[allow(unused_variables)]
     fn test_price_from_html_selector() {
         let config_yml = r#"
         !FromHtmlAttribute div#test:::data-price
@@ -170,8 +1266,916 @@ mod tests {
         .unwrap();
 
         let currency_target = price_source.get_price(&doc_and_path).unwrap();
-        let currency_expected = Currency::from_str("$1.25").unwrap();
+        let currency_expected = Money::from_str("$1.25").unwrap();
 
         assert_eq!(currency_target, currency_expected);
     }
+
+    #[test]
+    fn test_paywall_element_id_and_tags() {
+        let config_yml = r#"
+        id: premium
+        tags: [research, archive]
+        paywall_conditions:
+          - !HasRegexPath "^/premium/.*$"
+        price_source: !Hard $1.25
+        "#;
+
+        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+
+        assert_eq!(element.get_id(), Some("premium"));
+        assert!(element.has_tag("archive"));
+        assert!(!element.has_tag("news"));
+    }
+
+    #[test]
+    fn test_paywall_config_decision_refers_to_id() {
+        let config_yml = r#"
+        paths:
+          - id: free
+            paywall_conditions:
+              - !HasRegexPath "^/free/.*$"
+            price_source: !Hard $0.00
+          - id: premium
+            paywall_conditions:
+              - !HasRegexPath "^/premium/.*$"
+            price_source: !Hard $1.25
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/premium/test",
+        )
+        .unwrap();
+
+        let decision = config.get_price(&doc_and_path);
+
+        assert_eq!(decision.element_id.as_deref(), Some("premium"));
+        assert_eq!(
+            decision.price_option.into_result().unwrap(),
+            Money::from_str("$1.25").unwrap()
+        );
+        assert!(config.get_element_by_id("free").is_some());
+    }
+
+    #[test]
+    fn test_paywall_config_decision_without_id_refers_to_index() {
+        let config_yml = r#"
+        paths:
+          - id: free
+            paywall_conditions:
+              - !HasRegexPath "^/free/.*$"
+            price_source: !Hard $0.00
+          - paywall_conditions: []
+            price_source: !Hard $1.25
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/premium/test",
+        )
+        .unwrap();
+
+        let decision = config.get_price(&doc_and_path);
+
+        assert_eq!(decision.element_index, Some(1));
+        assert_eq!(decision.to_string(), "element #1: price $1.25");
+    }
+
+    #[test]
+    fn test_paywall_config_duplicate_id() {
+        let config_yml = r#"
+        paths:
+          - id: premium
+            paywall_conditions: []
+            price_source: !Hard $1.00
+          - id: premium
+            paywall_conditions: []
+            price_source: !Hard $2.00
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml);
+
+        match config {
+            Err(PaywallConfigError::DuplicateElementId(id)) => assert_eq!(id, "premium"),
+            _ => panic!("Expected DuplicateElementId error"),
+        }
+    }
+
+    #[test]
+    fn test_paywall_config_invalid_id() {
+        let config_yml = r#"
+        paths:
+          - id: "premium articles"
+            paywall_conditions: []
+            price_source: !Hard $1.00
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml);
+
+        match config {
+            Err(PaywallConfigError::InvalidElementId(_)) => {}
+            _ => panic!("Expected InvalidElementId error"),
+        }
+    }
+
+    #[test]
+    fn test_price_source_hard_multi_currency() {
+        let config_yml = r#"
+        !Hard { USD: "$1.00", EUR: "€0.95", GBP: "£0.85" }
+        "#;
+
+        let price_source: PriceSource = serde_yml::from_str(config_yml).unwrap();
+
+        let selection = CurrencySelection::default();
+        let ctx = selection.apply(RequestContext::new().with_header("Accept-Language", "de-DE"));
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/test/test",
+        )
+        .unwrap()
+        .with_request_context(ctx);
+
+        let currency_target = price_source.get_price(&doc_and_path).unwrap();
+
+        assert_eq!(currency_target, Money::from_str("€0.95").unwrap());
+    }
+
+    #[test]
+    fn test_paywall_config_currency_flows_to_price_option() {
+        let config_yml = r#"
+        currency_selection:
+          cookie: currency
+          fallback: GBP
+        paths:
+          - id: premium
+            paywall_conditions:
+              - !HasRegexPath "^/premium/.*$"
+            price_source: !Hard { USD: "$1.00", GBP: "£0.85" }
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let ctx = config
+            .get_currency_selection()
+            .apply(RequestContext::new().with_header("Accept-Language", "fr-CH"));
+
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/premium/test",
+        )
+        .unwrap()
+        .with_request_context(ctx);
+
+        let decision = config.get_price(&doc_and_path);
+
+        assert_eq!(
+            decision.price_option.currency_code(),
+            Some(&CurrencyCode::new("GBP").unwrap())
+        );
+        assert_eq!(
+            decision.price_option.into_result().unwrap(),
+            Money::from_str("£0.85").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paywall_config_applies_currency_selection() {
+        let config_yml = r#"
+        currency_selection:
+          fallback: EUR
+          document_currency: USD
+        paths:
+          - id: document
+            paywall_conditions:
+              - !HasRegexPath "^/document/.*$"
+            price_source: !FromHtmlAttribute div#test:::data-price
+          - id: table
+            paywall_conditions: []
+            price_source: !Hard { USD: "$1.00", EUR: "€0.95" }
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let html = "<html><head></head><body><div id=test data-price=\"1.25\"/></body></html>";
+        let price_at = |path: &str| {
+            let doc_and_path = DocumentAndPath::new_from_html_and_path_str(html, path).unwrap();
+            config.get_price(&doc_and_path).to_string()
+        };
+
+        assert_eq!(price_at("/document/a"), "element 'document': price $1.25");
+        assert_eq!(price_at("/table/a"), "element 'table': price €0.95");
+
+        let reader = RequestContext::new().with_header("X-Rustwall-Currency", "USD");
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(html, "/table/a")
+            .unwrap()
+            .with_request_context(reader);
+        assert_eq!(
+            config.get_price(&doc_and_path).to_string(),
+            "element 'table': price $1.00"
+        );
+    }
+
+    #[test]
+    fn test_bare_amount_uses_document_currency_only() {
+        let config_yml = r#"
+        currency_selection:
+          cookie: currency
+          document_currency: EUR
+        paths:
+          - paywall_conditions:
+              - !HasRegexPath "^/premium/.*$"
+            price_source: !FromHtmlAttribute div#test:::data-price
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let html = "<html><head></head><body><div id=test data-price=\"1.25\"/></body></html>";
+        let reader = RequestContext::new().with_cookie("currency", "USD");
+
+        let unconfigured = DocumentAndPath::new_from_html_and_path_str(html, "/premium/test")
+            .unwrap()
+            .with_request_context(
+                reader
+                    .clone()
+                    .with_preferred_currencies(vec![CurrencyCode::new("USD").unwrap()]),
+            );
+        assert!(matches!(
+            config.get_elements()[0].get_price(&unconfigured),
+            PaywallPriceOption::PriceParsingError(PaywallPriceError::Extract(
+                PriceSourceExtractError::UnknownCurrency(_)
+            ))
+        ));
+
+        let configured = DocumentAndPath::new_from_html_and_path_str(html, "/premium/test")
+            .unwrap()
+            .with_request_context(config.get_currency_selection().apply(reader));
+        assert_eq!(
+            config
+                .get_price(&configured)
+                .price_option
+                .into_result()
+                .unwrap(),
+            Money::from_str("€1.25").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_price_source_converted_from_html_attribute() {
+        let rates_path = std::env::temp_dir().join("rustwall_test_fresh_rates.yml");
+        std::fs::write(
+            &rates_path,
+            format!(
+                "base: USD\ntimestamp: {}\nrates:\n  GBP: 0.8\n",
+                crate::utils::unix_now()
+            ),
+        )
+        .unwrap();
+
+        let config_yml = format!(
+            r#"
+        !Converted
+          source: !FromHtmlAttribute div#test:::data-price
+          rates_file: {}
+          rounding: !Ending 99
+        "#,
+            rates_path.display()
+        );
+
+        let price_source: PriceSource = serde_yml::from_str(&config_yml).unwrap();
+
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body><div id=test data-price=\"$2.50\"/></body></html>",
+            "/test/test",
+        )
+        .unwrap()
+        .with_request_context(
+            RequestContext::new()
+                .with_preferred_currencies(vec![CurrencyCode::new("GBP").unwrap()]),
+        );
+
+        let currency_target = price_source.get_price(&doc_and_path).unwrap();
+
+        assert_eq!(currency_target, Money::from_str("£1.99").unwrap());
+    }
+
+    #[test]
+    fn test_paywall_config_tax_breakdown() {
+        let config_yml = r#"
+        tax:
+          default_country: DE
+          rates:
+            DE: 19%
+        paths:
+          - id: premium
+            paywall_conditions:
+              - !HasRegexPath "^/premium/.*$"
+            price_source: !Hard €2.00
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/premium/test",
+        )
+        .unwrap();
+
+        let decision = config.get_price(&doc_and_path);
+        let tax = decision.tax.unwrap();
+
+        assert_eq!(
+            decision.price_option.into_result().unwrap(),
+            Money::from_str("€2.00").unwrap()
+        );
+        assert_eq!(tax.tax, Money::from_str("€0.38").unwrap());
+        assert_eq!(tax.gross, Money::from_str("€2.38").unwrap());
+    }
+
+    #[test]
+    fn test_paywall_config_discount_before_tax() {
+        let config_yml = r#"
+        discounts:
+          codes:
+            - code: HALF
+              discount: !Percent 50%
+              elements: [premium]
+        tax:
+          default_country: DE
+          rates:
+            DE: 19%
+        paths:
+          - id: premium
+            paywall_conditions:
+              - !HasRegexPath "^/premium/.*$"
+            price_source: !Hard €2.00
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/premium/test",
+        )
+        .unwrap()
+        .with_request_context(RequestContext::new().with_query_string("coupon=HALF"));
+
+        let decision = config.get_price(&doc_and_path);
+        let discount = decision.discount.unwrap();
+
+        assert_eq!(discount.original, Money::from_str("€2.00").unwrap());
+        assert_eq!(discount.final_price, Money::from_str("€1.00").unwrap());
+        assert_eq!(
+            decision.price_option.into_result().unwrap(),
+            Money::from_str("€1.00").unwrap()
+        );
+        assert_eq!(
+            decision.tax.unwrap().gross,
+            Money::from_str("€1.19").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paywall_element_markdown_teaser() {
+        let config_yml = r#"
+        teaser: { sections: 1 }
+        paywall_conditions:
+          - !HasFrontMatterKey price
+        price_source: !FromHtmlAttribute div#test:::data-price
+        "#;
+
+        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_markdown_and_path_str(
+            "---\nprice: $1.00\n---\nIntro\n## One\nA\n## Two\nB\n",
+            "/a",
+        )
+        .unwrap();
+
+        assert_eq!(
+            element.get_teaser(&doc_and_path),
+            Some("Intro\n## One\nA\n")
+        );
+        match element.get_price(&doc_and_path) {
+            PaywallPriceOption::PriceParsingError(_) => {}
+            _ => panic!("Expected PriceParsingError"),
+        }
+    }
+
+    #[test]
+    fn test_price_source_from_meta() {
+        let config_yml = r#"
+        !FromMeta { key: price }
+        "#;
+
+        let price_source: PriceSource = serde_yml::from_str(config_yml).unwrap();
+
+        let html = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head><meta name=\"rustwall:price\" content=\"$2.00\"></head><body></body></html>",
+            "/a",
+        )
+        .unwrap();
+        let markdown =
+            DocumentAndPath::new_from_markdown_and_path_str("---\nprice: \"€1.50\"\n---\n", "/a")
+                .unwrap();
+        let missing = DocumentAndPath::new_from_markdown_and_path_str("Body", "/a").unwrap();
+
+        assert_eq!(
+            price_source.get_price(&html).unwrap(),
+            Money::from_str("$2.00").unwrap()
+        );
+        assert_eq!(
+            price_source.get_price(&markdown).unwrap(),
+            Money::from_str("€1.50").unwrap()
+        );
+        assert!(matches!(
+            price_source.get_price(&missing),
+            Err(PriceSourceExtractError::MetaNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_price_source_from_meta_bare_amount() {
+        let price_source = PriceSource::from_meta("price");
+        let bare = || {
+            DocumentAndPath::new_from_markdown_and_path_str("---\nprice: \"1.50\"\n---\n", "/a")
+                .unwrap()
+        };
+        let eur = CurrencyCode::new("EUR").unwrap();
+
+        let preferred = bare().with_request_context(
+            RequestContext::new().with_preferred_currencies(vec![eur.clone()]),
+        );
+        let configured =
+            bare().with_request_context(RequestContext::new().with_document_currency(Some(eur)));
+
+        assert!(matches!(
+            price_source.get_price(&preferred),
+            Err(PriceSourceExtractError::UnknownCurrency(_))
+        ));
+        assert_eq!(
+            price_source.get_price(&configured).unwrap(),
+            Money::from_str("€1.50").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paywall_element_json_price_and_redaction() {
+        let config_yml = r#"
+        paywall_conditions:
+          - !JsonPathMatches "$.article.premium"
+        price_source: !FromJsonPath $.article.price
+        redact:
+          - path: $.article.body
+          - path: $.article.summary
+            truncate: 5
+        "#;
+
+        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_json_and_path_str(
+            r#"{"article": {"premium": true, "price": 1.5, "summary": "Short summary", "body": "Full text"}}"#,
+            "/api/articles/1",
+        )
+        .unwrap()
+        .with_request_context(
+            RequestContext::new().with_document_currency(Some(CurrencyCode::new("EUR").unwrap())),
+        );
+
+        assert_eq!(
+            element.get_price(&doc_and_path).into_result().unwrap(),
+            Money::from_str("€1.50").unwrap()
+        );
+
+        let redacted = element.get_redacted_json(&doc_and_path).unwrap();
+        assert_eq!(redacted["article"]["summary"], "Short");
+        assert_eq!(redacted["article"]["body"], "");
+        assert_eq!(redacted["article"]["price"], 1.5);
+    }
+
+    #[test]
+    fn test_price_source_from_json_path_bare_number() {
+        let price_source = PriceSource::from_json_path("$.price").unwrap();
+        let doc_and_path = DocumentAndPath::new_from_json_and_path_str(r#"{"price": 2}"#, "/a")
+            .unwrap()
+            .with_request_context(
+                RequestContext::new()
+                    .with_preferred_currencies(vec![CurrencyCode::new("GBP").unwrap()]),
+            );
+
+        assert!(matches!(
+            price_source.get_price(&doc_and_path),
+            Err(PriceSourceExtractError::UnknownCurrency(_))
+        ));
+    }
+
+    #[test]
+    fn test_paywall_element_binary_response() {
+        let config_yml = r#"
+        checkout_url: /checkout?item={path}
+        paywall_conditions:
+          - !HasContentType application/pdf
+        price_source: !Hard $5.00
+        "#;
+
+        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+        let doc_and_path =
+            DocumentAndPath::new_from_binary_and_path_str("application/pdf", 2048, "/r/1&a.pdf")
+                .unwrap()
+                .with_request_context(RequestContext::new().with_header("Range", "bytes=0-1023"));
+
+        assert_eq!(
+            element.get_binary_response(&doc_and_path, false),
+            Some(BinaryResponse::RedirectToCheckout(
+                "/checkout?item=/r/1%26a.pdf".to_string()
+            ))
+        );
+        assert_eq!(
+            element.get_binary_response(&doc_and_path, true),
+            Some(BinaryResponse::Partial(ByteRange {
+                start: 0,
+                end: 1023
+            }))
+        );
+        assert_eq!(
+            element.get_price(&doc_and_path).into_result().unwrap(),
+            Money::from_str("$5.00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_price_source_from_json_ld() {
+        let config_yml = r#"
+        !FromJsonLd Product
+        "#;
+
+        let price_source: PriceSource = serde_yml::from_str(config_yml).unwrap();
+
+        let priced = DocumentAndPath::new_from_html_and_path_str(
+            r#"<html><head><script type="application/ld+json">{"@type": "Product", "offers": {"price": "12.50", "priceCurrency": "EUR"}}</script></head><body></body></html>"#,
+            "/a",
+        )
+        .unwrap();
+        let bad_currency = DocumentAndPath::new_from_html_and_path_str(
+            r#"<html><head><script type="application/ld+json">{"@type": "Product", "offers": {"price": 1, "priceCurrency": "Euro"}}</script></head><body></body></html>"#,
+            "/a",
+        )
+        .unwrap();
+
+        assert_eq!(
+            price_source.get_price(&priced).unwrap(),
+            Money::from_str("€12.50").unwrap()
+        );
+        assert!(matches!(
+            price_source.get_price(&bad_currency),
+            Err(PriceSourceExtractError::JsonLdError(
+                JsonLdError::InvalidCurrency(_)
+            ))
+        ));
+
+        let without_currency = || {
+            DocumentAndPath::new_from_html_and_path_str(
+                r#"<html><head><script type="application/ld+json">{"@type": "Product", "offers": {"price": "4.00"}}</script></head><body></body></html>"#,
+                "/a",
+            )
+            .unwrap()
+        };
+        let preferred = without_currency().with_request_context(
+            RequestContext::new()
+                .with_preferred_currencies(vec![CurrencyCode::new("EUR").unwrap()]),
+        );
+        let configured = without_currency().with_request_context(
+            RequestContext::new().with_document_currency(Some(CurrencyCode::new("USD").unwrap())),
+        );
+
+        assert!(matches!(
+            price_source.get_price(&preferred),
+            Err(PriceSourceExtractError::UnknownCurrency(_))
+        ));
+        assert_eq!(
+            price_source.get_price(&configured).unwrap(),
+            Money::from_str("$4.00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paywall_element_first_of_price_source() {
+        let config_yml = r#"
+        paywall_conditions: []
+        price_source: !FirstOf [ !FromHtmlAttribute div#test:::data-price, !FromMeta { key: price }, !Hard $1.00 ]
+        "#;
+
+        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head><meta name=\"rustwall:price\" content=\"$3.00\"></head><body></body></html>",
+            "/a",
+        )
+        .unwrap();
+
+        assert_eq!(
+            element.get_price(&doc_and_path).into_result().unwrap(),
+            Money::from_str("$3.00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_paywall_price_option_result_and_error_source() {
+        use std::error::Error;
+
+        let config_yml = r#"
+        paywall_conditions:
+          - !HasRegexPath "^/premium/.*$"
+        price_source: !FromHtmlAttribute div#test:::data-price
+        "#;
+
+        let element: PaywallElement = serde_yml::from_str(config_yml).unwrap();
+        let free = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/free/test",
+        )
+        .unwrap();
+        let broken = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/premium/test",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            element.get_price(&free).into_result(),
+            Err(PaywallPriceError::ConditionsNotMet)
+        ));
+
+        let error = element.get_price(&broken).into_result().unwrap_err();
+        let extract_error = error.source().unwrap();
+        let selector_error = extract_error.source().unwrap();
+
+        assert_eq!(error.to_string(), "Cannot extract price");
+        assert_eq!(
+            error.get_message(),
+            format!("Cannot extract price: {}", extract_error)
+        );
+
+        assert!(matches!(
+            extract_error.downcast_ref::<PriceSourceExtractError>(),
+            Some(PriceSourceExtractError::HtmlAttributeSelectorError(_))
+        ));
+        assert!(matches!(
+            selector_error.downcast_ref::<HtmlAttributeSelectorError>(),
+            Some(HtmlAttributeSelectorError::ElementNotFound)
+        ));
+
+        let invalid = DocumentAndPath::new_from_html_and_path_str(
+            r#"<html><body><div id="test" data-price="$abc"></div></body></html>"#,
+            "/premium/test",
+        )
+        .unwrap();
+        let error = element.get_price(&invalid).into_result().unwrap_err();
+        let extract_error = error.source().unwrap();
+
+        assert!(matches!(
+            extract_error.downcast_ref::<PriceSourceExtractError>(),
+            Some(PriceSourceExtractError::InvalidPrice(raw, _)) if raw == "$abc"
+        ));
+        assert!(
+            extract_error
+                .source()
+                .unwrap()
+                .downcast_ref::<MoneyError>()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_paywall_config_error_policies() {
+        let config_yml = r#"
+        paths:
+          - id: marketing
+            on_error: allow
+            paywall_conditions:
+              - !HasRegexPath "^/marketing/.*$"
+            price_source: !FromHtmlAttribute div#price:::data-price
+          - id: research
+            paywall_conditions:
+              - !HasRegexPath "^/research/.*$"
+            price_source: !FromHtmlAttribute div#price:::data-price
+          - id: news
+            on_error: !fixed_price $1.00
+            paywall_conditions:
+              - !HasRegexPath "^/news/.*$"
+            price_source: !FromHtmlAttribute div#price:::data-price
+        "#;
+
+        let mut config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let events = Arc::new(InMemoryPaywallEventSink::default());
+        config.set_event_sink(events.clone());
+
+        let decide = |path: &str| {
+            let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+                "<html><head></head><body></body></html>",
+                path,
+            )
+            .unwrap();
+            config.get_price(&doc_and_path)
+        };
+
+        let marketing = decide("/marketing/a");
+        let research = decide("/research/a");
+        let news = decide("/news/a");
+
+        assert!(matches!(
+            marketing.price_option,
+            PaywallPriceOption::ConditionsNotMet
+        ));
+        assert!(marketing.error_event.is_some());
+        assert!(matches!(
+            research.price_option.get_error(),
+            Some(PaywallPriceError::Extract(_))
+        ));
+        assert_eq!(
+            news.price_option.into_result().unwrap(),
+            Money::from_str("$1.00").unwrap()
+        );
+
+        let policies: Vec<&str> = events.get_events().iter().map(|e| e.policy).collect();
+        assert_eq!(policies, vec!["allow", "deny", "fixed_price"]);
+    }
+
+    #[test]
+    fn test_paywall_config_tax_after_error_policy() {
+        let config_yml = r#"
+        tax:
+          default_country: DE
+          rates: { DE: 19% }
+        paths:
+          - id: news
+            on_error: !fixed_price $1.00
+            paywall_conditions:
+              - !HasRegexPath "^/news/.*$"
+            price_source: !FromMeta { key: price }
+          - id: marketing
+            on_error: allow
+            paywall_conditions:
+              - !HasRegexPath "^/marketing/.*$"
+            price_source: !Hard $90000000000000000.00
+        "#;
+
+        let mut config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let events = Arc::new(InMemoryPaywallEventSink::default());
+        config.set_event_sink(events.clone());
+        let decide = |path: &str| {
+            let doc_and_path =
+                DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", path)
+                    .unwrap();
+            config.get_price(&doc_and_path)
+        };
+
+        let news = decide("/news/a");
+        assert_eq!(news.tax.unwrap().gross, Money::from_str("$1.19").unwrap());
+        assert_eq!(events.get_events().len(), 1);
+
+        let marketing = decide("/marketing/a");
+        assert!(matches!(
+            marketing.price_option.into_result(),
+            Err(PaywallPriceError::Tax(_))
+        ));
+        let policies: Vec<&str> = events.get_events().iter().map(|e| e.policy).collect();
+        assert_eq!(policies, vec!["fixed_price", "deny"]);
+    }
+
+    #[test]
+    fn test_paywall_config_document_error_policy() {
+        let config_yml = r#"
+        paths:
+          - id: research
+            on_error: !fixed_price $5.00
+            paywall_conditions:
+              - !HasRegexPath "^/research/.*$"
+              - !MatchesCssSelector "div.premium"
+            price_source: !Hard $2.00
+        "#;
+
+        let mut config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        config.set_event_sink(Arc::new(InMemoryPaywallEventSink::default()));
+        let error =
+            DocumentAndPath::new_from_html_and_path_str("<html><head</head>", "/research/a")
+                .unwrap_err();
+
+        let research = config.get_price_for_document_error(
+            &UrlPath::new("/research/a").unwrap(),
+            &RequestContext::new(),
+            &error,
+        );
+        let other = config.get_price_for_document_error(
+            &UrlPath::new("/news/a").unwrap(),
+            &RequestContext::new(),
+            &error,
+        );
+
+        assert_eq!(
+            research.price_option.into_result().unwrap(),
+            Money::from_str("$5.00").unwrap()
+        );
+        assert!(matches!(
+            other.price_option,
+            PaywallPriceOption::ConditionsNotMet
+        ));
+    }
+
+    #[test]
+    fn test_paywall_config_explain() {
+        let config_yml = r#"
+        explain_header: true
+        paths:
+          - id: research
+            paywall_conditions:
+              - !HasRegexPath "^/research/.*$"
+            price_source: !Hard $5.00
+          - id: premium
+            paywall_conditions:
+              - !HasRegexPath "^/premium/.*$"
+              - !MatchesCssSelector "div.premium"
+            price_source: !FirstOf [ !FromMeta { key: price }, !Hard €2.00 ]
+          - id: never-reached
+            paywall_conditions: []
+            price_source: !Hard $1.00
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body><div class=\"premium\"></div></body></html>",
+            "/premium/a",
+        )
+        .unwrap();
+
+        let trace = config.explain(&doc_and_path);
+
+        assert_eq!(trace.elements.len(), 2);
+        assert!(!trace.elements[0].matched);
+        assert!(trace.elements[0].price.is_none());
+        assert_eq!(
+            trace.elements[1].conditions[1].detail.as_deref(),
+            Some("<div class=\"premium\">")
+        );
+        let price = trace.elements[1].price.as_ref().unwrap();
+        assert_eq!(price.source, "FirstOf #2: Hard €2.00");
+        assert_eq!(price.result, Ok(Money::from_str("€2.00").unwrap()));
+        assert_eq!(trace.decision, "element 'premium': price €2.00");
+
+        let (decision, header) = config.get_price_with_explain_header(&doc_and_path);
+        assert_eq!(decision.to_string(), trace.decision);
+        let (name, value) = header.unwrap();
+        assert_eq!(name, "X-Rustwall-Explain");
+        assert_eq!(
+            value,
+            "'research'=no(HasRegexPath); 'premium'=match; \
+             price=FirstOf #2: Hard \\u{20ac}2.00 -> \\u{20ac}2.00; \
+             decision=element 'premium': price \\u{20ac}2.00"
+        );
+    }
+
+    #[test]
+    fn test_paywall_config_explain_evaluates_once() {
+        let config_yml = r#"
+        paths:
+          - id: premium
+            paywall_conditions: []
+            price_source: !FirstOf [ !FromMeta { key: price }, !FromJsonLd Product ]
+            on_error: allow
+        "#;
+
+        let mut config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let sink = Arc::new(InMemoryPaywallEventSink::default());
+        config.set_event_sink(sink.clone());
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/a",
+        )
+        .unwrap();
+
+        let trace = config.explain(&doc_and_path);
+
+        assert_eq!(trace.decision, "element 'premium': free after error");
+        assert_eq!(sink.get_events().len(), 1);
+    }
+
+    #[test]
+    fn test_paywall_config_explain_header_is_opt_in() {
+        let config_yml = r#"
+        paths:
+          - paywall_conditions: []
+            price_source: !Hard $1.00
+        "#;
+
+        let config = PaywallConfigV1::from_yaml_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head></head><body></body></html>",
+            "/a",
+        )
+        .unwrap();
+
+        assert!(
+            config
+                .get_price_with_explain_header(&doc_and_path)
+                .1
+                .is_none()
+        );
+        assert_eq!(config.explain(&doc_and_path).elements[0].get_label(), "#0");
+    }
 }
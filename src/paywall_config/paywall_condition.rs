@@ -1,33 +1,314 @@
-use html_editor::operation::{Queryable, Selector};
-use html_editor::parse;
+use html_editor::Element;
 use regex::Regex;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 
-use super::UrlPath;
-use super::{DocumentAndPath, RequestableDoc, RequestableDoc::HtmlNode};
+use super::RequestableDoc::{Binary, HtmlNode, Json, Markdown};
+use super::custom_types::deserialize_tagged;
+use super::{ConditionTrace, CustomCondition, CustomType, DocumentAndPath};
+use crate::utils::{CssSelector, CssSelectorError, JsonPathError, JsonPathPredicate, block_on};
 
 /// Conditions which specify a given [document and path](DocumentAndPath) to be a paywall
-#[derive(Deserialize)]
+///
+/// Besides the built-in variants, conditions of library users are read under the tags they
+/// are [registered](super::register_condition) with
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(remote = "Self")]
 pub enum PaywallCondition {
-    #[serde(deserialize_with = "deserialize_regex")]
+    #[serde(
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
     HasRegexPath(Regex),
-    #[serde(deserialize_with = "deserialize_css_selector")]
-    MatchesCssSelector(Selector),
+    MatchesCssSelector(CssSelector),
+    HasFrontMatterKey(String),
+    /// Compares [document metadata](super::RequestableDoc::get_meta), e.g.
+    /// `!MetaEquals { key: paywall, value: true }`
+    MetaEquals {
+        key: String,
+        #[serde(deserialize_with = "deserialize_scalar_string")]
+        value: String,
+    },
+    /// [JSONPath predicate](JsonPathPredicate) on a JSON document, e.g.
+    /// `!JsonPathMatches "$.article.premium == true"`
+    JsonPathMatches(JsonPathPredicate),
+    /// MIME type of the document, with `*` as subtype wildcard, e.g. `!HasContentType audio/*`
+    HasContentType(String),
+    /// Case-insensitive extension of the URL path, e.g. `!HasFileExtension pdf`
+    HasFileExtension(String),
+    /// Binary documents of at least this many bytes
+    MinSize(u64),
+    /// [Registered](super::register_condition) condition of a library user
+    #[serde(skip)]
+    Custom(CustomType<dyn CustomCondition>),
 }
 
 impl PaywallCondition {
+    pub fn path_regex(regex: &str) -> Result<PaywallCondition, regex::Error> {
+        Ok(PaywallCondition::HasRegexPath(Regex::new(regex)?))
+    }
+
+    pub fn css_selector(selector: &str) -> Result<PaywallCondition, CssSelectorError> {
+        Ok(PaywallCondition::MatchesCssSelector(CssSelector::new(
+            selector,
+        )?))
+    }
+
+    pub fn front_matter_key(key: &str) -> PaywallCondition {
+        PaywallCondition::HasFrontMatterKey(key.to_string())
+    }
+
+    pub fn meta_equals(key: &str, value: &str) -> PaywallCondition {
+        PaywallCondition::MetaEquals {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    pub fn json_path_matches(predicate: &str) -> Result<PaywallCondition, JsonPathError> {
+        Ok(PaywallCondition::JsonPathMatches(JsonPathPredicate::new(
+            predicate,
+        )?))
+    }
+
+    pub fn content_type(content_type: &str) -> PaywallCondition {
+        PaywallCondition::HasContentType(content_type.to_string())
+    }
+
+    pub fn file_extension(extension: &str) -> PaywallCondition {
+        PaywallCondition::HasFileExtension(extension.to_string())
+    }
+
     /// Check if either [document and/or path](DocumentAndPath) match a paywall condition
     pub fn is_paywalled(&self, doc_and_path: &DocumentAndPath) -> bool {
         let url_path = doc_and_path.get_url_path_as_str();
         let reqdoc = doc_and_path.get_document();
 
         match (self, reqdoc) {
-            (PaywallCondition::HasRegexPath(regex), _) => regex.is_match(&url_path),
+            (PaywallCondition::HasRegexPath(regex), _) => regex.is_match(url_path),
             (PaywallCondition::MatchesCssSelector(selector), HtmlNode(node)) => {
-                node.query(&selector).is_some()
-            } //(_, _) => false,
+                selector.query(node).is_some()
+            }
+            (PaywallCondition::MatchesCssSelector(_), Markdown(_) | Json(_) | Binary { .. }) => {
+                false
+            }
+            (PaywallCondition::HasFrontMatterKey(key), Markdown(markdown)) => {
+                markdown.get_front_matter_value(key).is_some()
+            }
+            (PaywallCondition::HasFrontMatterKey(_), HtmlNode(_) | Json(_) | Binary { .. }) => {
+                false
+            }
+            (PaywallCondition::MetaEquals { key, value }, _) => {
+                reqdoc.get_meta(key).as_deref() == Some(value.as_str())
+            }
+            (PaywallCondition::JsonPathMatches(predicate), Json(document)) => {
+                predicate.is_match(document)
+            }
+            (PaywallCondition::JsonPathMatches(_), HtmlNode(_) | Markdown(_) | Binary { .. }) => {
+                false
+            }
+            (PaywallCondition::HasContentType(pattern), _) => {
+                let content_type = reqdoc.get_content_type();
+
+                match pattern.strip_suffix("/*") {
+                    Some(main_type) => content_type
+                        .split_once('/')
+                        .is_some_and(|(t, _)| t.eq_ignore_ascii_case(main_type)),
+                    None => content_type.eq_ignore_ascii_case(pattern),
+                }
+            }
+            (PaywallCondition::HasFileExtension(extension), _) => {
+                has_file_extension(url_path, extension)
+            }
+            (PaywallCondition::MinSize(min_size), Binary { size, .. }) => size >= min_size,
+            (PaywallCondition::MinSize(_), HtmlNode(_) | Markdown(_) | Json(_)) => false,
+            (PaywallCondition::Custom(custom), _) => {
+                block_on(custom.get_inner().is_paywalled(doc_and_path))
+            }
         }
     }
+
+    /// [is_paywalled](Self::is_paywalled) awaiting [custom conditions](CustomCondition) instead
+    /// of blocking on them
+    pub async fn is_paywalled_async(&self, doc_and_path: &DocumentAndPath) -> bool {
+        match self {
+            PaywallCondition::Custom(custom) => custom.get_inner().is_paywalled(doc_and_path).await,
+            _ => self.is_paywalled(doc_and_path),
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            PaywallCondition::HasRegexPath(_) => "HasRegexPath",
+            PaywallCondition::MatchesCssSelector(_) => "MatchesCssSelector",
+            PaywallCondition::HasFrontMatterKey(_) => "HasFrontMatterKey",
+            PaywallCondition::MetaEquals { .. } => "MetaEquals",
+            PaywallCondition::JsonPathMatches(_) => "JsonPathMatches",
+            PaywallCondition::HasContentType(_) => "HasContentType",
+            PaywallCondition::HasFileExtension(_) => "HasFileExtension",
+            PaywallCondition::MinSize(_) => "MinSize",
+            PaywallCondition::Custom(custom) => custom.get_tag(),
+        }
+    }
+
+    /// Check the condition and record what was found in the document
+    pub fn explain(&self, doc_and_path: &DocumentAndPath) -> ConditionTrace {
+        self.trace(doc_and_path, self.is_paywalled(doc_and_path))
+    }
+
+    /// Record what was found in the document for a condition evaluated to `matched`
+    pub(super) fn trace(&self, doc_and_path: &DocumentAndPath, matched: bool) -> ConditionTrace {
+        let reqdoc = doc_and_path.get_document();
+
+        let detail = match (self, reqdoc) {
+            (PaywallCondition::MatchesCssSelector(selector), HtmlNode(node)) => Some(
+                selector
+                    .query(node)
+                    .map_or("no matching node".to_string(), describe_element),
+            ),
+            (PaywallCondition::HasFrontMatterKey(key), Markdown(_))
+            | (PaywallCondition::MetaEquals { key, .. }, _) => Some(
+                reqdoc
+                    .get_meta(key)
+                    .map_or(format!("no '{}' metadata", key), |v| {
+                        format!("found '{}'", v)
+                    }),
+            ),
+            (PaywallCondition::HasContentType(_), _) => {
+                Some(format!("content type {}", reqdoc.get_content_type()))
+            }
+            (PaywallCondition::MinSize(_), Binary { size, .. }) => Some(format!("{} bytes", size)),
+            (PaywallCondition::JsonPathMatches(_), Json(_))
+            | (
+                PaywallCondition::HasRegexPath(_)
+                | PaywallCondition::HasFileExtension(_)
+                | PaywallCondition::Custom(_),
+                _,
+            ) => None,
+            (PaywallCondition::MatchesCssSelector(_), Markdown(_) | Json(_) | Binary { .. })
+            | (PaywallCondition::HasFrontMatterKey(_), HtmlNode(_) | Json(_) | Binary { .. })
+            | (PaywallCondition::JsonPathMatches(_), HtmlNode(_) | Markdown(_) | Binary { .. })
+            | (PaywallCondition::MinSize(_), HtmlNode(_) | Markdown(_) | Json(_)) => Some(format!(
+                "not applicable to {} documents",
+                reqdoc.get_type_name()
+            )),
+        };
+
+        ConditionTrace {
+            condition: self.to_string(),
+            matched,
+            detail,
+        }
+    }
+
+    /// Result of conditions that depend on the path alone, `None` for conditions that need the
+    /// document
+    pub fn matches_path(&self, url_path: &str) -> Option<bool> {
+        match self {
+            PaywallCondition::HasRegexPath(regex) => Some(regex.is_match(url_path)),
+            PaywallCondition::HasFileExtension(extension) => {
+                Some(has_file_extension(url_path, extension))
+            }
+            PaywallCondition::MatchesCssSelector(_)
+            | PaywallCondition::HasFrontMatterKey(_)
+            | PaywallCondition::MetaEquals { .. }
+            | PaywallCondition::JsonPathMatches(_)
+            | PaywallCondition::HasContentType(_)
+            | PaywallCondition::MinSize(_)
+            | PaywallCondition::Custom(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for PaywallCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaywallCondition::HasRegexPath(regex) => write!(f, "HasRegexPath {:?}", regex.as_str()),
+            PaywallCondition::MatchesCssSelector(selector) => {
+                write!(f, "MatchesCssSelector {:?}", selector.as_str())
+            }
+            PaywallCondition::HasFrontMatterKey(key) => write!(f, "HasFrontMatterKey {}", key),
+            PaywallCondition::MetaEquals { key, value } => {
+                write!(f, "MetaEquals {{ key: {}, value: {} }}", key, value)
+            }
+            PaywallCondition::JsonPathMatches(predicate) => {
+                write!(f, "JsonPathMatches {:?}", predicate.to_string())
+            }
+            PaywallCondition::HasContentType(pattern) => write!(f, "HasContentType {}", pattern),
+            PaywallCondition::HasFileExtension(extension) => {
+                write!(f, "HasFileExtension {}", extension)
+            }
+            PaywallCondition::MinSize(size) => write!(f, "MinSize {}", size),
+            PaywallCondition::Custom(custom) => write!(f, "{}", custom),
+        }
+    }
+}
+
+/// Regexes are equal if their source is
+impl PartialEq for PaywallCondition {
+    fn eq(&self, other: &Self) -> bool {
+        use PaywallCondition::*;
+
+        match (self, other) {
+            (HasRegexPath(a), HasRegexPath(b)) => a.as_str() == b.as_str(),
+            (MatchesCssSelector(a), MatchesCssSelector(b)) => a == b,
+            (HasFrontMatterKey(a), HasFrontMatterKey(b)) => a == b,
+            (
+                MetaEquals { key, value },
+                MetaEquals {
+                    key: other_key,
+                    value: other_value,
+                },
+            ) => key == other_key && value == other_value,
+            (JsonPathMatches(a), JsonPathMatches(b)) => a == b,
+            (HasContentType(a), HasContentType(b)) => a == b,
+            (HasFileExtension(a), HasFileExtension(b)) => a == b,
+            (MinSize(a), MinSize(b)) => a == b,
+            (Custom(a), Custom(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Registered tags are looked up before the built-in variants are deserialized
+impl<'de> Deserialize<'de> for PaywallCondition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_tagged(deserializer)
+    }
+}
+
+impl Serialize for PaywallCondition {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            PaywallCondition::Custom(custom) => custom.serialize(serializer),
+            _ => PaywallCondition::serialize(self, serializer),
+        }
+    }
+}
+
+/// Opening tag of an element, e.g. `<div id="paywall" class="premium">`
+fn describe_element(element: &Element) -> String {
+    let attrs: String = element
+        .attrs
+        .iter()
+        .map(|(k, v)| format!(" {}=\"{}\"", k, v))
+        .collect();
+
+    format!("<{}{}>", element.name, attrs)
+}
+
+fn has_file_extension(url_path: &str, extension: &str) -> bool {
+    url_path
+        .rsplit('/')
+        .next()
+        .and_then(|file| file.rsplit_once('.'))
+        .is_some_and(|(_, ext)| ext.eq_ignore_ascii_case(extension.trim_start_matches('.')))
 }
 
 fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
@@ -35,20 +316,34 @@ where
     D: Deserializer<'de>,
 {
     let regex_str: String = String::deserialize(deserializer)?;
-    return Regex::new(&regex_str).map_err(serde::de::Error::custom);
+    Regex::new(&regex_str).map_err(serde::de::Error::custom)
 }
 
-fn deserialize_css_selector<'de, D>(deserializer: D) -> Result<Selector, D::Error>
+fn serialize_regex<S>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(regex.as_str())
+}
+
+fn deserialize_scalar_string<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let css_selector: &str = &String::deserialize(deserializer)?;
-    return Ok(Selector::from(css_selector));
+    match serde_yml::Value::deserialize(deserializer)? {
+        serde_yml::Value::String(s) => Ok(s),
+        serde_yml::Value::Number(n) => Ok(n.to_string()),
+        serde_yml::Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(serde::de::Error::custom(
+            "expected a string, number or boolean",
+        )),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use html_editor::parse;
 
     #[test]
     fn test_path_has_regex_path_has_paywall_true() {
@@ -117,4 +412,139 @@ mod tests {
 
         assert!(!condition.is_paywalled(&doc_and_path));
     }
+
+    #[test]
+    fn test_front_matter_key() {
+        let config_yml = r#"
+        !HasFrontMatterKey premium
+        "#;
+
+        let condition: PaywallCondition = serde_yml::from_str(config_yml).unwrap();
+
+        let with_key =
+            DocumentAndPath::new_from_markdown_and_path_str("---\npremium: yes\n---\n# A", "/a")
+                .unwrap();
+        let without_key = DocumentAndPath::new_from_markdown_and_path_str("# A", "/a").unwrap();
+
+        assert!(condition.is_paywalled(&with_key));
+        assert!(!condition.is_paywalled(&without_key));
+    }
+
+    #[test]
+    fn test_css_selector_on_markdown_false() {
+        let config_yml = r#"
+        !MatchesCssSelector "body"
+        "#;
+
+        let condition: PaywallCondition = serde_yml::from_str(config_yml).unwrap();
+        let doc_and_path = DocumentAndPath::new_from_markdown_and_path_str("# body", "/a").unwrap();
+
+        assert!(!condition.is_paywalled(&doc_and_path));
+    }
+
+    #[test]
+    fn test_meta_equals_html_and_markdown() {
+        let config_yml = r#"
+        !MetaEquals { key: paywall, value: true }
+        "#;
+
+        let condition: PaywallCondition = serde_yml::from_str(config_yml).unwrap();
+
+        let html = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head><meta name=\"rustwall:paywall\" content=\"true\"></head><body></body></html>",
+            "/a",
+        )
+        .unwrap();
+        let markdown =
+            DocumentAndPath::new_from_markdown_and_path_str("---\npaywall: true\n---\n", "/a")
+                .unwrap();
+        let free_markdown =
+            DocumentAndPath::new_from_markdown_and_path_str("---\npaywall: false\n---\n", "/a")
+                .unwrap();
+
+        assert!(condition.is_paywalled(&html));
+        assert!(condition.is_paywalled(&markdown));
+        assert!(!condition.is_paywalled(&free_markdown));
+    }
+
+    #[test]
+    fn test_json_path_matches() {
+        let config_yml = r#"
+        !JsonPathMatches "$.article.premium == true"
+        "#;
+
+        let condition: PaywallCondition = serde_yml::from_str(config_yml).unwrap();
+
+        let premium =
+            DocumentAndPath::new_from_json_and_path_str(r#"{"article": {"premium": true}}"#, "/a")
+                .unwrap();
+        let free =
+            DocumentAndPath::new_from_json_and_path_str(r#"{"article": {"premium": false}}"#, "/a")
+                .unwrap();
+
+        assert!(condition.is_paywalled(&premium));
+        assert!(!condition.is_paywalled(&free));
+    }
+
+    #[test]
+    fn test_binary_conditions() {
+        let conditions: Vec<PaywallCondition> = serde_yml::from_str(
+            r#"
+        - !HasContentType audio/*
+        - !HasFileExtension MP3
+        - !MinSize 1000
+        "#,
+        )
+        .unwrap();
+
+        let episode =
+            DocumentAndPath::new_from_binary_and_path_str("audio/mpeg", 5000, "/podcast/1.mp3")
+                .unwrap();
+        let trailer =
+            DocumentAndPath::new_from_binary_and_path_str("audio/mpeg", 500, "/podcast/t.mp3")
+                .unwrap();
+        let report =
+            DocumentAndPath::new_from_binary_and_path_str("application/pdf", 5000, "/r/1.pdf")
+                .unwrap();
+
+        assert!(conditions.iter().all(|c| c.is_paywalled(&episode)));
+        assert!(!conditions[2].is_paywalled(&trailer));
+        assert!(!conditions[0].is_paywalled(&report));
+        assert!(!conditions[1].is_paywalled(&report));
+    }
+
+    #[test]
+    fn test_explain_records_what_was_found() {
+        let conditions: Vec<PaywallCondition> = serde_yml::from_str(
+            r#"
+        - !MetaEquals { key: paywall, value: true }
+        - !MatchesCssSelector "div.locked"
+        - !MinSize 1000
+        "#,
+        )
+        .unwrap();
+
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            r#"<html><head><meta name="rustwall:paywall" content="false"></head><body></body></html>"#,
+            "/a",
+        )
+        .unwrap();
+        let traces: Vec<_> = conditions
+            .iter()
+            .map(|c| c.explain(&doc_and_path))
+            .collect();
+
+        assert_eq!(
+            traces[0].condition,
+            "MetaEquals { key: paywall, value: true }"
+        );
+        assert!(!traces[0].matched);
+        assert_eq!(traces[0].detail.as_deref(), Some("found 'false'"));
+        assert_eq!(traces[1].condition, "MatchesCssSelector \"div.locked\"");
+        assert_eq!(traces[1].detail.as_deref(), Some("no matching node"));
+        assert_eq!(
+            traces[2].detail.as_deref(),
+            Some("not applicable to HTML documents")
+        );
+    }
 }
@@ -0,0 +1,329 @@
+use regex::Regex;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+
+use super::{DocumentAndPath, PriceSourceExtractError, RequestableDoc};
+use crate::money::{CurrencyCode, Decimal, Money, MoneyError};
+use crate::pricing::{ExprType, ExprValue, Expression, ExpressionError};
+use crate::utils::HtmlAttributeSelector;
+
+/// Value of an [expression](PriceExpression) variable; text variables that are missing in the
+/// document or request are empty, missing numbers fail the price extraction
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub enum ExpressionVariable {
+    HtmlNumber(HtmlAttributeSelector),
+    HtmlText(HtmlAttributeSelector),
+    Meta(String),
+    Header(String),
+    Cookie(String),
+    Query(String),
+}
+
+impl ExpressionVariable {
+    fn get_type(&self) -> ExprType {
+        match self {
+            ExpressionVariable::HtmlNumber(_) => ExprType::Number,
+            _ => ExprType::Text,
+        }
+    }
+
+    fn get_value(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> Result<ExprValue, PriceSourceExtractError> {
+        let doc = doc_and_path.get_document();
+        let ctx = doc_and_path.get_request_context();
+        let text = |value: Option<&str>| ExprValue::Text(value.unwrap_or_default().to_string());
+
+        match (self, doc) {
+            (ExpressionVariable::HtmlNumber(selector), RequestableDoc::HtmlNode(node)) => selector
+                .get_attribute::<Decimal>(node)
+                .map(ExprValue::Number)
+                .map_err(PriceSourceExtractError::HtmlAttributeSelectorError),
+            (ExpressionVariable::HtmlNumber(_), _) => Err(
+                PriceSourceExtractError::UnsupportedDocument(doc.get_type_name()),
+            ),
+            (ExpressionVariable::HtmlText(selector), RequestableDoc::HtmlNode(node)) => {
+                Ok(text(selector.get_attribute::<String>(node).ok().as_deref()))
+            }
+            (ExpressionVariable::HtmlText(_), _) => Ok(text(None)),
+            (ExpressionVariable::Meta(key), _) => Ok(text(doc.get_meta(key).as_deref())),
+            (ExpressionVariable::Header(name), _) => Ok(text(ctx.get_header(name))),
+            (ExpressionVariable::Cookie(name), _) => Ok(text(ctx.get_cookie(name))),
+            (ExpressionVariable::Query(name), _) => Ok(text(ctx.get_query_param(name))),
+        }
+    }
+}
+
+/// Price computed by an [expression](Expression) over declared `variables`, the named groups
+/// of `path_regex` (as text) and the `words` of the document (as number); the expression is
+/// type checked when the config is loaded and must yield a non-negative number in `currency`,
+/// by default the [document currency](super::CurrencySelection)
+/// # Examples
+/// ```yaml
+/// price_source: !Expression
+///   expression: 'if section == "research" then 5.00 else base * (1 + 0.1 * floor(words / 1000))'
+///   currency: USD
+///   path_regex: '^/(?P<section>[a-z]+)/'
+///   variables:
+///     base: !HtmlNumber div#price:::data-base
+///     plan: !Cookie plan
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "PriceExpressionConfig")]
+pub struct PriceExpression {
+    expression: Expression,
+    currency: Option<CurrencyCode>,
+    path_regex: Option<Regex>,
+    variables: HashMap<String, ExpressionVariable>,
+}
+
+#[derive(Deserialize)]
+struct PriceExpressionConfig {
+    expression: String,
+    #[serde(default)]
+    currency: Option<CurrencyCode>,
+    #[serde(default)]
+    path_regex: Option<String>,
+    #[serde(default)]
+    variables: HashMap<String, ExpressionVariable>,
+}
+
+impl TryFrom<PriceExpressionConfig> for PriceExpression {
+    type Error = String;
+
+    fn try_from(config: PriceExpressionConfig) -> Result<Self, Self::Error> {
+        let path_regex = config
+            .path_regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+
+        let mut types: HashMap<String, ExprType> = HashMap::new();
+        types.insert("words".to_string(), ExprType::Number);
+        for name in path_regex.iter().flat_map(|r| r.capture_names().flatten()) {
+            types.insert(name.to_string(), ExprType::Text);
+        }
+        for (name, variable) in &config.variables {
+            if types.insert(name.clone(), variable.get_type()).is_some() {
+                return Err(format!(
+                    "Variable '{}' is already defined by 'words' or a path capture",
+                    name
+                ));
+            }
+        }
+
+        let expression =
+            Expression::parse(&config.expression, &types).map_err(|e| e.to_string())?;
+        if expression.get_result_type() != ExprType::Number {
+            return Err(format!(
+                "Price expression must yield a number, not {}",
+                expression.get_result_type()
+            ));
+        }
+
+        Ok(PriceExpression {
+            expression,
+            currency: config.currency,
+            path_regex,
+            variables: config.variables,
+        })
+    }
+}
+
+/// Path regexes are equal if their source is
+impl PartialEq for PriceExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
+            && self.currency == other.currency
+            && self.path_regex.as_ref().map(Regex::as_str)
+                == other.path_regex.as_ref().map(Regex::as_str)
+            && self.variables == other.variables
+    }
+}
+
+impl Serialize for PriceExpression {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("expression", self.expression.as_str())?;
+        if let Some(currency) = &self.currency {
+            map.serialize_entry("currency", currency)?;
+        }
+        if let Some(path_regex) = &self.path_regex {
+            map.serialize_entry("path_regex", path_regex.as_str())?;
+        }
+        if !self.variables.is_empty() {
+            // sorted for a stable output
+            let variables: BTreeMap<&String, &ExpressionVariable> = self.variables.iter().collect();
+            map.serialize_entry("variables", &variables)?;
+        }
+        map.end()
+    }
+}
+
+impl PriceExpression {
+    pub fn get_expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    pub fn get_price(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> Result<Money, PriceSourceExtractError> {
+        let mut values = HashMap::new();
+        let words = doc_and_path.get_document().get_word_count();
+        values.insert(
+            "words".to_string(),
+            ExprValue::Number(Decimal::new(words as i128, 0)),
+        );
+
+        if let Some(regex) = &self.path_regex {
+            let captures = regex.captures(doc_and_path.get_url_path_as_str());
+            for name in regex.capture_names().flatten() {
+                let value = captures
+                    .as_ref()
+                    .and_then(|c| c.name(name))
+                    .map_or("", |m| m.as_str());
+                values.insert(name.to_string(), ExprValue::Text(value.to_string()));
+            }
+        }
+        for (name, variable) in &self.variables {
+            values.insert(name.clone(), variable.get_value(doc_and_path)?);
+        }
+
+        let amount = match self.expression.evaluate(&values) {
+            Ok(ExprValue::Number(amount)) => amount,
+            Ok(other) => {
+                return Err(PriceSourceExtractError::ExpressionError(
+                    ExpressionError::Type(format!("expected number, got {}", other.get_type())),
+                ));
+            }
+            Err(e) => return Err(PriceSourceExtractError::ExpressionError(e)),
+        };
+
+        if amount.is_negative() {
+            return Err(PriceSourceExtractError::InvalidPrice(
+                amount.to_string(),
+                MoneyError::InvalidFormat {
+                    value: amount.to_string(),
+                    reason: "price must not be negative".to_string(),
+                },
+            ));
+        }
+
+        let currency = self
+            .currency
+            .as_ref()
+            .or(doc_and_path.get_request_context().get_document_currency())
+            .cloned()
+            .ok_or_else(|| PriceSourceExtractError::UnknownCurrency(amount.to_string()))?;
+
+        Money::from_decimal(&amount, currency)
+            .map_err(|e| PriceSourceExtractError::InvalidPrice(amount.to_string(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paywall_config::RequestContext;
+    use std::str::FromStr;
+
+    const CONFIG: &str = r#"
+    expression: 'if section == "research" then 5.00 else base * (1 + 0.1 * floor(words / 3))'
+    currency: USD
+    path_regex: '^/(?P<section>[a-z]+)/'
+    variables:
+      base: !HtmlNumber div#price:::data-base
+    "#;
+
+    #[test]
+    fn test_expression_price() {
+        let expression: PriceExpression = serde_yml::from_str(CONFIG).unwrap();
+        let page = "<html><body><div id=price data-base=\"2.00\"/><p>one two three four five six seven</p></body></html>";
+
+        let news = DocumentAndPath::new_from_html_and_path_str(page, "/news/a").unwrap();
+        let research = DocumentAndPath::new_from_html_and_path_str(page, "/research/a").unwrap();
+
+        assert_eq!(
+            expression.get_price(&news).unwrap(),
+            Money::from_str("$2.40").unwrap()
+        );
+        assert_eq!(
+            expression.get_price(&research).unwrap(),
+            Money::from_str("$5.00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_expression_missing_number_variable() {
+        let expression: PriceExpression = serde_yml::from_str(CONFIG).unwrap();
+        let doc_and_path =
+            DocumentAndPath::new_from_html_and_path_str("<html><body></body></html>", "/news/a")
+                .unwrap();
+
+        assert!(matches!(
+            expression.get_price(&doc_and_path),
+            Err(PriceSourceExtractError::HtmlAttributeSelectorError(_))
+        ));
+    }
+
+    #[test]
+    fn test_expression_currency_from_config_only() {
+        let config_yml = r#"
+        expression: '2 - discount'
+        variables:
+          discount: !HtmlNumber div#discount:::data-value
+        "#;
+        let expression: PriceExpression = serde_yml::from_str(config_yml).unwrap();
+        let request = |discount: &str, ctx: RequestContext| {
+            let page = format!(
+                "<html><body><div id=discount data-value=\"{}\"/></body></html>",
+                discount
+            );
+            DocumentAndPath::new_from_html_and_path_str(&page, "/a")
+                .unwrap()
+                .with_request_context(ctx)
+        };
+        let preferred = RequestContext::new()
+            .with_preferred_currencies(vec![CurrencyCode::new("GBP").unwrap()]);
+        let configured =
+            RequestContext::new().with_document_currency(Some(CurrencyCode::new("EUR").unwrap()));
+
+        assert!(matches!(
+            expression.get_price(&request("0.5", preferred)),
+            Err(PriceSourceExtractError::UnknownCurrency(_))
+        ));
+        assert_eq!(
+            expression
+                .get_price(&request("0.5", configured.clone()))
+                .unwrap(),
+            Money::from_str("€1.50").unwrap()
+        );
+        assert!(matches!(
+            expression.get_price(&request("3", configured)),
+            Err(PriceSourceExtractError::InvalidPrice(..))
+        ));
+    }
+
+    #[test]
+    fn test_expression_type_checked_at_load() {
+        let not_a_number = r#"
+        expression: 'plan == "pro"'
+        variables:
+          plan: !Cookie plan
+        "#;
+        let text_arithmetic = r#"
+        expression: 'section * 2'
+        path_regex: '^/(?P<section>[a-z]+)/'
+        "#;
+
+        assert!(serde_yml::from_str::<PriceExpression>(not_a_number).is_err());
+        assert!(serde_yml::from_str::<PriceExpression>(text_arithmetic).is_err());
+    }
+}
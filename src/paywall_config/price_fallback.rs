@@ -0,0 +1,292 @@
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+use super::{CurrencyTable, DocumentAndPath, PriceSource, PriceSourceExtractError};
+use crate::money::Money;
+use crate::utils::block_on;
+
+/// Tries [price sources](PriceSource) in order and takes the first price that can be extracted,
+/// optionally clamped to `min` and `max` bounds in the currency of that price; with bounds, a
+/// price in a currency they do not list counts as a failed source
+///
+/// Deserializes either from a list of sources or from a map with `sources`, `min` and `max`
+/// # Examples
+/// ```yaml
+/// price_source: !FirstOf [ !FromHtmlAttribute div#price:::data-price, !Hard $1.00 ]
+/// price_source: !FirstOf
+///   sources:
+///     - !FromMeta { key: price }
+///     - !Hard $1.00
+///   min: $0.50
+///   max: { USD: "$20.00", EUR: "€18.00" }
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct PriceFallback {
+    sources: Vec<PriceSource>,
+    min: Option<CurrencyTable>,
+    max: Option<CurrencyTable>,
+}
+
+impl PriceFallback {
+    pub fn get_price(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> Result<Money, PriceSourceExtractError> {
+        block_on(self.get_price_async(doc_and_path))
+    }
+
+    /// [get_price](Self::get_price) awaiting [custom price sources](super::CustomPriceSource)
+    pub async fn get_price_async(
+        &self,
+        doc_and_path: &DocumentAndPath,
+    ) -> Result<Money, PriceSourceExtractError> {
+        let mut errors = Vec::new();
+
+        for source in &self.sources {
+            match Box::pin(source.get_price_async(doc_and_path))
+                .await
+                .and_then(|price| self.clamp(price))
+            {
+                Ok(price) => return Ok(price),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        Err(PriceSourceExtractError::AllSourcesFailed(errors))
+    }
+
+    pub fn get_sources(&self) -> &[PriceSource] {
+        &self.sources
+    }
+
+    /// Raise the price to `min` or lower it to `max`; a price in a currency a configured bound
+    /// has no entry for is rejected, so that it cannot escape the bounds
+    pub fn clamp(&self, price: Money) -> Result<Money, PriceSourceExtractError> {
+        let bound = |table: &Option<CurrencyTable>| match table {
+            Some(table) => table
+                .get(price.currency_code())
+                .map(|bound| Some(bound.minor_units()))
+                .ok_or_else(|| PriceSourceExtractError::UnboundedCurrency(price.clone())),
+            None => Ok(None),
+        };
+
+        Ok(match (bound(&self.min)?, bound(&self.max)?) {
+            (Some(min), _) if price.minor_units() < min => price.with_minor_units(min),
+            (_, Some(max)) if price.minor_units() > max => price.with_minor_units(max),
+            _ => price,
+        })
+    }
+
+    fn validated<E: de::Error>(
+        sources: Vec<PriceSource>,
+        min: Option<CurrencyTable>,
+        max: Option<CurrencyTable>,
+    ) -> Result<PriceFallback, E> {
+        if sources.is_empty() {
+            return Err(E::custom("FirstOf needs at least one price source"));
+        }
+
+        if let (Some(min), Some(max)) = (&min, &max) {
+            for code in min.get_currency_codes() {
+                let (Some(lower), Some(upper)) = (min.get(code), max.get(code)) else {
+                    continue;
+                };
+                if lower.minor_units() > upper.minor_units() {
+                    return Err(E::custom(format!("min {} is above max {}", lower, upper)));
+                }
+            }
+        }
+
+        Ok(PriceFallback { sources, min, max })
+    }
+}
+
+impl<'de> Deserialize<'de> for PriceFallback {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PriceFallbackVisitor;
+
+        impl<'de> Visitor<'de> for PriceFallbackVisitor {
+            type Value = PriceFallback;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter
+                    .write_str("a list of price sources or a map with 'sources', 'min' and 'max'")
+            }
+
+            fn visit_seq<S>(self, mut seq: S) -> Result<Self::Value, S::Error>
+            where
+                S: SeqAccess<'de>,
+            {
+                let mut sources = Vec::new();
+
+                while let Some(source) = seq.next_element::<PriceSource>()? {
+                    sources.push(source);
+                }
+
+                PriceFallback::validated(sources, None, None)
+            }
+
+            fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                let mut sources = None;
+                let mut min = None;
+                let mut max = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "sources" => sources = Some(map.next_value::<Vec<PriceSource>>()?),
+                        "min" => min = Some(map.next_value::<CurrencyTable>()?),
+                        "max" => max = Some(map.next_value::<CurrencyTable>()?),
+                        _ => {
+                            return Err(de::Error::unknown_field(&key, &["sources", "min", "max"]));
+                        }
+                    }
+                }
+
+                let sources = sources.ok_or_else(|| de::Error::missing_field("sources"))?;
+                PriceFallback::validated(sources, min, max)
+            }
+        }
+
+        deserializer.deserialize_any(PriceFallbackVisitor)
+    }
+}
+
+/// A list of sources without bounds, a map otherwise
+impl Serialize for PriceFallback {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.min.is_none() && self.max.is_none() {
+            return self.sources.serialize(serializer);
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("sources", &self.sources)?;
+        if let Some(min) = &self.min {
+            map.serialize_entry("min", min)?;
+        }
+        if let Some(max) = &self.max {
+            map.serialize_entry("max", max)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn doc(html: &str) -> DocumentAndPath {
+        DocumentAndPath::new_from_html_and_path_str(html, "/a").unwrap()
+    }
+
+    #[test]
+    fn test_first_of_falls_back() {
+        let fallback: PriceFallback = serde_yml::from_str(
+            r#"
+        [ !FromHtmlAttribute div#price:::data-price, !Hard $1.00 ]
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            fallback
+                .get_price(&doc("<html><body></body></html>"))
+                .unwrap(),
+            Money::from_str("$1.00").unwrap()
+        );
+        assert_eq!(
+            fallback
+                .get_price(&doc(
+                    "<html><body><div id=price data-price=\"$2.00\"/></body></html>"
+                ))
+                .unwrap(),
+            Money::from_str("$2.00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_first_of_clamps() {
+        let fallback: PriceFallback = serde_yml::from_str(
+            r#"
+        sources:
+          - !FromHtmlAttribute div#price:::data-price
+        min: $0.50
+        max: { USD: "$20.00" }
+        "#,
+        )
+        .unwrap();
+
+        let typo = doc("<html><body><div id=price data-price=\"$1000\"/></body></html>");
+        let euro = doc("<html><body><div id=price data-price=\"€1000\"/></body></html>");
+
+        assert_eq!(
+            fallback.get_price(&typo).unwrap(),
+            Money::from_str("$20.00").unwrap()
+        );
+        match fallback.get_price(&euro) {
+            Err(PriceSourceExtractError::AllSourcesFailed(errors)) => assert!(matches!(
+                errors[..],
+                [PriceSourceExtractError::UnboundedCurrency(_)]
+            )),
+            other => panic!("Expected UnboundedCurrency, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_first_of_skips_unbounded_currency() {
+        let fallback: PriceFallback = serde_yml::from_str(
+            r#"
+        sources:
+          - !FromHtmlAttribute div#price:::data-price
+          - !Hard { USD: $5.00, EUR: €4.00 }
+        min: { USD: $0.50, EUR: €0.50 }
+        max: $20.00
+        "#,
+        )
+        .unwrap();
+
+        let euro = doc("<html><body><div id=price data-price=\"€1000\"/></body></html>");
+
+        assert_eq!(
+            fallback.get_price(&euro).unwrap(),
+            Money::from_str("$5.00").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_first_of_all_failed() {
+        let fallback: PriceFallback = serde_yml::from_str(
+            r#"
+        [ !FromHtmlAttribute div#price:::data-price, !FromMeta { key: price } ]
+        "#,
+        )
+        .unwrap();
+
+        match fallback.get_price(&doc("<html><body></body></html>")) {
+            Err(PriceSourceExtractError::AllSourcesFailed(errors)) => assert_eq!(errors.len(), 2),
+            _ => panic!("Expected AllSourcesFailed"),
+        }
+    }
+
+    #[test]
+    fn test_first_of_invalid_config() {
+        assert!(serde_yml::from_str::<PriceFallback>("[]").is_err());
+        assert!(
+            serde_yml::from_str::<PriceFallback>(
+                "{ sources: [ !Hard $1.00 ], min: $5.00, max: $1.00 }"
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::money::CurrencyCode;
+
+/// Request data besides path and document that conditions and price sources may depend on
+#[derive(Clone, Debug, Default)]
+pub struct RequestContext {
+    headers: HashMap<String, String>,
+    cookies: HashMap<String, String>,
+    query_params: HashMap<String, String>,
+    preferred_currencies: Vec<CurrencyCode>,
+    document_currency: Option<CurrencyCode>,
+}
+
+impl RequestContext {
+    pub fn new() -> RequestContext {
+        RequestContext::default()
+    }
+
+    /// Header names are matched case-insensitively; a `Cookie` header is also split into cookies
+    pub fn with_header(mut self, name: &str, value: &str) -> RequestContext {
+        if name.eq_ignore_ascii_case("cookie") {
+            for pair in value.split(';') {
+                if let Some((k, v)) = pair.split_once('=') {
+                    self.cookies
+                        .insert(k.trim().to_string(), v.trim().to_string());
+                }
+            }
+        }
+
+        self.headers
+            .insert(name.to_ascii_lowercase(), value.to_string());
+        self
+    }
+
+    pub fn with_cookie(mut self, name: &str, value: &str) -> RequestContext {
+        self.cookies.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_query_param(mut self, name: &str, value: &str) -> RequestContext {
+        self.query_params
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Add all parameters of a query string like `a=1&b=2`, with or without the leading `?`
+    pub fn with_query_string(mut self, query: &str) -> RequestContext {
+        let query = query.strip_prefix('?').unwrap_or(query);
+
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            self.query_params
+                .insert(name.to_string(), value.to_string());
+        }
+
+        self
+    }
+
+    /// Currencies in order of preference, usually set via
+    /// [CurrencySelection](super::CurrencySelection::apply)
+    pub fn with_preferred_currencies(mut self, currencies: Vec<CurrencyCode>) -> RequestContext {
+        self.preferred_currencies = currencies;
+        self
+    }
+
+    /// Currency of document prices written without symbol or code, usually set via
+    /// [CurrencySelection](super::CurrencySelection::apply)
+    pub fn with_document_currency(mut self, currency: Option<CurrencyCode>) -> RequestContext {
+        self.document_currency = currency;
+        self
+    }
+
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn get_cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(String::as_str)
+    }
+
+    pub fn get_query_param(&self, name: &str) -> Option<&str> {
+        self.query_params.get(name).map(String::as_str)
+    }
+
+    pub fn get_preferred_currencies(&self) -> &[CurrencyCode] {
+        &self.preferred_currencies
+    }
+
+    pub fn get_document_currency(&self) -> Option<&CurrencyCode> {
+        self.document_currency.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_case_insensitive() {
+        let ctx = RequestContext::new().with_header("X-Currency", "EUR");
+
+        assert_eq!(ctx.get_header("x-currency"), Some("EUR"));
+        assert_eq!(ctx.get_header("X-CURRENCY"), Some("EUR"));
+    }
+
+    #[test]
+    fn test_cookie_header_is_split() {
+        let ctx = RequestContext::new().with_header("Cookie", "a=1; rustwall_currency=GBP");
+
+        assert_eq!(ctx.get_cookie("a"), Some("1"));
+        assert_eq!(ctx.get_cookie("rustwall_currency"), Some("GBP"));
+    }
+
+    #[test]
+    fn test_query_string() {
+        let ctx = RequestContext::new().with_query_string("?coupon=SPRING&flag");
+
+        assert_eq!(ctx.get_query_param("coupon"), Some("SPRING"));
+        assert_eq!(ctx.get_query_param("flag"), Some(""));
+        assert_eq!(ctx.get_query_param("other"), None);
+    }
+}
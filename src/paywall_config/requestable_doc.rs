@@ -1,17 +1,62 @@
-use html_editor::{parse, Node};
+use html_editor::operation::{Queryable, Selector};
+use html_editor::{Node, parse};
+use std::fmt;
 
-use super::{UrlPath, UrlPathError};
+use super::{MarkdownDoc, MarkdownDocError, RequestContext, UrlPath, UrlPathError};
 
 #[derive(Clone, Debug)]
 pub struct DocumentAndPath {
     document: RequestableDoc,
     url_path: UrlPath,
+    request_context: RequestContext,
 }
 
 #[derive(Debug)]
 pub enum DocumentAndPathError {
     UrlPathInvalidFormat(String),
-    UrlPathOrHtmlError((Option<String>, Option<String>)),
+    UrlPathOrHtmlError((Option<UrlPathError>, Option<String>)),
+    MarkdownError(MarkdownDocError),
+    JsonError(serde_json::Error),
+}
+
+impl fmt::Display for DocumentAndPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DocumentAndPathError::UrlPathInvalidFormat(path) => {
+                write!(f, "Invalid URL path format: {}", path)
+            }
+            DocumentAndPathError::UrlPathOrHtmlError((path_error, html_error)) => {
+                let errors: Vec<String> = [
+                    path_error
+                        .as_ref()
+                        .map(|UrlPathError::InvalidFormat(path)| {
+                            format!("invalid URL path '{}'", path)
+                        }),
+                    html_error
+                        .as_ref()
+                        .map(|e| format!("cannot parse HTML: {}", e)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                write!(f, "Invalid document or path: {}", errors.join("; "))
+            }
+            DocumentAndPathError::MarkdownError(err) => write!(f, "Cannot parse Markdown: {}", err),
+            DocumentAndPathError::JsonError(err) => write!(f, "Cannot parse JSON: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DocumentAndPathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DocumentAndPathError::UrlPathOrHtmlError((Some(err), _)) => Some(err),
+            DocumentAndPathError::MarkdownError(err) => Some(err),
+            DocumentAndPathError::JsonError(err) => Some(err),
+            DocumentAndPathError::UrlPathInvalidFormat(_)
+            | DocumentAndPathError::UrlPathOrHtmlError((None, _)) => None,
+        }
+    }
 }
 
 impl DocumentAndPath {
@@ -25,6 +70,7 @@ impl DocumentAndPath {
             Ok(up) => Ok(DocumentAndPath {
                 document: document.clone(),
                 url_path: up,
+                request_context: RequestContext::default(),
             }),
             Err(UrlPathError::InvalidFormat(e)) => {
                 Err(DocumentAndPathError::UrlPathInvalidFormat(e))
@@ -43,13 +89,13 @@ impl DocumentAndPath {
             (Ok(path), Ok(node)) => Ok(DocumentAndPath {
                 document: RequestableDoc::HtmlNode(node[0].clone()),
                 url_path: path,
+                request_context: RequestContext::default(),
             }),
-            (Err(UrlPathError::InvalidFormat(e)), Ok(_)) => {
-                Err(DocumentAndPathError::UrlPathOrHtmlError((Some(e), None)))
-            }
-            (Err(UrlPathError::InvalidFormat(e1)), Err(e2)) => Err(
-                DocumentAndPathError::UrlPathOrHtmlError((Some(e1), Some(e2.to_string()))),
-            ),
+            (Err(e), Ok(_)) => Err(DocumentAndPathError::UrlPathOrHtmlError((Some(e), None))),
+            (Err(e1), Err(e2)) => Err(DocumentAndPathError::UrlPathOrHtmlError((
+                Some(e1),
+                Some(e2.to_string()),
+            ))),
             (Ok(_), Err(e2)) => Err(DocumentAndPathError::UrlPathOrHtmlError((
                 None,
                 Some(e2.to_string()),
@@ -57,29 +103,167 @@ impl DocumentAndPath {
         }
     }
 
+    pub fn new_from_markdown_and_path_str(
+        markdown_str: &str,
+        path: &str,
+    ) -> Result<DocumentAndPath, DocumentAndPathError> {
+        let url_path = UrlPath::new(path).map_err(|UrlPathError::InvalidFormat(e)| {
+            DocumentAndPathError::UrlPathInvalidFormat(e)
+        })?;
+        let markdown =
+            MarkdownDoc::parse(markdown_str).map_err(DocumentAndPathError::MarkdownError)?;
+
+        Ok(DocumentAndPath {
+            document: RequestableDoc::Markdown(markdown),
+            url_path,
+            request_context: RequestContext::default(),
+        })
+    }
+
+    pub fn new_from_json_and_path_str(
+        json_str: &str,
+        path: &str,
+    ) -> Result<DocumentAndPath, DocumentAndPathError> {
+        let url_path = UrlPath::new(path).map_err(|UrlPathError::InvalidFormat(e)| {
+            DocumentAndPathError::UrlPathInvalidFormat(e)
+        })?;
+        let json = serde_json::from_str(json_str).map_err(DocumentAndPathError::JsonError)?;
+
+        Ok(DocumentAndPath {
+            document: RequestableDoc::Json(json),
+            url_path,
+            request_context: RequestContext::default(),
+        })
+    }
+
+    /// Binary asset like a PDF or audio file, described by its MIME type and size in bytes
+    pub fn new_from_binary_and_path_str(
+        content_type: &str,
+        size: u64,
+        path: &str,
+    ) -> Result<DocumentAndPath, DocumentAndPathError> {
+        let url_path = UrlPath::new(path).map_err(|UrlPathError::InvalidFormat(e)| {
+            DocumentAndPathError::UrlPathInvalidFormat(e)
+        })?;
+
+        Ok(DocumentAndPath {
+            document: RequestableDoc::Binary {
+                content_type: content_type.to_string(),
+                size,
+            },
+            url_path,
+            request_context: RequestContext::default(),
+        })
+    }
+
     pub fn new(document: &RequestableDoc, url_path: &UrlPath) -> DocumentAndPath {
-        return DocumentAndPath {
+        DocumentAndPath {
             document: document.clone(),
             url_path: url_path.clone(),
-        };
+            request_context: RequestContext::default(),
+        }
+    }
+
+    pub fn with_request_context(mut self, request_context: RequestContext) -> DocumentAndPath {
+        self.request_context = request_context;
+        self
     }
 
     pub fn get_document(&self) -> &RequestableDoc {
-        return &self.document;
+        &self.document
     }
 
     pub fn get_url_path(&self) -> &UrlPath {
-        return &self.url_path;
+        &self.url_path
     }
 
     pub fn get_url_path_as_str(&self) -> &str {
-        return self.url_path.get_path();
+        self.url_path.get_path()
+    }
+
+    pub fn get_request_context(&self) -> &RequestContext {
+        &self.request_context
     }
 }
 
 #[derive(Clone, Debug)]
 pub enum RequestableDoc {
     HtmlNode(Node),
+    Markdown(MarkdownDoc),
+    Json(serde_json::Value),
+    Binary { content_type: String, size: u64 },
+}
+
+impl RequestableDoc {
+    /// MIME type without parameters, e.g. `text/html` or `audio/mpeg`
+    pub fn get_content_type(&self) -> &str {
+        match self {
+            RequestableDoc::HtmlNode(_) => "text/html",
+            RequestableDoc::Markdown(_) => "text/markdown",
+            RequestableDoc::Json(_) => "application/json",
+            RequestableDoc::Binary { content_type, .. } => content_type
+                .split(';')
+                .next()
+                .unwrap_or(content_type)
+                .trim(),
+        }
+    }
+
+    /// Number of whitespace separated words of the readable text: HTML text outside of
+    /// `<script>` and `<style>`, the Markdown body or all JSON strings; `0` for binaries
+    pub fn get_word_count(&self) -> usize {
+        match self {
+            RequestableDoc::HtmlNode(node) => count_html_words(node),
+            RequestableDoc::Markdown(markdown) => markdown.get_body().split_whitespace().count(),
+            RequestableDoc::Json(value) => count_json_words(value),
+            RequestableDoc::Binary { .. } => 0,
+        }
+    }
+
+    pub fn get_type_name(&self) -> &'static str {
+        match self {
+            RequestableDoc::HtmlNode(_) => "HTML",
+            RequestableDoc::Markdown(_) => "Markdown",
+            RequestableDoc::Json(_) => "JSON",
+            RequestableDoc::Binary { .. } => "binary",
+        }
+    }
+
+    /// Metadata written by the site generator: the `content` of a `<meta name="rustwall:key">`
+    /// tag in HTML, a scalar front matter value in Markdown
+    pub fn get_meta(&self, key: &str) -> Option<String> {
+        match self {
+            RequestableDoc::HtmlNode(node) => {
+                let name = format!("rustwall:{}", key);
+
+                node.query_all(&Selector::from("meta"))
+                    .iter()
+                    .find(|meta| meta.attrs.iter().any(|(k, v)| k == "name" && *v == name))
+                    .and_then(|meta| meta.attrs.iter().find(|(k, _)| k == "content"))
+                    .map(|(_, v)| v.clone())
+            }
+            RequestableDoc::Markdown(markdown) => markdown.get_front_matter_str(key),
+            RequestableDoc::Json(_) | RequestableDoc::Binary { .. } => None,
+        }
+    }
+}
+
+fn count_html_words(node: &Node) -> usize {
+    match node {
+        Node::Text(text) => text.split_whitespace().count(),
+        Node::Element(element) if element.name == "script" || element.name == "style" => 0,
+        Node::Element(element) => element.children.iter().map(count_html_words).sum(),
+        Node::Comment(_) | Node::Doctype(_) => 0,
+    }
+}
+
+fn count_json_words(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::String(s) => s.split_whitespace().count(),
+        serde_json::Value::Array(items) => items.iter().map(count_json_words).sum(),
+        serde_json::Value::Object(map) => map.values().map(count_json_words).sum(),
+        _ => 0,
+    }
 }
 
 #[cfg(test)]
@@ -87,6 +271,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(unused_variables)]
     fn test_document_and_path_happy_path() {
         let node: Node = Node::new_element(
             "h1",
@@ -101,6 +286,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::single_match)]
     fn test_document_and_path_sad_path() {
         let node: Node = Node::new_element(
             "h1",
@@ -120,6 +306,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::single_match)]
     fn test_document_and_path_from_strs_success() {
         let path = "/test/test";
         let html = "<html><head></head><body></body></html>";
@@ -147,4 +334,97 @@ mod tests {
             _ => {}
         }
     }
+
+    #[test]
+    fn test_document_and_path_from_markdown() {
+        let doc_and_path =
+            DocumentAndPath::new_from_markdown_and_path_str("---\npaywall: true\n---\nBody", "/a")
+                .unwrap();
+
+        match doc_and_path.get_document() {
+            RequestableDoc::Markdown(markdown) => assert_eq!(markdown.get_body(), "Body"),
+            _ => panic!("Expected Markdown document"),
+        }
+    }
+
+    #[test]
+    fn test_get_meta_from_html() {
+        let doc_and_path = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head><meta name=\"paywall\" content=\"false\"><meta name=\"rustwall:paywall\" content=\"true\"><meta name=\"author\" content=\"Jo\"></head><body></body></html>",
+            "/a",
+        )
+        .unwrap();
+        let doc = doc_and_path.get_document();
+
+        assert_eq!(doc.get_meta("paywall").as_deref(), Some("true"));
+        assert_eq!(doc.get_meta("author"), None);
+        assert_eq!(doc.get_meta("price"), None);
+    }
+
+    #[test]
+    fn test_get_meta_from_markdown() {
+        let doc_and_path =
+            DocumentAndPath::new_from_markdown_and_path_str("---\npaywall: true\n---\nBody", "/a")
+                .unwrap();
+
+        assert_eq!(
+            doc_and_path.get_document().get_meta("paywall").as_deref(),
+            Some("true")
+        );
+    }
+
+    #[test]
+    fn test_document_and_path_from_invalid_markdown() {
+        let doc_and_path = DocumentAndPath::new_from_markdown_and_path_str("---\nunclosed", "/a");
+
+        match doc_and_path {
+            Err(DocumentAndPathError::MarkdownError(_)) => {}
+            _ => panic!("Expected MarkdownError"),
+        }
+    }
+
+    #[test]
+    fn test_document_and_path_from_invalid_json() {
+        let doc_and_path = DocumentAndPath::new_from_json_and_path_str("{\"article\":", "/a");
+
+        match doc_and_path {
+            Err(error @ DocumentAndPathError::JsonError(_)) => assert!(
+                std::error::Error::source(&error)
+                    .unwrap()
+                    .downcast_ref::<serde_json::Error>()
+                    .unwrap()
+                    .is_eof()
+            ),
+            _ => panic!("Expected JsonError"),
+        }
+    }
+
+    #[test]
+    fn test_binary_content_type_without_parameters() {
+        let doc_and_path = DocumentAndPath::new_from_binary_and_path_str(
+            "audio/mpeg; charset=binary",
+            1024,
+            "/podcast/1.mp3",
+        )
+        .unwrap();
+
+        assert_eq!(doc_and_path.get_document().get_content_type(), "audio/mpeg");
+    }
+
+    #[test]
+    fn test_word_count() {
+        let html = DocumentAndPath::new_from_html_and_path_str(
+            "<html><head><script>var a = 1;</script></head><body><h1>Two words</h1><p>and three more</p></body></html>",
+            "/a",
+        )
+        .unwrap();
+        let json = DocumentAndPath::new_from_json_and_path_str(
+            r#"{"a": ["one two", {"b": "three"}], "c": 4}"#,
+            "/a",
+        )
+        .unwrap();
+
+        assert_eq!(html.get_document().get_word_count(), 5);
+        assert_eq!(json.get_document().get_word_count(), 3);
+    }
 }
@@ -26,6 +26,21 @@ impl UrlPath {
     pub fn get_path(&self) -> &str {
         &self.path
     }
+
+    /// Path with every byte except `/` and unreserved characters percent-encoded, safe to put
+    /// into a query parameter
+    pub fn percent_encode(&self) -> String {
+        let mut encoded = String::with_capacity(self.path.len());
+        for byte in self.path.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    encoded.push(byte as char)
+                }
+                _ => encoded.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        encoded
+    }
 }
 
 #[cfg(test)]
@@ -47,6 +62,18 @@ mod tests {
         assert_eq!(url_path_complex.get_path(), complex_path);
     }
 
+    #[test]
+    fn test_percent_encode() {
+        let url_path = UrlPath::new("/r/a b&c=d+é%.pdf").unwrap();
+        assert_eq!(
+            url_path.percent_encode(),
+            "/r/a%20b%26c%3Dd%2B%C3%A9%25.pdf"
+        );
+
+        let plain = UrlPath::new("/a/b/c-d_e.f~").unwrap();
+        assert_eq!(plain.percent_encode(), "/a/b/c-d_e.f~");
+    }
+
     #[test]
     fn test_invalid_url_path_no_leading_slash() {
         let path = "articles/123";
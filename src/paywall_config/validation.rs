@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use super::config_version::get_version;
+use super::{
+    ConfigFormat, ConfigLoadError, PaywallConfig, PaywallConfigError, PaywallConfigV1,
+    PaywallElement,
+};
+
+/// Problem found in a paywall config, with 1-based line and column; `file` is set for configs
+/// [checked through the loader](ConfigLoader::validate)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl ConfigIssue {
+    fn from_yaml_error(error: &serde_yml::Error, line_offset: usize) -> ConfigIssue {
+        let (line, column) = error
+            .location()
+            .map_or((1, 1), |l| (l.line() + line_offset, l.column()));
+
+        ConfigIssue {
+            file: None,
+            line,
+            column,
+            message: strip_location(&error.to_string()),
+        }
+    }
+
+    /// Issue of a config that could not be loaded, located in the file at fault
+    pub(super) fn from_load_error(error: &ConfigLoadError) -> ConfigIssue {
+        let file = error.get_file();
+        let (line, column) = match error {
+            ConfigLoadError::Parse { error, .. } => fs::read_to_string(file)
+                .ok()
+                .and_then(|config| error.get_location(&config)),
+            _ => None,
+        }
+        .unwrap_or((1, 1));
+        let message = error.to_string();
+        let message = message
+            .strip_prefix(&format!("{}: ", file.display()))
+            .unwrap_or(&message);
+
+        ConfigIssue {
+            file: Some(file.to_path_buf()),
+            line,
+            column,
+            message: strip_location(message),
+        }
+    }
+}
+
+/// serde_yml appends " at line X column Y" to its messages, which is reported separately
+fn strip_location(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(i) => message[..i].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// Element of the `paths` block sequence: its lines and its text with the leading `-`
+/// blanked, so it parses on its own with unchanged line and column numbers
+struct ElementBlock {
+    lines: Range<usize>,
+    text: String,
+}
+
+/// Check a YAML config and report every problem found instead of only the first one; see
+/// [ConfigLoader::validate] for configs with includes, variables and overlays
+///
+/// The config as a whole is parsed first; if that fails, each element of a block-style `paths`
+/// list is parsed on its own so that errors in several elements are all reported. Element ids
+/// are checked for format and duplicates
+pub fn validate(config_yml: &str) -> Vec<ConfigIssue> {
+    let value = match serde_yml::from_str::<serde_yml::Value>(config_yml) {
+        Ok(value) => value,
+        Err(e) => return vec![ConfigIssue::from_yaml_error(&e, 0)],
+    };
+    if let Err(e) = get_version(&value) {
+        let (line, column) = locate_top_level_key(config_yml, "version");
+        return vec![ConfigIssue {
+            file: None,
+            line,
+            column,
+            message: e.to_string(),
+        }];
+    }
+
+    let blocks = split_path_elements(config_yml);
+
+    match serde_yml::from_str::<PaywallConfigV1>(config_yml) {
+        Ok(config) => {
+            let ids = config
+                .get_elements()
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| {
+                    let id = e.get_id()?;
+                    Some((id.to_string(), locate_id(config_yml, blocks.get(i), id)))
+                })
+                .collect();
+            validate_ids(ids)
+        }
+        Err(e) => {
+            let mut issues = Vec::new();
+            let mut ids = Vec::new();
+
+            for block in &blocks {
+                match serde_yml::from_str::<PaywallElement>(&block.text) {
+                    Ok(element) => {
+                        if let Some(id) = element.get_id() {
+                            ids.push((id.to_string(), locate_id(config_yml, Some(block), id)));
+                        }
+                    }
+                    Err(e) => issues.push(ConfigIssue::from_yaml_error(&e, block.lines.start)),
+                }
+            }
+
+            let whole = ConfigIssue::from_yaml_error(&e, 0);
+            let in_elements = blocks
+                .iter()
+                .any(|b| b.lines.contains(&(whole.line.saturating_sub(1))));
+            if issues.is_empty() || !in_elements {
+                issues.push(whole);
+            }
+
+            issues.extend(validate_ids(ids));
+            issues.sort_by_key(|i| (i.line, i.column));
+            issues
+        }
+    }
+}
+
+/// Check a config in any [format](ConfigFormat); JSON and TOML configs are reported with the
+/// first problem only
+pub fn validate_with_format(config: &str, format: ConfigFormat) -> Vec<ConfigIssue> {
+    if format == ConfigFormat::Yaml {
+        return validate(config);
+    }
+
+    match PaywallConfig::from_str_with_format(config, format) {
+        Ok(_) => Vec::new(),
+        Err(PaywallConfigError::Format(e)) => {
+            let (line, column) = e.get_location(config).unwrap_or((1, 1));
+            vec![ConfigIssue {
+                file: None,
+                line,
+                column,
+                message: strip_location(&e.to_string()),
+            }]
+        }
+        Err(e) => vec![ConfigIssue {
+            file: None,
+            line: 1,
+            column: 1,
+            message: strip_location(&e.to_string()),
+        }],
+    }
+}
+
+/// Check one file of a [loaded](ConfigLoader) config; included YAML files may be a bare list
+/// of elements, which is checked like the `paths` of a config, and files may leave out their
+/// elements when they include them
+pub(super) fn validate_source(config: &str, format: ConfigFormat) -> Vec<ConfigIssue> {
+    let value = match format.parse(config) {
+        Ok(value) => value,
+        Err(_) => return validate_with_format(config, format),
+    };
+    if get_version(&value).is_err() {
+        return validate_with_format(config, format);
+    }
+
+    match (&value, format) {
+        (serde_yml::Value::Sequence(_), ConfigFormat::Yaml) => {
+            validate(&format!("paths:\n{}", config))
+                .into_iter()
+                .map(|issue| ConfigIssue {
+                    line: issue.line.saturating_sub(1).max(1),
+                    ..issue
+                })
+                .collect()
+        }
+        (serde_yml::Value::Mapping(map), ConfigFormat::Yaml) if !map.contains_key("paths") => {
+            validate(&format!("{}\npaths: []\n", config))
+        }
+        (serde_yml::Value::Mapping(map), _) if map.contains_key("paths") => {
+            validate_with_format(config, format)
+        }
+        // checked when the elements are merged
+        _ => Vec::new(),
+    }
+}
+
+fn validate_ids(ids: Vec<(String, (usize, usize))>) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for (id, (line, column)) in ids {
+        if !PaywallConfigV1::is_valid_id(&id) {
+            issues.push(ConfigIssue {
+                file: None,
+                line,
+                column,
+                message: PaywallConfigError::InvalidElementId(id.clone()).to_string(),
+            });
+        }
+        if let Some(first_line) = seen.get(&id) {
+            issues.push(ConfigIssue {
+                file: None,
+                line,
+                column,
+                message: format!(
+                    "{}, first defined on line {}",
+                    PaywallConfigError::DuplicateElementId(id.clone()),
+                    first_line
+                ),
+            });
+        } else {
+            seen.insert(id, line);
+        }
+    }
+
+    issues
+}
+
+/// Line and column of the `id:` value of an element, the element start if not found
+fn locate_id(config_yml: &str, block: Option<&ElementBlock>, id: &str) -> (usize, usize) {
+    let Some(block) = block else {
+        return (1, 1);
+    };
+
+    config_yml
+        .lines()
+        .enumerate()
+        .skip(block.lines.start)
+        .take(block.lines.len())
+        .find_map(|(i, line)| {
+            let key = line.find("id:")?;
+            let before = line[..key].trim_start_matches([' ', '-']);
+            let value = line[key + 3..].trim().trim_matches(['"', '\'']);
+            (before.is_empty() && value == id).then(|| {
+                let column = key + 3 + line[key + 3..].find(|c: char| c != ' ').unwrap_or(0);
+                (i + 1, column + 1)
+            })
+        })
+        .unwrap_or((block.lines.start + 1, 1))
+}
+
+/// Line and column of a top level key, the start of the config if not found
+fn locate_top_level_key(config_yml: &str, key: &str) -> (usize, usize) {
+    let lines: Vec<&str> = config_yml.lines().collect();
+    let top_indent = lines
+        .iter()
+        .find(|l| is_content(l))
+        .map_or(0, |l| indent_of(l));
+
+    lines
+        .iter()
+        .position(|l| {
+            indent_of(l) == top_indent
+                && l.trim_start()
+                    .strip_prefix(key)
+                    .is_some_and(|rest| rest.starts_with(':'))
+        })
+        .map_or((1, 1), |i| (i + 1, top_indent + 1))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+/// Split a block-style `paths:` sequence into its elements; empty for flow style
+/// or no elements
+fn split_path_elements(config_yml: &str) -> Vec<ElementBlock> {
+    let lines: Vec<&str> = config_yml.lines().collect();
+    let Some(top_indent) = lines.iter().find(|l| is_content(l)).map(|l| indent_of(l)) else {
+        return Vec::new();
+    };
+    let Some(paths_line) = lines
+        .iter()
+        .position(|l| indent_of(l) == top_indent && l.trim() == "paths:")
+    else {
+        return Vec::new();
+    };
+
+    let end = lines
+        .iter()
+        .enumerate()
+        .skip(paths_line + 1)
+        .find(|(_, l)| {
+            is_content(l)
+                && (indent_of(l) < top_indent
+                    || (indent_of(l) == top_indent && !l.trim_start().starts_with('-')))
+        })
+        .map_or(lines.len(), |(i, _)| i);
+
+    let Some(item_indent) = lines[paths_line + 1..end]
+        .iter()
+        .find(|l| is_content(l))
+        .filter(|l| l.trim_start().starts_with('-'))
+        .map(|l| indent_of(l))
+    else {
+        return Vec::new();
+    };
+
+    let starts: Vec<usize> = (paths_line + 1..end)
+        .filter(|i| indent_of(lines[*i]) == item_indent && lines[*i].trim_start().starts_with('-'))
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, start)| {
+            let block_end = starts.get(n + 1).copied().unwrap_or(end);
+            let mut text = String::new();
+            for (i, line) in lines[*start..block_end].iter().enumerate() {
+                if i == 0 {
+                    text.push_str(&line.replacen('-', " ", 1));
+                } else {
+                    text.push_str(line);
+                }
+                text.push('\n');
+            }
+
+            ElementBlock {
+                lines: *start..block_end,
+                text,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_config_has_no_issues() {
+        let config_yml = r#"
+paths:
+  - id: premium
+    paywall_conditions:
+      - !HasRegexPath "^/premium/.*$"
+    price_source: !Hard $1.25
+"#;
+
+        assert_eq!(validate(config_yml), vec![]);
+    }
+
+    #[test]
+    fn test_reports_every_broken_element_with_location() {
+        let config_yml = r#"
+paths:
+  - id: a
+    paywall_conditions:
+      - !HasRegexPat "^/a/.*$"
+    price_source: !Hard $1.25
+  - id: b
+    paywall_conditions:
+      - !MatchesCssSelector "div > p"
+    price_source: !Hard $1.25
+  - id: c
+    paywall_conditions: []
+    price_source: !FromHtmlAttribute div#price:data-price
+  - id: d
+    paywall_conditions: []
+    price_source: !Hard 1.25
+"#;
+
+        let issues = validate(config_yml);
+        let lines: Vec<usize> = issues.iter().map(|i| i.line).collect();
+
+        assert_eq!(lines, vec![5, 9, 13, 16], "{:?}", issues);
+        assert!(issues[0].message.contains("HasRegexPat"));
+        assert!(issues[1].message.contains("div > p"));
+        assert_eq!(issues[3].column, 19);
+    }
+
+    #[test]
+    fn test_reports_duplicate_and_invalid_ids() {
+        let config_yml = r#"
+paths:
+  - id: premium
+    paywall_conditions: []
+    price_source: !Hard $1.00
+  - id: premium
+    paywall_conditions: []
+    price_source: !Hard $2.00
+  - id: "bad id"
+    paywall_conditions: []
+    price_source: !Hard $2.00
+"#;
+
+        let issues = validate(config_yml);
+
+        assert_eq!(issues.len(), 2);
+        assert_eq!((issues[0].line, issues[0].column), (6, 9));
+        assert!(issues[0].message.contains("first defined on line 3"));
+        assert_eq!(issues[1].line, 9);
+    }
+
+    #[test]
+    fn test_reports_yaml_syntax_error() {
+        let issues = validate("paths:\n  - id: [unclosed\n");
+
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].line >= 2);
+    }
+
+    #[test]
+    fn test_reports_errors_outside_paths() {
+        let config_yml = r#"
+currency_selection:
+  fallback: EURO
+paths:
+  - paywall_conditions: []
+    price_source: !Hard $1.00
+"#;
+
+        let issues = validate(config_yml);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 3);
+    }
+
+    #[test]
+    fn test_reports_unknown_keys() {
+        let config_yml = r#"
+version: 2
+include: [sections/*.yml]
+curency_selection:
+  fallback: EUR
+paths:
+  - id: a
+    on_eror: !fixed_price $1.00
+    paywall_conditions: []
+    price_source: !Hard $1.00
+"#;
+
+        let issues = validate(config_yml);
+
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert_eq!(issues[0].line, 4);
+        assert!(
+            issues[0]
+                .message
+                .contains("unknown field `curency_selection`")
+        );
+        assert_eq!((issues[1].line, issues[1].column), (8, 5));
+        assert!(issues[1].message.contains("unknown field `on_eror`"));
+    }
+
+    #[test]
+    fn test_version_2_config() {
+        let config_yml = "version: 2\npaths:\n  - id: a\n    paywall_conditions: []\n    price_source: !Hard 1.25\n";
+        let issues = validate(config_yml);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 5);
+
+        let issues = validate("version: 7\npaths: []\n");
+        assert_eq!(issues[0].line, 1);
+        assert!(issues[0].message.contains("version 7"));
+    }
+
+    #[test]
+    fn test_json_and_toml_configs() {
+        let json = r#"{ "paths": [ { "paywall_conditions": [], "price_source": { "!Hard": "$1.25" } } ] }"#;
+        assert_eq!(validate_with_format(json, ConfigFormat::Json), vec![]);
+
+        let issues = validate_with_format("{\n  \"paths\": 5\n}", ConfigFormat::Json);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("invalid type"), "{:?}", issues);
+
+        let issues = validate_with_format("[[paths]\n", ConfigFormat::Toml);
+        assert_eq!(issues[0].line, 1);
+    }
+}
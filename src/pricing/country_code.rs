@@ -0,0 +1,82 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// ISO 3166-1 alpha-2 country code, e.g. `DE`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CountryCode {
+    code: String,
+}
+
+#[derive(Debug, Error)]
+pub enum CountryCodeError {
+    #[error("Invalid country code '{0}': expected two ASCII letters like 'DE'")]
+    InvalidFormat(String),
+}
+
+impl CountryCode {
+    pub fn new(code: &str) -> Result<Self, CountryCodeError> {
+        if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+            Ok(CountryCode {
+                code: code.to_ascii_uppercase(),
+            })
+        } else {
+            Err(CountryCodeError::InvalidFormat(code.to_string()))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.code
+    }
+}
+
+impl fmt::Display for CountryCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+impl FromStr for CountryCode {
+    type Err = CountryCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CountryCode::new(s)
+    }
+}
+
+impl Serialize for CountryCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CountryCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CountryCodeVisitor;
+
+        impl Visitor<'_> for CountryCodeVisitor {
+            type Value = CountryCode;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an ISO 3166-1 alpha-2 country code like 'DE'")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                CountryCode::new(value).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_string(CountryCodeVisitor)
+    }
+}
@@ -0,0 +1,543 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+use crate::money::{Decimal, Money, MoneyError};
+use crate::paywall_config::RequestContext;
+use crate::utils::{PathGlob, deserialize_optional_timestamp};
+
+/// Reduction of a price, either relative (`!Percent 25%`) or absolute (`!Fixed $1.00`)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Discount {
+    Percent(Decimal),
+    Fixed(Money),
+}
+
+impl Discount {
+    /// Amount taken off `price`, capped at the price itself; `None` if a fixed discount is in
+    /// another currency
+    pub fn amount_off(&self, price: &Money) -> Result<Option<Money>, MoneyError> {
+        let amount = match self {
+            Discount::Percent(percent) => price.checked_mul(percent)?,
+            Discount::Fixed(fixed) if fixed.currency_code() == price.currency_code() => {
+                fixed.clone()
+            }
+            Discount::Fixed(_) => return Ok(None),
+        };
+
+        if amount > *price {
+            return Ok(Some(price.clone()));
+        }
+
+        Ok(Some(amount))
+    }
+}
+
+/// Coupon code readers enter via query parameter or cookie
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiscountCode {
+    code: String,
+    discount: Discount,
+    /// Ids of the paywall elements the code is valid for; empty means all elements
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    elements: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_uses: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    expires: Option<u64>,
+}
+
+/// Discount applied automatically on matching paths during a time window
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Promotion {
+    name: String,
+    discount: Discount,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    paths: Vec<PathGlob>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    elements: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    starts: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_timestamp",
+        skip_serializing_if = "Option::is_none"
+    )]
+    until: Option<u64>,
+}
+
+/// Counts how often each discount code has been redeemed
+pub trait DiscountUsageStore: Send + Sync {
+    fn get_uses(&self, code: &str) -> u64;
+
+    /// Count one use unless `max_uses` is reached; returns whether the use was counted
+    fn try_record_use(&self, code: &str, max_uses: Option<u64>) -> bool;
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryDiscountUsageStore {
+    uses: Mutex<HashMap<String, u64>>,
+}
+
+impl DiscountUsageStore for InMemoryDiscountUsageStore {
+    fn get_uses(&self, code: &str) -> u64 {
+        let uses = self.uses.lock().unwrap_or_else(|e| e.into_inner());
+        uses.get(code).copied().unwrap_or(0)
+    }
+
+    fn try_record_use(&self, code: &str, max_uses: Option<u64>) -> bool {
+        let mut uses = self.uses.lock().unwrap_or_else(|e| e.into_inner());
+        let count = uses.entry(code.to_string()).or_insert(0);
+
+        if max_uses.is_some_and(|max| *count >= max) {
+            return false;
+        }
+
+        *count += 1;
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscountSource {
+    Code(String),
+    Promotion(String),
+}
+
+impl fmt::Display for DiscountSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiscountSource::Code(code) => write!(f, "code '{}'", code),
+            DiscountSource::Promotion(name) => write!(f, "promotion '{}'", name),
+        }
+    }
+}
+
+/// Original price, discount and final price handed to templates and payment
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscountedPrice {
+    pub original: Money,
+    pub discount: Money,
+    pub final_price: Money,
+    pub source: Option<DiscountSource>,
+}
+
+#[derive(Debug, Error)]
+pub enum DiscountError {
+    #[error("Duplicate discount code '{0}'")]
+    DuplicateCode(String),
+    #[error("Discount code '{0}' is unknown or no longer valid")]
+    InvalidCode(String),
+    #[error("Discount code '{0}' has reached its usage limit")]
+    UsageLimitReached(String),
+    #[error("Invalid discount {0}: percentages must be between 0% and 100%")]
+    InvalidPercent(Decimal),
+    #[error("Cannot compute discount: {0}")]
+    Money(#[from] MoneyError),
+}
+
+/// Discount codes and promotions applied after the [price source](crate::paywall_config::PriceSource)
+/// and before tax and payment; the single largest applicable discount wins
+/// # Examples
+/// ```yaml
+/// discounts:
+///   code_query_param: coupon
+///   code_cookie: rustwall_coupon
+///   codes:
+///     - code: SPRING25
+///       discount: !Percent 25%
+///       elements: [premium]
+///       max_uses: 100
+///       expires: 2025-05-01T00:00:00Z
+///   promotions:
+///     - name: archive-sale
+///       paths: ["/archive/**"]
+///       discount: !Percent 50%
+///       until: 2025-06-01T00:00:00Z
+/// ```
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(try_from = "DiscountConfigFile")]
+pub struct DiscountConfig {
+    code_query_param: String,
+    code_cookie: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    codes: Vec<DiscountCode>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    promotions: Vec<Promotion>,
+    #[serde(skip)]
+    usage_store: Arc<dyn DiscountUsageStore>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiscountConfigFile {
+    #[serde(default = "default_code_query_param")]
+    code_query_param: String,
+    #[serde(default = "default_code_cookie")]
+    code_cookie: String,
+    #[serde(default)]
+    codes: Vec<DiscountCode>,
+    #[serde(default)]
+    promotions: Vec<Promotion>,
+}
+
+fn default_code_query_param() -> String {
+    "coupon".to_string()
+}
+
+fn default_code_cookie() -> String {
+    "rustwall_coupon".to_string()
+}
+
+impl TryFrom<DiscountConfigFile> for DiscountConfig {
+    type Error = DiscountError;
+
+    fn try_from(file: DiscountConfigFile) -> Result<Self, Self::Error> {
+        let mut seen = HashSet::new();
+
+        for code in &file.codes {
+            if !seen.insert(code.code.to_ascii_uppercase()) {
+                return Err(DiscountError::DuplicateCode(code.code.clone()));
+            }
+        }
+
+        let discounts = file
+            .codes
+            .iter()
+            .map(|c| &c.discount)
+            .chain(file.promotions.iter().map(|p| &p.discount));
+
+        for discount in discounts {
+            if let Discount::Percent(percent) = discount
+                && (*percent < Decimal::ZERO || *percent > Decimal::ONE)
+            {
+                return Err(DiscountError::InvalidPercent(*percent));
+            }
+        }
+
+        Ok(DiscountConfig {
+            code_query_param: file.code_query_param,
+            code_cookie: file.code_cookie,
+            codes: file.codes,
+            promotions: file.promotions,
+            usage_store: Arc::new(InMemoryDiscountUsageStore::default()),
+        })
+    }
+}
+
+/// Compares the settings only, not the usage store
+impl PartialEq for DiscountConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.code_query_param == other.code_query_param
+            && self.code_cookie == other.code_cookie
+            && self.codes == other.codes
+            && self.promotions == other.promotions
+    }
+}
+
+impl fmt::Debug for DiscountConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DiscountConfig")
+            .field("code_query_param", &self.code_query_param)
+            .field("code_cookie", &self.code_cookie)
+            .field("codes", &self.codes)
+            .field("promotions", &self.promotions)
+            .finish_non_exhaustive()
+    }
+}
+
+fn applies_to_element(elements: &[String], element_id: Option<&str>) -> bool {
+    elements.is_empty() || element_id.is_some_and(|id| elements.iter().any(|e| e == id))
+}
+
+fn is_active(starts: Option<u64>, until: Option<u64>, now: u64) -> bool {
+    starts.is_none_or(|s| now >= s) && until.is_none_or(|u| now < u)
+}
+
+impl DiscountConfig {
+    /// Replace the default in-memory usage counter, e.g. with a shared database
+    pub fn set_usage_store(&mut self, usage_store: Arc<dyn DiscountUsageStore>) {
+        self.usage_store = usage_store;
+    }
+
+    pub fn get_usage_store(&self) -> &Arc<dyn DiscountUsageStore> {
+        &self.usage_store
+    }
+
+    /// Code entered by the reader, query parameter before cookie
+    pub fn get_requested_code<'a>(&self, ctx: &'a RequestContext) -> Option<&'a str> {
+        ctx.get_query_param(&self.code_query_param)
+            .or_else(|| ctx.get_cookie(&self.code_cookie))
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+    }
+
+    fn find_code(&self, code: &str) -> Option<&DiscountCode> {
+        self.codes
+            .iter()
+            .find(|c| c.code.eq_ignore_ascii_case(code))
+    }
+
+    fn is_code_usable(&self, code: &DiscountCode, element_id: Option<&str>, now: u64) -> bool {
+        let has_uses_left = code
+            .max_uses
+            .is_none_or(|max| self.usage_store.get_uses(&code.code) < max);
+
+        applies_to_element(&code.elements, element_id)
+            && is_active(None, code.expires, now)
+            && has_uses_left
+    }
+
+    pub fn apply(
+        &self,
+        price: &Money,
+        element_id: Option<&str>,
+        path: &str,
+        ctx: &RequestContext,
+    ) -> Result<DiscountedPrice, DiscountError> {
+        self.apply_at(price, element_id, path, ctx, crate::utils::unix_now())
+    }
+
+    /// [apply](Self::apply) at a given unix time
+    pub fn apply_at(
+        &self,
+        price: &Money,
+        element_id: Option<&str>,
+        path: &str,
+        ctx: &RequestContext,
+        now: u64,
+    ) -> Result<DiscountedPrice, DiscountError> {
+        let mut candidates: Vec<(DiscountSource, &Discount)> = Vec::new();
+
+        if let Some(code) = self
+            .get_requested_code(ctx)
+            .and_then(|c| self.find_code(c))
+            .filter(|c| self.is_code_usable(c, element_id, now))
+        {
+            candidates.push((DiscountSource::Code(code.code.clone()), &code.discount));
+        }
+
+        for promotion in &self.promotions {
+            let matches_path =
+                promotion.paths.is_empty() || promotion.paths.iter().any(|g| g.is_match(path));
+
+            if matches_path
+                && applies_to_element(&promotion.elements, element_id)
+                && is_active(promotion.starts, promotion.until, now)
+            {
+                candidates.push((
+                    DiscountSource::Promotion(promotion.name.clone()),
+                    &promotion.discount,
+                ));
+            }
+        }
+
+        let mut best = DiscountedPrice {
+            original: price.clone(),
+            discount: Money::zero(price.currency_code().clone()),
+            final_price: price.clone(),
+            source: None,
+        };
+
+        for (source, discount) in candidates {
+            let Some(amount) = discount.amount_off(price)? else {
+                continue;
+            };
+
+            if amount > best.discount {
+                best = DiscountedPrice {
+                    original: price.clone(),
+                    final_price: price.checked_sub(&amount)?,
+                    discount: amount,
+                    source: Some(source),
+                };
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// Count the use of an applied discount code once the reader pays
+    pub fn redeem(&self, discounted: &DiscountedPrice) -> Result<(), DiscountError> {
+        let Some(DiscountSource::Code(code)) = &discounted.source else {
+            return Ok(());
+        };
+
+        let config = self
+            .find_code(code)
+            .ok_or_else(|| DiscountError::InvalidCode(code.clone()))?;
+
+        if !self
+            .usage_store
+            .try_record_use(&config.code, config.max_uses)
+        {
+            return Err(DiscountError::UsageLimitReached(code.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_timestamp;
+
+    fn money(s: &str) -> Money {
+        s.parse().unwrap()
+    }
+
+    fn discount_config() -> DiscountConfig {
+        let config_yml = r#"
+        codes:
+          - code: SPRING25
+            discount: !Percent 25%
+            elements: [premium]
+            max_uses: 1
+            expires: 2025-05-01T00:00:00Z
+          - code: DOLLAR
+            discount: !Fixed $1.00
+        promotions:
+          - name: archive-sale
+            paths: ["/archive/**"]
+            discount: !Percent 50%
+            until: 2025-06-01T00:00:00Z
+        "#;
+
+        serde_yml::from_str(config_yml).unwrap()
+    }
+
+    fn april() -> u64 {
+        parse_timestamp("2025-04-15").unwrap()
+    }
+
+    #[test]
+    fn test_code_from_query() {
+        let config = discount_config();
+        let ctx = RequestContext::new().with_query_param("coupon", "spring25");
+
+        let discounted = config
+            .apply_at(
+                &money("$2.00"),
+                Some("premium"),
+                "/premium/a",
+                &ctx,
+                april(),
+            )
+            .unwrap();
+
+        assert_eq!(discounted.original, money("$2.00"));
+        assert_eq!(discounted.discount, money("$0.50"));
+        assert_eq!(discounted.final_price, money("$1.50"));
+        assert_eq!(
+            discounted.source,
+            Some(DiscountSource::Code("SPRING25".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_code_restricted_to_element_and_expiry() {
+        let config = discount_config();
+        let ctx = RequestContext::new().with_cookie("rustwall_coupon", "SPRING25");
+
+        let other_element = config
+            .apply_at(&money("$2.00"), Some("basic"), "/basic/a", &ctx, april())
+            .unwrap();
+        let expired = config
+            .apply_at(
+                &money("$2.00"),
+                Some("premium"),
+                "/premium/a",
+                &ctx,
+                parse_timestamp("2025-05-02").unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(other_element.final_price, money("$2.00"));
+        assert_eq!(expired.final_price, money("$2.00"));
+    }
+
+    #[test]
+    fn test_best_discount_wins() {
+        let config = discount_config();
+        let ctx = RequestContext::new().with_query_param("coupon", "DOLLAR");
+
+        let discounted = config
+            .apply_at(&money("$3.00"), None, "/archive/2020/post", &ctx, april())
+            .unwrap();
+
+        assert_eq!(discounted.final_price, money("$1.50"));
+        assert_eq!(
+            discounted.source,
+            Some(DiscountSource::Promotion("archive-sale".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_fixed_discount_capped_and_currency_bound() {
+        let config = discount_config();
+        let ctx = RequestContext::new().with_query_param("coupon", "DOLLAR");
+
+        let capped = config
+            .apply_at(&money("$0.50"), None, "/a", &ctx, april())
+            .unwrap();
+        let other_currency = config
+            .apply_at(&money("€0.50"), None, "/a", &ctx, april())
+            .unwrap();
+
+        assert_eq!(capped.final_price, money("$0.00"));
+        assert_eq!(other_currency.final_price, money("€0.50"));
+    }
+
+    #[test]
+    fn test_usage_limit() {
+        let config = discount_config();
+        let ctx = RequestContext::new().with_query_param("coupon", "SPRING25");
+        let apply = || {
+            config
+                .apply_at(
+                    &money("$2.00"),
+                    Some("premium"),
+                    "/premium/a",
+                    &ctx,
+                    april(),
+                )
+                .unwrap()
+        };
+
+        let first = apply();
+        config.redeem(&first).unwrap();
+        let second = apply();
+
+        assert_eq!(second.final_price, money("$2.00"));
+        assert!(matches!(
+            config.redeem(&first),
+            Err(DiscountError::UsageLimitReached(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_codes_rejected() {
+        let config_yml = r#"
+        codes:
+          - { code: A, discount: !Percent 10% }
+          - { code: a, discount: !Percent 20% }
+        "#;
+
+        let config: Result<DiscountConfig, _> = serde_yml::from_str(config_yml);
+
+        assert!(config.is_err());
+    }
+}
@@ -0,0 +1,782 @@
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+use crate::money::{Decimal, DecimalError};
+
+/// Deepest nesting of parentheses, operators and `if`s accepted by the parser
+const MAX_DEPTH: usize = 64;
+/// Longest expression accepted, which also bounds chains like `1 + 1 + ... + 1`
+const MAX_TOKENS: usize = 512;
+
+/// Small pricing language without loops, recursion or side effects, so evaluation always
+/// terminates; expressions are type checked before they are evaluated
+///
+/// Supports decimal numbers, strings, `true`/`false`, `+ - * /`, comparisons,
+/// `and`/`or`/`not`, `if ... then ... else ...` and the functions `floor`, `ceil`, `round`,
+/// `min` and `max`
+/// # Examples
+/// ```
+/// use rustwall::pricing::{Expression, ExprType, ExprValue};
+/// use std::collections::HashMap;
+///
+/// let types = HashMap::from([("words".to_string(), ExprType::Number)]);
+/// let expr = Expression::parse("2 * (1 + 0.1 * floor(words / 1000))", &types).unwrap();
+///
+/// let values = HashMap::from([("words".to_string(), ExprValue::Number("2500".parse().unwrap()))]);
+/// assert_eq!(expr.evaluate(&values).unwrap(), ExprValue::Number("2.4".parse().unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Expr,
+    result_type: ExprType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExprType {
+    Number,
+    Text,
+    Bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprValue {
+    Number(Decimal),
+    Text(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ExpressionError {
+    #[error("Syntax error at position {position}: {message}")]
+    Syntax { position: usize, message: String },
+    #[error("Expression nested deeper than {MAX_DEPTH} levels")]
+    TooDeep,
+    #[error("Expression longer than {MAX_TOKENS} tokens")]
+    TooLong,
+    #[error("Unknown variable '{0}'")]
+    UnknownVariable(String),
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("Type error: {0}")]
+    Type(String),
+    #[error("Variable '{0}' has no value")]
+    MissingValue(String),
+    #[error("Arithmetic error: {0}")]
+    Arithmetic(#[from] DecimalError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(ExprValue),
+    Variable(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+impl ExprType {
+    fn name(&self) -> &'static str {
+        match self {
+            ExprType::Number => "number",
+            ExprType::Text => "text",
+            ExprType::Bool => "bool",
+        }
+    }
+}
+
+impl fmt::Display for ExprType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl ExprValue {
+    pub fn get_type(&self) -> ExprType {
+        match self {
+            ExprValue::Number(_) => ExprType::Number,
+            ExprValue::Text(_) => ExprType::Text,
+            ExprValue::Bool(_) => ExprType::Bool,
+        }
+    }
+}
+
+impl Expression {
+    /// Parse and type check against the types of all variables the expression may use
+    pub fn parse(
+        source: &str,
+        variable_types: &HashMap<String, ExprType>,
+    ) -> Result<Expression, ExpressionError> {
+        let tokens = tokenize(source)?;
+        if tokens.len() > MAX_TOKENS {
+            return Err(ExpressionError::TooLong);
+        }
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let root = parser.parse_expr()?;
+
+        if let Some((position, token)) = parser.tokens.get(parser.pos) {
+            return Err(ExpressionError::Syntax {
+                position: *position,
+                message: format!("unexpected {}", token),
+            });
+        }
+
+        let result_type = type_of(&root, variable_types)?;
+
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+            result_type,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn get_result_type(&self) -> ExprType {
+        self.result_type
+    }
+
+    /// Evaluate with values for the variables; values must have the types given to
+    /// [parse](Self::parse)
+    pub fn evaluate(
+        &self,
+        values: &HashMap<String, ExprValue>,
+    ) -> Result<ExprValue, ExpressionError> {
+        evaluate(&self.root, values)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Decimal),
+    Text(String),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Text(s) => write!(f, "string \"{}\"", s),
+            Token::Ident(i) => write!(f, "'{}'", i),
+            Token::Symbol(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 15] = [
+    "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "(", ")", ",", "!", "=",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek() {
+                if !(d.is_ascii_digit() || d == '.') {
+                    break;
+                }
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            let number = source[start..end]
+                .parse()
+                .map_err(|_| ExpressionError::Syntax {
+                    position: start,
+                    message: format!("invalid number '{}'", &source[start..end]),
+                })?;
+            tokens.push((start, Token::Number(number)));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek() {
+                if !(d.is_alphanumeric() || d == '_') {
+                    break;
+                }
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            tokens.push((start, Token::Ident(source[start..end].to_string())));
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, d)) if d == c => break,
+                    Some((_, d)) => text.push(d),
+                    None => {
+                        return Err(ExpressionError::Syntax {
+                            position: start,
+                            message: "unterminated string".to_string(),
+                        });
+                    }
+                }
+            }
+            tokens.push((start, Token::Text(text)));
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| source[start..].starts_with(*s))
+                .ok_or_else(|| ExpressionError::Syntax {
+                    position: start,
+                    message: format!("unexpected character '{}'", c),
+                })?;
+            if *symbol == "=" || *symbol == "!" {
+                return Err(ExpressionError::Syntax {
+                    position: start,
+                    message: format!("unexpected '{}', use '==', '!=' or 'not'", symbol),
+                });
+            }
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push((start, Token::Symbol(symbol)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(0, |(p, _)| *p)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(i)) if i == keyword)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ExpressionError> {
+        if self.is_keyword(keyword) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", keyword)))
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), ExpressionError> {
+        if self.is_symbol(symbol) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn error(&self, message: &str) -> ExpressionError {
+        let found = self
+            .peek()
+            .map_or("end of expression".to_string(), Token::to_string);
+
+        ExpressionError::Syntax {
+            position: self.position(),
+            message: format!("{}, found {}", message, found),
+        }
+    }
+
+    fn nested<F>(&mut self, f: F) -> Result<Expr, ExpressionError>
+    where
+        F: FnOnce(&mut Parser) -> Result<Expr, ExpressionError>,
+    {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ExpressionError::TooDeep);
+        }
+        let expr = f(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ExpressionError> {
+        self.nested(|p| {
+            if !p.is_keyword("if") {
+                return p.parse_or();
+            }
+
+            p.pos += 1;
+            let condition = p.parse_expr()?;
+            p.expect_keyword("then")?;
+            let then_branch = p.parse_expr()?;
+            p.expect_keyword("else")?;
+            let else_branch = p.parse_expr()?;
+
+            Ok(Expr::If(
+                Box::new(condition),
+                Box::new(then_branch),
+                Box::new(else_branch),
+            ))
+        })
+    }
+
+    fn parse_binary<F>(
+        &mut self,
+        operators: &[(&str, BinaryOp)],
+        keywords: bool,
+        mut operand: F,
+    ) -> Result<Expr, ExpressionError>
+    where
+        F: FnMut(&mut Parser) -> Result<Expr, ExpressionError>,
+    {
+        let mut left = operand(self)?;
+
+        loop {
+            let op = operators.iter().find(|(token, _)| {
+                if keywords {
+                    self.is_keyword(token)
+                } else {
+                    self.is_symbol(token)
+                }
+            });
+            let Some((_, op)) = op else {
+                return Ok(left);
+            };
+
+            self.pos += 1;
+            let right = operand(self)?;
+            left = Expr::Binary(*op, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ExpressionError> {
+        self.parse_binary(&[("or", BinaryOp::Or)], true, Parser::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ExpressionError> {
+        self.parse_binary(&[("and", BinaryOp::And)], true, Parser::parse_not)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, ExpressionError> {
+        if self.is_keyword("not") {
+            self.pos += 1;
+            return self.nested(|p| Ok(Expr::Not(Box::new(p.parse_not()?))));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ExpressionError> {
+        let left = self.parse_additive()?;
+        let operators = [
+            ("==", BinaryOp::Eq),
+            ("!=", BinaryOp::NotEq),
+            ("<=", BinaryOp::LtEq),
+            (">=", BinaryOp::GtEq),
+            ("<", BinaryOp::Lt),
+            (">", BinaryOp::Gt),
+        ];
+
+        match operators.iter().find(|(token, _)| self.is_symbol(token)) {
+            Some((_, op)) => {
+                self.pos += 1;
+                let right = self.parse_additive()?;
+                Ok(Expr::Binary(*op, Box::new(left), Box::new(right)))
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ExpressionError> {
+        self.parse_binary(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            false,
+            Parser::parse_multiplicative,
+        )
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ExpressionError> {
+        self.parse_binary(
+            &[("*", BinaryOp::Mul), ("/", BinaryOp::Div)],
+            false,
+            Parser::parse_unary,
+        )
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ExpressionError> {
+        if self.is_symbol("-") {
+            self.pos += 1;
+            return self.nested(|p| Ok(Expr::Negate(Box::new(p.parse_unary()?))));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ExpressionError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.error("expected a value"));
+        };
+
+        match token {
+            Token::Number(n) => {
+                self.pos += 1;
+                Ok(Expr::Literal(ExprValue::Number(n)))
+            }
+            Token::Text(s) => {
+                self.pos += 1;
+                Ok(Expr::Literal(ExprValue::Text(s)))
+            }
+            Token::Symbol("(") => {
+                self.pos += 1;
+                let expr = self.parse_expr()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Token::Ident(ident) => match ident.as_str() {
+                "true" | "false" => {
+                    self.pos += 1;
+                    Ok(Expr::Literal(ExprValue::Bool(ident == "true")))
+                }
+                "if" | "then" | "else" | "and" | "or" | "not" => {
+                    Err(self.error("expected a value"))
+                }
+                _ => {
+                    self.pos += 1;
+                    if !self.is_symbol("(") {
+                        return Ok(Expr::Variable(ident));
+                    }
+
+                    self.pos += 1;
+                    let mut args = Vec::new();
+                    if !self.is_symbol(")") {
+                        args.push(self.parse_expr()?);
+                        while self.is_symbol(",") {
+                            self.pos += 1;
+                            args.push(self.parse_expr()?);
+                        }
+                    }
+                    self.expect_symbol(")")?;
+                    Ok(Expr::Call(ident, args))
+                }
+            },
+            Token::Symbol(_) => Err(self.error("expected a value")),
+        }
+    }
+}
+
+fn type_error(message: String) -> ExpressionError {
+    ExpressionError::Type(message)
+}
+
+fn type_of(expr: &Expr, types: &HashMap<String, ExprType>) -> Result<ExprType, ExpressionError> {
+    let expect = |expr: &Expr, expected: ExprType, context: &str| {
+        let actual = type_of(expr, types)?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(type_error(format!(
+                "{} expects {}, got {}",
+                context, expected, actual
+            )))
+        }
+    };
+
+    match expr {
+        Expr::Literal(value) => Ok(value.get_type()),
+        Expr::Variable(name) => types
+            .get(name)
+            .copied()
+            .ok_or_else(|| ExpressionError::UnknownVariable(name.clone())),
+        Expr::Negate(inner) => {
+            expect(inner, ExprType::Number, "'-'")?;
+            Ok(ExprType::Number)
+        }
+        Expr::Not(inner) => {
+            expect(inner, ExprType::Bool, "'not'")?;
+            Ok(ExprType::Bool)
+        }
+        Expr::Binary(op, left, right) => match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+                expect(left, ExprType::Number, "arithmetic")?;
+                expect(right, ExprType::Number, "arithmetic")?;
+                Ok(ExprType::Number)
+            }
+            BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => {
+                expect(left, ExprType::Number, "ordering comparison")?;
+                expect(right, ExprType::Number, "ordering comparison")?;
+                Ok(ExprType::Bool)
+            }
+            BinaryOp::Eq | BinaryOp::NotEq => {
+                let left_type = type_of(left, types)?;
+                expect(right, left_type, "comparison with a value of that type")?;
+                Ok(ExprType::Bool)
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                expect(left, ExprType::Bool, "'and'/'or'")?;
+                expect(right, ExprType::Bool, "'and'/'or'")?;
+                Ok(ExprType::Bool)
+            }
+        },
+        Expr::If(condition, then_branch, else_branch) => {
+            expect(condition, ExprType::Bool, "'if' condition")?;
+            let then_type = type_of(then_branch, types)?;
+            expect(
+                else_branch,
+                then_type,
+                "'else' branch of the same type as 'then'",
+            )?;
+            Ok(then_type)
+        }
+        Expr::Call(name, args) => {
+            let arity_ok = match name.as_str() {
+                "floor" | "ceil" | "round" => args.len() == 1,
+                "min" | "max" => !args.is_empty(),
+                _ => return Err(ExpressionError::UnknownFunction(name.clone())),
+            };
+            if !arity_ok {
+                return Err(type_error(format!(
+                    "wrong number of arguments for '{}'",
+                    name
+                )));
+            }
+            for arg in args {
+                expect(arg, ExprType::Number, &format!("'{}'", name))?;
+            }
+            Ok(ExprType::Number)
+        }
+    }
+}
+
+fn evaluate(
+    expr: &Expr,
+    values: &HashMap<String, ExprValue>,
+) -> Result<ExprValue, ExpressionError> {
+    let number = |expr: &Expr| match evaluate(expr, values)? {
+        ExprValue::Number(n) => Ok(n),
+        other => Err(type_error(format!(
+            "expected number, got {}",
+            other.get_type()
+        ))),
+    };
+    let boolean = |expr: &Expr| match evaluate(expr, values)? {
+        ExprValue::Bool(b) => Ok(b),
+        other => Err(type_error(format!(
+            "expected bool, got {}",
+            other.get_type()
+        ))),
+    };
+
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Variable(name) => values
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ExpressionError::MissingValue(name.clone())),
+        Expr::Negate(inner) => Ok(ExprValue::Number(
+            Decimal::ZERO.checked_sub(&number(inner)?)?,
+        )),
+        Expr::Not(inner) => Ok(ExprValue::Bool(!boolean(inner)?)),
+        Expr::Binary(op, left, right) => match op {
+            BinaryOp::Add => Ok(ExprValue::Number(
+                number(left)?.checked_add(&number(right)?)?,
+            )),
+            BinaryOp::Sub => Ok(ExprValue::Number(
+                number(left)?.checked_sub(&number(right)?)?,
+            )),
+            BinaryOp::Mul => Ok(ExprValue::Number(
+                number(left)?.checked_mul(&number(right)?)?,
+            )),
+            BinaryOp::Div => Ok(ExprValue::Number(
+                number(left)?.checked_div(&number(right)?)?,
+            )),
+            BinaryOp::Lt => Ok(ExprValue::Bool(number(left)? < number(right)?)),
+            BinaryOp::LtEq => Ok(ExprValue::Bool(number(left)? <= number(right)?)),
+            BinaryOp::Gt => Ok(ExprValue::Bool(number(left)? > number(right)?)),
+            BinaryOp::GtEq => Ok(ExprValue::Bool(number(left)? >= number(right)?)),
+            BinaryOp::Eq => Ok(ExprValue::Bool(
+                evaluate(left, values)? == evaluate(right, values)?,
+            )),
+            BinaryOp::NotEq => Ok(ExprValue::Bool(
+                evaluate(left, values)? != evaluate(right, values)?,
+            )),
+            BinaryOp::And => Ok(ExprValue::Bool(boolean(left)? && boolean(right)?)),
+            BinaryOp::Or => Ok(ExprValue::Bool(boolean(left)? || boolean(right)?)),
+        },
+        Expr::If(condition, then_branch, else_branch) => {
+            if boolean(condition)? {
+                evaluate(then_branch, values)
+            } else {
+                evaluate(else_branch, values)
+            }
+        }
+        Expr::Call(name, args) => {
+            let args = args.iter().map(number).collect::<Result<Vec<_>, _>>()?;
+
+            let result = match name.as_str() {
+                "floor" => Decimal::new(args[0].floor(), 0),
+                "ceil" => Decimal::new(-Decimal::ZERO.checked_sub(&args[0])?.floor(), 0),
+                "round" => Decimal::new(args[0].round(), 0),
+                "min" => args.into_iter().min().unwrap_or(Decimal::ZERO),
+                "max" => args.into_iter().max().unwrap_or(Decimal::ZERO),
+                _ => return Err(ExpressionError::UnknownFunction(name.clone())),
+            };
+            Ok(ExprValue::Number(result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn types() -> HashMap<String, ExprType> {
+        HashMap::from([
+            ("base".to_string(), ExprType::Number),
+            ("words".to_string(), ExprType::Number),
+            ("section".to_string(), ExprType::Text),
+        ])
+    }
+
+    fn values(section: &str, words: &str) -> HashMap<String, ExprValue> {
+        HashMap::from([
+            ("base".to_string(), ExprValue::Number(decimal("2.00"))),
+            ("words".to_string(), ExprValue::Number(decimal(words))),
+            ("section".to_string(), ExprValue::Text(section.to_string())),
+        ])
+    }
+
+    #[test]
+    fn test_arithmetic_and_functions() {
+        let expr = Expression::parse("base * (1 + 0.1 * floor(words / 1000))", &types()).unwrap();
+
+        assert_eq!(
+            expr.evaluate(&values("news", "2500")).unwrap(),
+            ExprValue::Number(decimal("2.4"))
+        );
+        assert_eq!(
+            Expression::parse("max(ceil(1.2), round(2.5), -min(1, 3))", &types())
+                .unwrap()
+                .evaluate(&values("news", "0"))
+                .unwrap(),
+            ExprValue::Number(decimal("3"))
+        );
+    }
+
+    #[test]
+    fn test_if_then_else() {
+        let expr = Expression::parse(
+            r#"if section == "research" and not words < 100 then 5.00 else 1.00"#,
+            &types(),
+        )
+        .unwrap();
+
+        assert_eq!(expr.get_result_type(), ExprType::Number);
+        assert_eq!(
+            expr.evaluate(&values("research", "500")).unwrap(),
+            ExprValue::Number(decimal("5"))
+        );
+        assert_eq!(
+            expr.evaluate(&values("news", "500")).unwrap(),
+            ExprValue::Number(decimal("1"))
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        assert!(matches!(
+            Expression::parse("section * 2", &types()),
+            Err(ExpressionError::Type(_))
+        ));
+        assert!(matches!(
+            Expression::parse("if words then 1 else 2", &types()),
+            Err(ExpressionError::Type(_))
+        ));
+        assert!(matches!(
+            Expression::parse("if true then 1 else \"2\"", &types()),
+            Err(ExpressionError::Type(_))
+        ));
+        assert!(matches!(
+            Expression::parse("author == \"x\"", &types()),
+            Err(ExpressionError::UnknownVariable(_))
+        ));
+        assert!(matches!(
+            Expression::parse("sqrt(2)", &types()),
+            Err(ExpressionError::UnknownFunction(_))
+        ));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert!(matches!(
+            Expression::parse("1 +", &types()),
+            Err(ExpressionError::Syntax { .. })
+        ));
+        assert!(matches!(
+            Expression::parse("section = \"a\"", &types()),
+            Err(ExpressionError::Syntax { position: 8, .. })
+        ));
+        assert!(matches!(
+            Expression::parse("1 2", &types()),
+            Err(ExpressionError::Syntax { position: 2, .. })
+        ));
+        assert_eq!(
+            Expression::parse(
+                &format!("{}1{}", "(".repeat(100), ")".repeat(100)),
+                &types()
+            ),
+            Err(ExpressionError::TooDeep)
+        );
+        assert_eq!(
+            Expression::parse(&"1 + ".repeat(300), &types()),
+            Err(ExpressionError::TooLong)
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let expr = Expression::parse("base / (words - words)", &types()).unwrap();
+
+        assert_eq!(
+            expr.evaluate(&values("news", "5")),
+            Err(ExpressionError::Arithmetic(DecimalError::DivisionByZero))
+        );
+    }
+}
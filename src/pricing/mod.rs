@@ -0,0 +1,12 @@
+pub mod country_code;
+pub mod discount;
+pub mod expression;
+pub mod tax;
+
+pub use country_code::{CountryCode, CountryCodeError};
+pub use discount::{
+    Discount, DiscountCode, DiscountConfig, DiscountError, DiscountSource, DiscountUsageStore,
+    DiscountedPrice, InMemoryDiscountUsageStore, Promotion,
+};
+pub use expression::{ExprType, ExprValue, Expression, ExpressionError};
+pub use tax::{TaxBreakdown, TaxConfig, TaxDisplay, TaxError};
@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use thiserror::Error;
+
+use super::CountryCode;
+use crate::money::{Decimal, Money, MoneyError};
+use crate::paywall_config::RequestContext;
+use crate::utils::resolve_config_path;
+
+/// Whether prices are shown to the reader with or without tax
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxDisplay {
+    #[default]
+    Inclusive,
+    Exclusive,
+}
+
+/// Tax (e.g. VAT) applied on top of the net prices of [price sources](crate::paywall_config::PriceSource),
+/// at the rate of the reader's country
+/// # Examples
+/// ```yaml
+/// tax:
+///   country_header: CF-IPCountry
+///   default_country: DE
+///   overlay_display: inclusive
+///   checkout_display: exclusive
+///   rates:
+///     DE: 19%
+///     FR: 0.20
+///   rates_file: vat_rates.yml
+/// ```
+///
+/// `rates_file` is relative to the config file; serializes with its rates inlined
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "TaxConfigFile")]
+pub struct TaxConfig {
+    country_header: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country_cookie: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_country: Option<CountryCode>,
+    overlay_display: TaxDisplay,
+    checkout_display: TaxDisplay,
+    #[serde(serialize_with = "serialize_sorted_rates")]
+    rates: HashMap<CountryCode, Decimal>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaxConfigFile {
+    #[serde(default = "default_country_header")]
+    country_header: String,
+    #[serde(default)]
+    country_cookie: Option<String>,
+    #[serde(default)]
+    default_country: Option<CountryCode>,
+    #[serde(default)]
+    overlay_display: TaxDisplay,
+    #[serde(default)]
+    checkout_display: TaxDisplay,
+    #[serde(default)]
+    rates: HashMap<CountryCode, Decimal>,
+    #[serde(default)]
+    rates_file: Option<String>,
+}
+
+fn serialize_sorted_rates<S>(
+    rates: &HashMap<CountryCode, Decimal>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    rates
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+fn default_country_header() -> String {
+    "X-Rustwall-Country".to_string()
+}
+
+#[derive(Debug, Error)]
+pub enum TaxError {
+    #[error("Cannot read tax rates file '{path}': {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Cannot parse tax rates: {0}")]
+    Yaml(#[from] serde_yml::Error),
+    #[error("Invalid tax rate {rate} for {country}: rates must be between 0 and 1")]
+    InvalidRate { country: CountryCode, rate: Decimal },
+    #[error("Cannot compute tax: {0}")]
+    Money(#[from] MoneyError),
+}
+
+impl TryFrom<TaxConfigFile> for TaxConfig {
+    type Error = TaxError;
+
+    fn try_from(file: TaxConfigFile) -> Result<Self, Self::Error> {
+        let mut rates = HashMap::new();
+
+        if let Some(path) = &file.rates_file {
+            let path = resolve_config_path(path);
+            let content = fs::read_to_string(&path).map_err(|source| TaxError::Io {
+                path: path.display().to_string(),
+                source,
+            })?;
+            let file_rates: HashMap<CountryCode, Decimal> = serde_yml::from_str(&content)?;
+            rates.extend(file_rates);
+        }
+
+        // inline rates override the shared table
+        rates.extend(file.rates);
+
+        for (country, rate) in &rates {
+            if *rate < Decimal::ZERO || *rate > Decimal::ONE {
+                return Err(TaxError::InvalidRate {
+                    country: country.clone(),
+                    rate: *rate,
+                });
+            }
+        }
+
+        Ok(TaxConfig {
+            country_header: file.country_header,
+            country_cookie: file.country_cookie,
+            default_country: file.default_country,
+            overlay_display: file.overlay_display,
+            checkout_display: file.checkout_display,
+            rates,
+        })
+    }
+}
+
+/// Net price, tax and gross price for one reader
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxBreakdown {
+    pub net: Money,
+    pub tax: Money,
+    pub gross: Money,
+    pub rate: Decimal,
+    pub country: Option<CountryCode>,
+}
+
+impl TaxBreakdown {
+    pub fn display_price(&self, display: TaxDisplay) -> &Money {
+        match display {
+            TaxDisplay::Inclusive => &self.gross,
+            TaxDisplay::Exclusive => &self.net,
+        }
+    }
+}
+
+impl TaxConfig {
+    /// Country of the reader from header, cookie or the configured default
+    pub fn get_country(&self, ctx: &RequestContext) -> Option<CountryCode> {
+        let from_cookie = self
+            .country_cookie
+            .as_deref()
+            .and_then(|c| ctx.get_cookie(c));
+
+        [ctx.get_header(&self.country_header), from_cookie]
+            .into_iter()
+            .flatten()
+            .find_map(|value| CountryCode::new(value.trim()).ok())
+            .or_else(|| self.default_country.clone())
+    }
+
+    /// Tax rate of a country; countries without a rate are taxed at zero
+    pub fn get_rate(&self, country: Option<&CountryCode>) -> Decimal {
+        country
+            .and_then(|c| self.rates.get(c))
+            .copied()
+            .unwrap_or(Decimal::ZERO)
+    }
+
+    pub fn compute(&self, net: &Money, ctx: &RequestContext) -> Result<TaxBreakdown, TaxError> {
+        let country = self.get_country(ctx);
+        let rate = self.get_rate(country.as_ref());
+        let tax = net.checked_mul(&rate)?;
+        let gross = net.checked_add(&tax)?;
+
+        Ok(TaxBreakdown {
+            net: net.clone(),
+            tax,
+            gross,
+            rate,
+            country,
+        })
+    }
+
+    pub fn get_overlay_display(&self) -> TaxDisplay {
+        self.overlay_display
+    }
+
+    pub fn get_checkout_display(&self) -> TaxDisplay {
+        self.checkout_display
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(s: &str) -> Money {
+        s.parse().unwrap()
+    }
+
+    fn tax_config() -> TaxConfig {
+        let config_yml = r#"
+        country_header: CF-IPCountry
+        default_country: DE
+        checkout_display: exclusive
+        rates:
+          DE: 19%
+          FR: 0.2
+          LU: "17%"
+        "#;
+
+        serde_yml::from_str(config_yml).unwrap()
+    }
+
+    #[test]
+    fn test_compute_from_header_country() {
+        let config = tax_config();
+        let ctx = RequestContext::new().with_header("CF-IPCountry", "fr");
+
+        let breakdown = config.compute(&money("€2.50"), &ctx).unwrap();
+
+        assert_eq!(breakdown.country, Some(CountryCode::new("FR").unwrap()));
+        assert_eq!(breakdown.tax, money("€0.50"));
+        assert_eq!(breakdown.gross, money("€3.00"));
+    }
+
+    #[test]
+    fn test_compute_default_country_and_rounding() {
+        let config = tax_config();
+
+        let breakdown = config
+            .compute(&money("€0.99"), &RequestContext::new())
+            .unwrap();
+
+        assert_eq!(breakdown.tax, money("€0.19"));
+        assert_eq!(breakdown.gross, money("€1.18"));
+        assert_eq!(
+            breakdown.display_price(config.get_overlay_display()),
+            &money("€1.18")
+        );
+        assert_eq!(
+            breakdown.display_price(config.get_checkout_display()),
+            &money("€0.99")
+        );
+    }
+
+    #[test]
+    fn test_unknown_country_is_untaxed() {
+        let config = tax_config();
+        let ctx = RequestContext::new().with_header("CF-IPCountry", "US");
+
+        let breakdown = config.compute(&money("$1.00"), &ctx).unwrap();
+
+        assert_eq!(breakdown.gross, money("$1.00"));
+        assert!(breakdown.tax.is_zero());
+    }
+
+    #[test]
+    fn test_invalid_rate_rejected() {
+        let config: Result<TaxConfig, _> = serde_yml::from_str("rates: { DE: 19 }");
+
+        assert!(config.is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Run a future to completion on the current thread, parking it while the future is pending
+///
+/// No async runtime is started, so futures that need one (e.g. tokio I/O) must be spawned on
+/// their runtime and awaited through a channel
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// Future that is pending until a background thread has sent a value and woken it
+    struct Delayed {
+        receiver: mpsc::Receiver<u32>,
+        started: bool,
+    }
+
+    impl Future for Delayed {
+        type Output = u32;
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            if let Ok(value) = self.receiver.try_recv() {
+                return Poll::Ready(value);
+            }
+            if !self.started {
+                self.started = true;
+                let waker = cx.waker().clone();
+                let (sender, receiver) = mpsc::channel();
+                self.receiver = receiver;
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    sender.send(42).unwrap();
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_block_on_ready_future() {
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+    }
+
+    #[test]
+    fn test_block_on_waits_for_wake() {
+        let (_, receiver) = mpsc::channel();
+        let delayed = Delayed {
+            receiver,
+            started: false,
+        };
+
+        assert_eq!(block_on(async { delayed.await + 1 }), 43);
+    }
+}
@@ -0,0 +1,45 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    static CONFIG_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with relative paths in the config, like `rates_file`, resolved against `dir`
+pub fn with_config_dir<T>(dir: &Path, f: impl FnOnce() -> T) -> T {
+    let previous = CONFIG_DIR.with(|d| d.replace(Some(dir.to_path_buf())));
+    let result = f();
+    CONFIG_DIR.with(|d| d.replace(previous));
+    result
+}
+
+/// `path` relative to the directory of the config being read, or as is outside of
+/// [with_config_dir]
+pub fn resolve_config_path(path: &str) -> PathBuf {
+    CONFIG_DIR.with(|d| match d.borrow().as_deref() {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_config_path() {
+        assert_eq!(resolve_config_path("rates.yml"), Path::new("rates.yml"));
+
+        with_config_dir(Path::new("/etc/rustwall"), || {
+            assert_eq!(
+                resolve_config_path("rates.yml"),
+                Path::new("/etc/rustwall/rates.yml")
+            );
+            assert_eq!(
+                resolve_config_path("/srv/rates.yml"),
+                Path::new("/srv/rates.yml")
+            );
+        });
+        assert_eq!(resolve_config_path("rates.yml"), Path::new("rates.yml"));
+    }
+}
@@ -0,0 +1,156 @@
+use html_editor::operation::{Queryable, Selector};
+use html_editor::{Element, Node};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CssSelectorError {
+    #[error("Empty CSS selector")]
+    Empty,
+    #[error(
+        "Unsupported CSS selector '{selector}' at position {position}: only tags, '#id', '.class' and lists like 'h1, div.a' are supported"
+    )]
+    Unsupported { selector: String, position: usize },
+}
+
+/// Parse a CSS selector, rejecting syntax that [Selector::from] would silently misread, like
+/// descendant combinators (`div span`) or attribute selectors (`a[target]`)
+pub fn parse_css_selector(selector: &str) -> Result<Selector, CssSelectorError> {
+    if selector.trim().is_empty() {
+        return Err(CssSelectorError::Empty);
+    }
+
+    let mut offset = 0;
+    for compound in selector.split(',') {
+        let start = offset + (compound.len() - compound.trim_start().len());
+        validate_compound(compound.trim()).map_err(|position| CssSelectorError::Unsupported {
+            selector: selector.to_string(),
+            position: start + position,
+        })?;
+        offset += compound.len() + 1;
+    }
+
+    Ok(Selector::from(selector))
+}
+
+/// [Validated](parse_css_selector) CSS selector that keeps its source text
+#[derive(Debug)]
+pub struct CssSelector {
+    source: String,
+    selector: Selector,
+}
+
+impl CssSelector {
+    pub fn new(selector: &str) -> Result<CssSelector, CssSelectorError> {
+        Ok(CssSelector {
+            source: selector.to_string(),
+            selector: parse_css_selector(selector)?,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// First element of the node matching the selector
+    pub fn query<'a>(&self, html_node: &'a Node) -> Option<&'a Element> {
+        html_node.query(&self.selector)
+    }
+}
+
+/// Selectors are equal if their source text is
+impl PartialEq for CssSelector {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Display for CssSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for CssSelector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let selector = String::deserialize(deserializer)?;
+        CssSelector::new(&selector).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for CssSelector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+/// Byte position of the first invalid character of a compound selector like `div#id.class`
+fn validate_compound(compound: &str) -> Result<(), usize> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+
+    if compound.is_empty() {
+        return Err(0);
+    }
+
+    let mut expect_name = !compound.starts_with(['#', '.']);
+    let mut name_len = 0;
+
+    for (i, c) in compound.char_indices() {
+        match c {
+            '#' | '.' if !expect_name || name_len > 0 => {
+                expect_name = true;
+                name_len = 0;
+            }
+            _ if expect_name && is_name_char(c) => name_len += 1,
+            _ => return Err(i),
+        }
+    }
+
+    if name_len == 0 {
+        return Err(compound.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_selectors() {
+        for selector in ["div", "#id", ".class", "div#test.a-b", "h1, h2", "meta"] {
+            assert!(parse_css_selector(selector).is_ok(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        assert_eq!(
+            parse_css_selector(" ").unwrap_err(),
+            CssSelectorError::Empty
+        );
+
+        for (selector, position) in [
+            ("div span", 3),
+            ("a[target=_blank]", 1),
+            ("div > p", 3),
+            ("div#", 4),
+            ("h1,", 3),
+            ("div..a", 4),
+        ] {
+            match parse_css_selector(selector) {
+                Err(CssSelectorError::Unsupported { position: p, .. }) => {
+                    assert_eq!(p, position, "{}", selector)
+                }
+                _ => panic!("Expected '{}' to be unsupported", selector),
+            }
+        }
+    }
+}
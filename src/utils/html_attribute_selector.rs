@@ -1,14 +1,14 @@
-use html_editor::operation::{Queryable, Selector};
-use html_editor::{parse, Node};
+use html_editor::Node;
 use serde::de::{self, Visitor};
-use serde::{Deserialize, Deserializer};
-use std::error::Error as StdError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::str::FromStr;
 
-#[derive(Debug)]
+use super::{CssSelector, CssSelectorError};
+
+#[derive(Debug, PartialEq)]
 pub struct HtmlAttributeSelector {
-    html_selector: Selector,
+    html_selector: CssSelector,
     attribute_name: String,
 }
 
@@ -43,33 +43,50 @@ impl fmt::Display for HtmlAttributeSelectorError {
     }
 }
 
+impl std::error::Error for HtmlAttributeSelectorError {}
+
 impl HtmlAttributeSelector {
+    pub fn new(
+        html_selector: &str,
+        attribute_name: &str,
+    ) -> Result<HtmlAttributeSelector, CssSelectorError> {
+        Ok(HtmlAttributeSelector {
+            html_selector: CssSelector::new(html_selector)?,
+            attribute_name: attribute_name.to_string(),
+        })
+    }
+
     pub fn get_attribute<T>(&self, html_node: &Node) -> Result<T, HtmlAttributeSelectorError>
     where
         T: FromStr,
     {
-        let target_element = html_node
-            .query(&self.html_selector)
+        let target_element = self
+            .html_selector
+            .query(html_node)
             .ok_or(HtmlAttributeSelectorError::ElementNotFound)?;
 
         let attrs = &target_element.attrs;
 
         let target_attribute = attrs
-            .into_iter()
-            .find(|x| x.0 == self.attribute_name.clone())
-            .map(|x| (&x.1).to_string())
+            .iter()
+            .find(|x| x.0 == self.attribute_name)
+            .map(|x| x.1.to_string())
             .ok_or(HtmlAttributeSelectorError::AttributeNotFound(
                 self.attribute_name.clone(),
             ))?;
 
-        let target_output = target_attribute.parse::<T>().map_err(|_| {
-            HtmlAttributeSelectorError::ConversionError {
+        target_attribute
+            .parse::<T>()
+            .map_err(|_| HtmlAttributeSelectorError::ConversionError {
                 value: target_attribute.to_string(),
                 target_type: std::any::type_name::<T>().to_string(),
-            }
-        });
+            })
+    }
+}
 
-        return target_output;
+impl fmt::Display for HtmlAttributeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:::{}", self.html_selector, self.attribute_name)
     }
 }
 
@@ -97,13 +114,13 @@ impl<'de> Deserialize<'de> for HtmlAttributeSelector {
                     return Err(de::Error::invalid_length(parts.len(), &self));
                 }
 
-                let html_selector = Selector::from(parts[0]);
+                let html_selector = CssSelector::new(parts[0]).map_err(de::Error::custom)?;
                 let attribute_name = parts[1].to_string();
 
-                return Ok(HtmlAttributeSelector {
+                Ok(HtmlAttributeSelector {
                     html_selector,
                     attribute_name,
-                });
+                })
             }
         }
 
@@ -111,9 +128,19 @@ impl<'de> Deserialize<'de> for HtmlAttributeSelector {
     }
 }
 
+impl Serialize for HtmlAttributeSelector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use html_editor::parse;
 
     #[test]
     fn test_deserialize_html_attribute_selector_valid_input() {
@@ -194,6 +221,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(unused_variables)]
     fn test_get_attribute_conversion_impossible() {
         let config_yml = r#"
         div#test:::data-test
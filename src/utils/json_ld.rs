@@ -0,0 +1,198 @@
+use html_editor::operation::{Queryable, Selector};
+use html_editor::{Element, Node};
+use serde_json::Value;
+use std::fmt;
+
+/// Price and currency of the first offer of a JSON-LD object embedded in
+/// `<script type="application/ld+json">`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonLdOffer {
+    pub price: String,
+    pub currency: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum JsonLdError {
+    InvalidJson(String),
+    TypeNotFound(String),
+    OfferNotFound(String),
+    InvalidPrice(Value),
+    InvalidCurrency(String),
+}
+
+impl fmt::Display for JsonLdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonLdError::InvalidJson(err) => write!(f, "Invalid JSON-LD script: {}", err),
+            JsonLdError::TypeNotFound(ld_type) => {
+                write!(f, "No JSON-LD object with @type '{}'", ld_type)
+            }
+            JsonLdError::OfferNotFound(ld_type) => {
+                write!(f, "JSON-LD object '{}' has no offers with a price", ld_type)
+            }
+            JsonLdError::InvalidPrice(price) => {
+                write!(f, "JSON-LD offer price {} is not a number or string", price)
+            }
+            JsonLdError::InvalidCurrency(currency) => {
+                write!(
+                    f,
+                    "JSON-LD priceCurrency '{}' is not an ISO-4217 code",
+                    currency
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonLdError {}
+
+/// Find the first JSON-LD object of `ld_type`, also inside arrays and `@graph`, and read
+/// `offers.price` and `offers.priceCurrency`; `offers` may be a single offer or a list
+///
+/// Scripts that are not valid JSON are skipped; their error is only returned if no other script
+/// has an object of `ld_type`
+pub fn find_json_ld_offer(html_node: &Node, ld_type: &str) -> Result<JsonLdOffer, JsonLdError> {
+    let scripts = html_node.query_all(&Selector::from("script"));
+    let mut invalid_json = None;
+
+    for script in scripts.iter().filter(|s| is_json_ld(s)) {
+        let text: String = script
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Node::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        let value: Value = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            Err(e) => {
+                invalid_json.get_or_insert(JsonLdError::InvalidJson(e.to_string()));
+                continue;
+            }
+        };
+
+        if let Some(object) = find_by_type(&value, ld_type) {
+            return read_offer(object, ld_type);
+        }
+    }
+
+    Err(invalid_json.unwrap_or_else(|| JsonLdError::TypeNotFound(ld_type.to_string())))
+}
+
+fn is_json_ld(script: &Element) -> bool {
+    script
+        .attrs
+        .iter()
+        .any(|(k, v)| k == "type" && v.eq_ignore_ascii_case("application/ld+json"))
+}
+
+fn has_type(value: &Value, ld_type: &str) -> bool {
+    match value.get("@type") {
+        Some(Value::String(t)) => t == ld_type,
+        Some(Value::Array(types)) => types.iter().any(|t| t == ld_type),
+        _ => false,
+    }
+}
+
+fn find_by_type<'a>(value: &'a Value, ld_type: &str) -> Option<&'a Value> {
+    match value {
+        Value::Array(items) => items.iter().find_map(|v| find_by_type(v, ld_type)),
+        Value::Object(_) if has_type(value, ld_type) => Some(value),
+        Value::Object(map) => map.get("@graph").and_then(|g| find_by_type(g, ld_type)),
+        _ => None,
+    }
+}
+
+fn read_offer(object: &Value, ld_type: &str) -> Result<JsonLdOffer, JsonLdError> {
+    let offer = match object.get("offers") {
+        Some(Value::Array(offers)) => offers.iter().find(|o| o.get("price").is_some()),
+        Some(offer) => Some(offer).filter(|o| o.get("price").is_some()),
+        None => None,
+    }
+    .ok_or_else(|| JsonLdError::OfferNotFound(ld_type.to_string()))?;
+
+    let price = match &offer["price"] {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        other => return Err(JsonLdError::InvalidPrice(other.clone())),
+    };
+    let currency = offer
+        .get("priceCurrency")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+
+    Ok(JsonLdOffer { price, currency })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use html_editor::parse;
+
+    fn page(json_ld: &str) -> Node {
+        let html = format!(
+            "<html><head><script type=\"application/ld+json\">{}</script></head><body></body></html>",
+            json_ld
+        );
+        parse(&html).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn test_find_offer_in_graph() {
+        let node = page(
+            r#"{"@graph": [{"@type": "WebPage"}, {"@type": ["Product", "Article"], "offers": [{"price": 2.5, "priceCurrency": "EUR"}]}]}"#,
+        );
+
+        assert_eq!(
+            find_json_ld_offer(&node, "Product").unwrap(),
+            JsonLdOffer {
+                price: "2.5".to_string(),
+                currency: Some("EUR".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn test_type_and_offer_not_found() {
+        let node = page(r#"{"@type": "Product", "name": "Report"}"#);
+
+        assert!(matches!(
+            find_json_ld_offer(&node, "Article"),
+            Err(JsonLdError::TypeNotFound(_))
+        ));
+        assert!(matches!(
+            find_json_ld_offer(&node, "Product"),
+            Err(JsonLdError::OfferNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_skips_invalid_json_scripts() {
+        let html = concat!(
+            "<html><head>",
+            r#"<script type="application/ld+json">{"@type": "WebPage",</script>"#,
+            r#"<script type="application/ld+json">{"@type": "Product", "offers": {"price": 3}}</script>"#,
+            "</head><body></body></html>"
+        );
+        let node = parse(html).unwrap()[0].clone();
+
+        assert_eq!(
+            find_json_ld_offer(&node, "Product").unwrap(),
+            JsonLdOffer {
+                price: "3".to_string(),
+                currency: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let node = page(r#"{"@type": "Product","#);
+
+        assert!(matches!(
+            find_json_ld_offer(&node, "Product"),
+            Err(JsonLdError::InvalidJson(_))
+        ));
+    }
+}
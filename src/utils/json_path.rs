@@ -0,0 +1,416 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use thiserror::Error;
+
+/// JSONPath-style selector supporting member access, array indices and wildcards
+/// # Examples
+/// ```yaml
+/// $.article.body
+/// $.article['price-eur']
+/// $.article.paragraphs[*].text
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    path: String,
+    segments: Vec<JsonPathSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum JsonPathError {
+    #[error("JSONPath '{0}' must start with '$'")]
+    MissingRoot(String),
+    #[error("Invalid JSONPath '{path}' at position {position}")]
+    InvalidSyntax { path: String, position: usize },
+    #[error("Invalid comparison value '{0}': expected a JSON literal")]
+    InvalidLiteral(String),
+}
+
+impl JsonPath {
+    pub fn new(path: &str) -> Result<JsonPath, JsonPathError> {
+        let path = path.trim();
+        let rest = path
+            .strip_prefix('$')
+            .ok_or_else(|| JsonPathError::MissingRoot(path.to_string()))?;
+        let invalid = |position: usize| JsonPathError::InvalidSyntax {
+            path: path.to_string(),
+            position,
+        };
+
+        let mut segments = Vec::new();
+        let mut pos = 0;
+        let bytes = rest.as_bytes();
+
+        while pos < rest.len() {
+            match bytes[pos] {
+                b'.' => {
+                    let end = rest[pos + 1..]
+                        .find(['.', '['])
+                        .map_or(rest.len(), |i| pos + 1 + i);
+                    let key = &rest[pos + 1..end];
+
+                    if key.is_empty() {
+                        return Err(invalid(pos + 2));
+                    }
+                    segments.push(if key == "*" {
+                        JsonPathSegment::Wildcard
+                    } else {
+                        JsonPathSegment::Key(key.to_string())
+                    });
+                    pos = end;
+                }
+                b'[' => {
+                    let end = rest[pos..].find(']').ok_or(invalid(pos + 1))? + pos;
+                    let inner = rest[pos + 1..end].trim();
+
+                    let segment = if inner == "*" {
+                        JsonPathSegment::Wildcard
+                    } else if let Some(key) = inner
+                        .strip_prefix('\'')
+                        .and_then(|k| k.strip_suffix('\''))
+                        .or_else(|| inner.strip_prefix('"').and_then(|k| k.strip_suffix('"')))
+                    {
+                        JsonPathSegment::Key(key.to_string())
+                    } else {
+                        JsonPathSegment::Index(inner.parse().map_err(|_| invalid(pos + 2))?)
+                    };
+                    segments.push(segment);
+                    pos = end + 1;
+                }
+                _ => return Err(invalid(pos + 1)),
+            }
+        }
+
+        Ok(JsonPath {
+            path: path.to_string(),
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    /// All values the path points to; empty if nothing matches
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![value];
+
+        for segment in &self.segments {
+            current = current
+                .into_iter()
+                .flat_map(|v| -> Vec<&Value> {
+                    match (segment, v) {
+                        (JsonPathSegment::Key(key), Value::Object(map)) => {
+                            map.get(key).into_iter().collect()
+                        }
+                        (JsonPathSegment::Index(i), Value::Array(items)) => {
+                            items.get(*i).into_iter().collect()
+                        }
+                        (JsonPathSegment::Wildcard, Value::Object(map)) => map.values().collect(),
+                        (JsonPathSegment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+
+        current
+    }
+
+    pub fn select_first<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.select(value).into_iter().next()
+    }
+
+    /// Apply `f` to every value the path points to
+    pub fn for_each_mut<F>(&self, value: &mut Value, mut f: F)
+    where
+        F: FnMut(&mut Value),
+    {
+        Self::visit_mut(&self.segments, value, &mut f);
+    }
+
+    fn visit_mut<F>(segments: &[JsonPathSegment], value: &mut Value, f: &mut F)
+    where
+        F: FnMut(&mut Value),
+    {
+        let Some((segment, rest)) = segments.split_first() else {
+            f(value);
+            return;
+        };
+
+        match (segment, value) {
+            (JsonPathSegment::Key(key), Value::Object(map)) => {
+                if let Some(v) = map.get_mut(key) {
+                    Self::visit_mut(rest, v, f);
+                }
+            }
+            (JsonPathSegment::Index(i), Value::Array(items)) => {
+                if let Some(v) = items.get_mut(*i) {
+                    Self::visit_mut(rest, v, f);
+                }
+            }
+            (JsonPathSegment::Wildcard, Value::Object(map)) => {
+                map.values_mut().for_each(|v| Self::visit_mut(rest, v, f));
+            }
+            (JsonPathSegment::Wildcard, Value::Array(items)) => {
+                items.iter_mut().for_each(|v| Self::visit_mut(rest, v, f));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let path = String::deserialize(deserializer)?;
+        JsonPath::new(&path).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for JsonPath {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsonComparison {
+    Equal,
+    NotEqual,
+}
+
+/// [JSONPath](JsonPath) optionally compared to a JSON literal; without a comparison it matches
+/// when any selected value is neither `null` nor `false`
+/// # Examples
+/// ```yaml
+/// $.article.premium == true
+/// $.article.section != "news"
+/// $.article.paywall
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPathPredicate {
+    path: JsonPath,
+    comparison: Option<(JsonComparison, Value)>,
+}
+
+impl JsonPathPredicate {
+    pub fn new(predicate: &str) -> Result<JsonPathPredicate, JsonPathError> {
+        let Some((i, cmp)) = find_operator(predicate) else {
+            return Ok(JsonPathPredicate {
+                path: JsonPath::new(predicate)?,
+                comparison: None,
+            });
+        };
+
+        let literal = predicate[i + 2..].trim();
+        let value = serde_json::from_str(literal)
+            .map_err(|_| JsonPathError::InvalidLiteral(literal.to_string()))?;
+
+        Ok(JsonPathPredicate {
+            path: JsonPath::new(&predicate[..i])?,
+            comparison: Some((cmp, value)),
+        })
+    }
+
+    pub fn is_match(&self, value: &Value) -> bool {
+        let selected = self.path.select(value);
+
+        match &self.comparison {
+            None => selected
+                .iter()
+                .any(|v| !matches!(v, Value::Null | Value::Bool(false))),
+            Some((JsonComparison::Equal, literal)) => selected.contains(&literal),
+            Some((JsonComparison::NotEqual, literal)) => {
+                !selected.is_empty() && !selected.contains(&literal)
+            }
+        }
+    }
+}
+
+/// Position of the first `==` or `!=` outside of quoted keys and string literals
+fn find_operator(predicate: &str) -> Option<(usize, JsonComparison)> {
+    let bytes = predicate.as_bytes();
+    let mut quote = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match (quote, bytes[i]) {
+            (Some(_), b'\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, c @ (b'\'' | b'"')) => quote = Some(c),
+            (None, b'=') if bytes.get(i + 1) == Some(&b'=') => {
+                return Some((i, JsonComparison::Equal));
+            }
+            (None, b'!') if bytes.get(i + 1) == Some(&b'=') => {
+                return Some((i, JsonComparison::NotEqual));
+            }
+            (None, _) => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+impl fmt::Display for JsonPathPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.comparison {
+            None => write!(f, "{}", self.path),
+            Some((JsonComparison::Equal, literal)) => write!(f, "{} == {}", self.path, literal),
+            Some((JsonComparison::NotEqual, literal)) => write!(f, "{} != {}", self.path, literal),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonPathPredicate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let predicate = String::deserialize(deserializer)?;
+        JsonPathPredicate::new(&predicate).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for JsonPathPredicate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn article() -> Value {
+        json!({
+            "article": {
+                "premium": true,
+                "price-eur": "€2.00",
+                "paragraphs": [{ "text": "a" }, { "text": "b" }]
+            }
+        })
+    }
+
+    #[test]
+    fn test_select_members_and_indices() {
+        let doc = article();
+
+        let path = JsonPath::new("$.article.paragraphs[1].text").unwrap();
+        assert_eq!(path.select_first(&doc), Some(&json!("b")));
+
+        let path = JsonPath::new("$.article['price-eur']").unwrap();
+        assert_eq!(path.select_first(&doc), Some(&json!("€2.00")));
+
+        let path = JsonPath::new("$.article.missing").unwrap();
+        assert!(path.select(&doc).is_empty());
+    }
+
+    #[test]
+    fn test_select_wildcard() {
+        let doc = article();
+        let path = JsonPath::new("$.article.paragraphs[*].text").unwrap();
+
+        assert_eq!(path.select(&doc), vec![&json!("a"), &json!("b")]);
+    }
+
+    #[test]
+    fn test_for_each_mut() {
+        let mut doc = article();
+        let path = JsonPath::new("$.article.paragraphs.*.text").unwrap();
+
+        path.for_each_mut(&mut doc, |v| *v = json!(""));
+
+        assert_eq!(doc["article"]["paragraphs"][0]["text"], json!(""));
+        assert_eq!(doc["article"]["paragraphs"][1]["text"], json!(""));
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        assert!(matches!(
+            JsonPath::new("article.body"),
+            Err(JsonPathError::MissingRoot(_))
+        ));
+        assert!(matches!(
+            JsonPath::new("$.article[x]"),
+            Err(JsonPathError::InvalidSyntax { .. })
+        ));
+        assert!(matches!(
+            JsonPath::new("$..article"),
+            Err(JsonPathError::InvalidSyntax { .. })
+        ));
+    }
+
+    #[test]
+    fn test_predicate() {
+        let doc = article();
+
+        assert!(
+            JsonPathPredicate::new("$.article.premium == true")
+                .unwrap()
+                .is_match(&doc)
+        );
+        assert!(
+            !JsonPathPredicate::new("$.article.premium != true")
+                .unwrap()
+                .is_match(&doc)
+        );
+        assert!(
+            JsonPathPredicate::new("$.article.premium")
+                .unwrap()
+                .is_match(&doc)
+        );
+        assert!(
+            !JsonPathPredicate::new("$.article.missing")
+                .unwrap()
+                .is_match(&doc)
+        );
+        assert!(matches!(
+            JsonPathPredicate::new("$.article.premium == yes"),
+            Err(JsonPathError::InvalidLiteral(_))
+        ));
+    }
+
+    #[test]
+    fn test_predicate_operators_in_quotes() {
+        let doc = json!({ "a==b": { "section": "x!=y" }, "note": "say \"==\"" });
+
+        let predicate = JsonPathPredicate::new(r#"$['a==b'].section == "x!=y""#).unwrap();
+        assert_eq!(predicate.path.as_str(), "$['a==b'].section");
+        assert!(predicate.is_match(&doc));
+
+        let predicate = JsonPathPredicate::new(r#"$.note != "say \"==\"""#).unwrap();
+        assert_eq!(predicate.path.as_str(), "$.note");
+        assert!(!predicate.is_match(&doc));
+
+        assert!(
+            JsonPathPredicate::new(r#"$["a==b"].section"#)
+                .unwrap()
+                .is_match(&doc)
+        );
+    }
+}
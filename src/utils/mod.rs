@@ -1,3 +1,17 @@
+pub mod block_on;
+pub mod config_dir;
+pub mod css_selector;
 pub mod html_attribute_selector;
+pub mod json_ld;
+pub mod json_path;
+pub mod path_glob;
+pub mod timestamp;
 
+pub use block_on::block_on;
+pub use config_dir::{resolve_config_path, with_config_dir};
+pub use css_selector::{CssSelector, CssSelectorError, parse_css_selector};
 pub use html_attribute_selector::{HtmlAttributeSelector, HtmlAttributeSelectorError};
+pub use json_ld::{JsonLdError, JsonLdOffer, find_json_ld_offer};
+pub use json_path::{JsonPath, JsonPathError, JsonPathPredicate};
+pub use path_glob::PathGlob;
+pub use timestamp::{deserialize_optional_timestamp, parse_timestamp, unix_now};
@@ -0,0 +1,104 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Glob over URL paths: `**` matches across segments, `*` within one segment, `?` one character
+/// # Examples
+/// ```yaml
+/// /archive/**
+/// /news/*/comments
+/// ```
+#[derive(Debug, Clone)]
+pub struct PathGlob {
+    pattern: String,
+    regex: Regex,
+}
+
+impl PathGlob {
+    pub fn new(pattern: &str) -> PathGlob {
+        let mut regex = String::from("^");
+        let mut chars = pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                _ => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+
+        PathGlob {
+            pattern: pattern.to_string(),
+            // every non-wildcard character is escaped, so the regex is always valid
+            regex: Regex::new(&regex).expect("escaped glob is a valid regex"),
+        }
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl PartialEq for PathGlob {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl<'de> Deserialize<'de> for PathGlob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Ok(PathGlob::new(&pattern))
+    }
+}
+
+impl Serialize for PathGlob {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_star_matches_across_segments() {
+        let glob = PathGlob::new("/archive/**");
+
+        assert!(glob.is_match("/archive/2020/01/post"));
+        assert!(glob.is_match("/archive/"));
+        assert!(!glob.is_match("/archived"));
+    }
+
+    #[test]
+    fn test_single_star_and_question_mark() {
+        let glob = PathGlob::new("/news/*/page-?");
+
+        assert!(glob.is_match("/news/sports/page-1"));
+        assert!(!glob.is_match("/news/sports/today/page-1"));
+        assert!(!glob.is_match("/news/sports/page-10"));
+    }
+
+    #[test]
+    fn test_regex_characters_are_literal() {
+        let glob = PathGlob::new("/a.b/(c)");
+
+        assert!(glob.is_match("/a.b/(c)"));
+        assert!(!glob.is_match("/axb/(c)"));
+    }
+}
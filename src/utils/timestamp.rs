@@ -0,0 +1,117 @@
+use serde::{Deserialize, Deserializer};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Parse unix seconds or an RFC 3339 UTC timestamp like `2025-06-01T12:00:00Z`
+/// (a date alone like `2025-06-01` means midnight UTC)
+pub fn parse_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(seconds);
+    }
+
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+
+    if !(0..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let seconds_of_day = match time {
+        Some(time) => {
+            let time = time.strip_suffix('Z').or(time.strip_suffix("+00:00"))?;
+            let time = time.split('.').next()?;
+            let mut time_parts = time.splitn(3, ':');
+            let hours: u64 = time_parts.next()?.parse().ok()?;
+            let minutes: u64 = time_parts.next()?.parse().ok()?;
+            let seconds: u64 = time_parts.next().unwrap_or("0").parse().ok()?;
+
+            if hours > 23 || minutes > 59 || seconds > 60 {
+                return None;
+            }
+
+            hours * 3600 + minutes * 60 + seconds
+        }
+        None => 0,
+    };
+
+    let days = days_from_civil(year, month, day);
+    u64::try_from(days.checked_mul(86400)?)
+        .ok()?
+        .checked_add(seconds_of_day)
+}
+
+/// Seconds since the unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Serde helper for optional fields holding a timestamp accepted by [parse_timestamp]
+pub fn deserialize_optional_timestamp<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawTimestamp {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Option::<RawTimestamp>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(RawTimestamp::Seconds(seconds)) => Ok(Some(seconds)),
+        Some(RawTimestamp::Text(text)) => parse_timestamp(&text)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("Invalid timestamp '{}'", text))),
+    }
+}
+
+// Howard Hinnant's days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unix_seconds() {
+        assert_eq!(parse_timestamp("1700000000"), Some(1700000000));
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20Z"), Some(1700000000));
+        assert_eq!(parse_timestamp("2024-02-29"), Some(1709164800));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(parse_timestamp("2024-13-01"), None);
+        assert_eq!(parse_timestamp("2024-01-01T10:00:00+02:00"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(parse_timestamp("99999999999999-01-01"), None);
+        assert_eq!(parse_timestamp("-99999999999999-01-01"), None);
+    }
+}